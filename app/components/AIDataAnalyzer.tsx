@@ -33,6 +33,8 @@ export default function AIDataAnalyzer() {
   const [selectedAgent, setSelectedAgent] = useState<Agent | null>(null);
   const [showAgentSelector, setShowAgentSelector] = useState(true);
   const messagesEndRef = useRef<HTMLDivElement>(null);
+  // The analysis whose ai-response events are being shown; its answer message has its id
+  const pendingRequest = useRef<{ id: string; agent: Agent } | null>(null);
 
   useEffect(() => {
     loadDataFiles();
@@ -46,12 +48,26 @@ export default function AIDataAnalyzer() {
   const setupEventListeners = async () => {
     try {
       await listen("ai-response", (event) => {
-        const response = event.payload as { message: string; error?: string };
-        if (response.error) {
-          addMessage("assistant", `Error: ${response.error}`, selectedAgent);
-        } else {
-          addMessage("assistant", response.message, selectedAgent);
+        const response = event.payload as {
+          request_id?: string;
+          message?: string;
+          error?: string;
+          delta?: string;
+        };
+        // Events of other requests (an earlier question, another window) are not ours
+        const pending = pendingRequest.current;
+        if (!pending || response.request_id !== pending.id) return;
+        // Streamed chunks grow the answer; the final event replaces it with the full message
+        if (response.delta !== undefined) {
+          const delta = response.delta;
+          updateStreamedMessage(pending, (content) => content + delta);
+          return;
         }
+        const answer = response.error
+          ? `Error: ${response.error}`
+          : response.message ?? "";
+        updateStreamedMessage(pending, () => answer);
+        pendingRequest.current = null;
         setIsLoading(false);
       });
     } catch (error) {
@@ -83,6 +99,27 @@ export default function AIDataAnalyzer() {
     setMessages((prev) => [...prev, newMessage]);
   };
 
+  const updateStreamedMessage = (
+    pending: { id: string; agent: Agent },
+    update: (content: string) => string
+  ) => {
+    setMessages((prev) => {
+      if (!prev.some((m) => m.id === pending.id)) {
+        const message: Message = {
+          id: pending.id,
+          role: "assistant",
+          content: update(""),
+          timestamp: new Date(),
+          agent: pending.agent,
+        };
+        return [...prev, message];
+      }
+      return prev.map((m) =>
+        m.id === pending.id ? { ...m, content: update(m.content) } : m
+      );
+    });
+  };
+
   const scrollToBottom = () => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
   };
@@ -103,6 +140,9 @@ export default function AIDataAnalyzer() {
     addMessage("user", userMessage, selectedAgent);
     setIsLoading(true);
 
+    const requestId = crypto.randomUUID();
+    pendingRequest.current = { id: requestId, agent: selectedAgent };
+
     try {
       await invoke("analyze_data_with_ai", {
         message: userMessage,
@@ -111,9 +151,11 @@ export default function AIDataAnalyzer() {
             ? selectedFiles
             : dataFiles.map((f) => f.filePath),
         agentType: selectedAgent.id,
+        requestId,
       });
     } catch (error) {
       console.error("Failed to send message to AI:", error);
+      pendingRequest.current = null;
       addMessage(
         "assistant",
         "Sorry, I encountered an error. Please try again.",
@@ -56,6 +56,8 @@ export default function RAGDataAnalyzer() {
   );
   const [showRAGContext, setShowRAGContext] = useState(false);
   const messagesEndRef = useRef<HTMLDivElement>(null);
+  // The analysis whose ai-response events are being shown; its answer message has its id
+  const pendingRequest = useRef<{ id: string; agent: Agent } | null>(null);
 
   useEffect(() => {
     loadDataFiles();
@@ -80,12 +82,26 @@ export default function RAGDataAnalyzer() {
   const setupEventListeners = async () => {
     try {
       await listen("ai-response", (event) => {
-        const response = event.payload as { message: string; error?: string };
-        if (response.error) {
-          addMessage("assistant", `Error: ${response.error}`, selectedAgent);
-        } else {
-          addMessage("assistant", response.message, selectedAgent);
+        const response = event.payload as {
+          request_id?: string;
+          message?: string;
+          error?: string;
+          delta?: string;
+        };
+        // Events of other requests (an earlier question, another window) are not ours
+        const pending = pendingRequest.current;
+        if (!pending || response.request_id !== pending.id) return;
+        // Streamed chunks grow the answer; the final event replaces it with the full message
+        if (response.delta !== undefined) {
+          const delta = response.delta;
+          updateStreamedMessage(pending, (content) => content + delta);
+          return;
         }
+        const answer = response.error
+          ? `Error: ${response.error}`
+          : response.message ?? "";
+        updateStreamedMessage(pending, () => answer);
+        pendingRequest.current = null;
         setIsLoading(false);
       });
     } catch (error) {
@@ -121,6 +137,27 @@ export default function RAGDataAnalyzer() {
     setMessages((prev) => [...prev, newMessage]);
   };
 
+  const updateStreamedMessage = (
+    pending: { id: string; agent: Agent },
+    update: (content: string) => string
+  ) => {
+    setMessages((prev) => {
+      if (!prev.some((m) => m.id === pending.id)) {
+        const message: Message = {
+          id: pending.id,
+          role: "assistant",
+          content: update(""),
+          timestamp: new Date(),
+          agent: pending.agent,
+        };
+        return [...prev, message];
+      }
+      return prev.map((m) =>
+        m.id === pending.id ? { ...m, content: update(m.content) } : m
+      );
+    });
+  };
+
   const scrollToBottom = () => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
   };
@@ -159,6 +196,8 @@ export default function RAGDataAnalyzer() {
     } catch (error) {
       console.error("Failed to query RAG system:", error);
       // Fallback to regular AI analysis
+      const requestId = crypto.randomUUID();
+      pendingRequest.current = { id: requestId, agent: selectedAgent };
       try {
         await invoke("analyze_data_with_ai", {
           message: userMessage,
@@ -167,9 +206,11 @@ export default function RAGDataAnalyzer() {
               ? selectedFiles
               : dataFiles.map((f) => f.filePath),
           agentType: selectedAgent.id,
+          requestId,
         });
       } catch (fallbackError) {
         console.error("Fallback AI analysis failed:", fallbackError);
+        pendingRequest.current = null;
         addMessage(
           "assistant",
           "Sorry, I encountered an error. Please try again.",
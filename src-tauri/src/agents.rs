@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::llm::CompletionOptions;
+use crate::rag::RAGFilters;
+
+const AGENTS_FILE_NAME: &str = "agents.json";
+// Used for agent types that are not in the registry
+const DEFAULT_AGENT: &str = "default";
+
+const PRODUCTIVITY_PROMPT: &str = "You are a productivity AI assistant analyzing digital activity data. Focus on time management, work efficiency, and productive habits. Provide actionable insights about productivity patterns and suggest improvements.";
+
+const APP_USAGE_PROMPT: &str = "You are an app usage AI assistant analyzing digital activity data. Focus on application usage patterns, time spent in different apps, and digital behavior analysis. Provide insights about app usage trends and habits.";
+
+const DATA_INSIGHTS_PROMPT: &str = "You are a data insights AI assistant analyzing digital activity data. Focus on finding interesting patterns, trends, and correlations in the data. Provide analytical insights and data-driven observations.";
+
+const CONVERSATION_PROMPT: &str = "You are a personal AI assistant having a natural conversation with the user. You have access to their digital activity data including screenshots, voice recordings, and app usage patterns. 
+
+Your personality:
+- Warm, friendly, and conversational
+- Speak naturally as if talking to a friend
+- Show genuine interest in their digital life and activities
+- Be helpful, supportive, and encouraging
+- Ask follow-up questions when appropriate
+- Use casual, natural language
+- Share personal insights about their patterns and habits
+- Be empathetic and understanding
+
+When discussing their data:
+- Reference their actual activities naturally
+- Point out interesting patterns in their routine
+- Suggest helpful observations about their productivity
+- Ask about their goals and help them achieve them
+- Be conversational about their app usage and online behavior
+- Offer gentle suggestions for improvement when relevant
+
+Always maintain a natural conversation flow. You're their personal AI companion who understands their digital life!";
+
+const DEFAULT_PROMPT: &str = "You are a helpful AI assistant analyzing digital activity data. Provide clear, insightful analysis based on the provided context.";
+
+/// An assistant personality: how it is prompted, which model answers and what it retrieves
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Agent {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub system_prompt: String,
+    /// Provider tried first; the rest of the fallback chain still backs it up
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Model for the preferred provider
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Retrieval filters applied within the requested time range
+    #[serde(default)]
+    pub filters: RAGFilters,
+}
+
+impl Agent {
+    fn builtin(name: &str, description: &str, system_prompt: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            system_prompt: system_prompt.to_string(),
+            provider: None,
+            model: None,
+            temperature: None,
+            max_tokens: None,
+            filters: RAGFilters::default(),
+        }
+    }
+
+    pub fn completion_options(&self) -> CompletionOptions {
+        CompletionOptions {
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Agent name cannot be empty".to_string());
+        }
+        if self.system_prompt.trim().is_empty() {
+            return Err(format!("Agent {} needs a system prompt", self.name));
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(format!(
+                    "Temperature of agent {} must be between 0 and 2",
+                    self.name
+                ));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err(format!("Max tokens of agent {} must be above 0", self.name));
+        }
+        Ok(())
+    }
+}
+
+fn builtin_agents() -> Vec<Agent> {
+    vec![
+        Agent::builtin(
+            "productivity",
+            "Time management, work efficiency and productive habits",
+            PRODUCTIVITY_PROMPT,
+        ),
+        Agent::builtin(
+            "app_usage",
+            "Which apps you use, for how long and how that changes",
+            APP_USAGE_PROMPT,
+        ),
+        Agent::builtin(
+            "data_insights",
+            "Patterns, trends and correlations across your activity",
+            DATA_INSIGHTS_PROMPT,
+        ),
+        Agent::builtin(
+            "conversation",
+            "A friendly chat about your day",
+            CONVERSATION_PROMPT,
+        ),
+        Agent::builtin(
+            "default",
+            "General analysis when no agent is picked",
+            DEFAULT_PROMPT,
+        ),
+    ]
+}
+
+fn registry_path() -> Result<PathBuf, String> {
+    Ok(crate::paths::get_app_config_dir()?.join(AGENTS_FILE_NAME))
+}
+
+/// Every agent, in the order they were added; the built-in ones until the registry is first saved
+pub fn list_agents() -> Vec<Agent> {
+    let path = match registry_path() {
+        Ok(path) => path,
+        Err(_) => return builtin_agents(),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            println!("[AGENTS] Ignoring invalid {}: {}", path.display(), e);
+            builtin_agents()
+        }),
+        Err(_) => builtin_agents(),
+    }
+}
+
+pub fn get_agent(name: &str) -> Result<Agent, String> {
+    list_agents()
+        .into_iter()
+        .find(|agent| agent.name == name)
+        .ok_or_else(|| format!("No agent named {}", name))
+}
+
+/// The agent to answer as; unknown agent types get the default agent
+pub fn resolve(agent_type: &str) -> Agent {
+    let agents = list_agents();
+    let found = agents
+        .iter()
+        .find(|agent| agent.name == agent_type)
+        .or_else(|| agents.iter().find(|agent| agent.name == DEFAULT_AGENT));
+    match found {
+        Some(agent) => agent.clone(),
+        None => Agent::builtin(DEFAULT_AGENT, "", DEFAULT_PROMPT),
+    }
+}
+
+/// Add an agent, or replace the one with the same name
+pub fn save_agent(agent: Agent) -> Result<(), String> {
+    agent.validate()?;
+    let mut agents = list_agents();
+    match agents
+        .iter_mut()
+        .find(|existing| existing.name == agent.name)
+    {
+        Some(existing) => *existing = agent,
+        None => agents.push(agent),
+    }
+    save_registry(&agents)
+}
+
+pub fn delete_agent(name: &str) -> Result<(), String> {
+    if name == DEFAULT_AGENT {
+        return Err("The default agent cannot be deleted".to_string());
+    }
+    let mut agents = list_agents();
+    let before = agents.len();
+    agents.retain(|agent| agent.name != name);
+    if agents.len() == before {
+        return Err(format!("No agent named {}", name));
+    }
+    save_registry(&agents)
+}
+
+fn save_registry(agents: &[Agent]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(agents)
+        .map_err(|e| format!("Failed to serialize agents: {}", e))?;
+    fs::write(registry_path()?, content).map_err(|e| format!("Failed to save agents: {}", e))
+}
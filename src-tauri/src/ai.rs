@@ -1,30 +1,43 @@
-use reqwest::Client;
 use std::sync::{Arc, Mutex};
-use tokio::runtime::Runtime;
 
-use crate::rag::{ingest_sql_data_rag, query_rag_system, RAGQuery};
-use crate::types::{OpenAIMessage, OpenAIRequest, OpenAIResponse};
+use crate::agents::Agent;
+use crate::budget::ContextBudget;
+use crate::llm::{complete_with_fallback, http_client, CompletionOptions, ProviderAnswer};
+use crate::rag::{
+    ingest_sql_data_rag, retrieve_rag_context, DataChunk, RAGFilters, RAGQuery, RetrievalMode,
+    SqlIngestMode,
+};
+use crate::time_expr::TimeWindow;
+use crate::trace::QueryTrace;
+
+// Default system prompt for general analysis requests
+pub const ASSISTANT_SYSTEM_PROMPT: &str = "You are a friendly, personal AI assistant who helps analyze your digital life through ScreenPipe data. You have access to your screenshots (OCR text), voice recordings (transcriptions), and app usage patterns. 
+
+Your personality:
+- Warm, conversational, and genuinely helpful
+- Use 'you' and speak directly to the person
+- Show empathy and understanding of their digital habits
+- Be encouraging and positive about their productivity
+- Ask follow-up questions when relevant
+- Use casual language like 'looks like', 'I can see', 'it seems'
+- Share insights that feel personal and meaningful
+
+When analyzing data:
+- Look for patterns in their daily routine
+- Identify productivity trends and time management
+- Notice what apps/websites they use most
+- Understand their work style and preferences
+- Find interesting insights about their digital behavior
+- Suggest improvements or observations gently
+
+Always be supportive and make them feel understood. You're here to help them understand their digital life better!";
 
 // Global state for storing selected model
 static SELECTED_MODEL: once_cell::sync::Lazy<Arc<Mutex<Option<String>>>> =
     once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(None)));
 
-// Ollama API request structure
-#[derive(serde::Serialize)]
-struct OllamaRequest {
-    model: String,
-    prompt: String,
-    stream: bool,
-}
-
-// Ollama API response structure
-#[derive(serde::Deserialize)]
-struct OllamaResponse {
-    response: String,
-    done: bool,
-    #[serde(rename = "model")]
-    model_name: String,
-}
+/// Receives each piece of a streamed answer as it arrives
+pub type DeltaSink<'a> = &'a mut (dyn FnMut(&str) + Send);
 
 // Function to set the selected model
 pub fn set_selected_model(model: &str) {
@@ -51,74 +64,25 @@ pub fn clear_selected_model() {
     }
 }
 
-pub fn call_ollama(prompt: &str) -> Result<String, String> {
-    println!("[OLLAMA] DEBUG: call_ollama started");
-    // Get the selected model or use default
-    let model = get_selected_model().unwrap_or_else(|| "gemma3n:latest".to_string());
-    println!("[OLLAMA] DEBUG: Using model: {}", model);
-    call_ollama_with_model(prompt, &model)
-}
-
-// Use HTTP API instead of CLI
+/// Generate with a specific model on the configured Ollama server, without a system prompt
 pub async fn call_ollama_with_model_async(prompt: &str, model: &str) -> Result<String, String> {
-    println!("[OLLAMA] DEBUG: call_ollama_with_model_async started");
-    println!("[OLLAMA] DEBUG: Model: {}", model);
-    println!("[OLLAMA] DEBUG: Prompt length: {} characters", prompt.len());
-
-    let request_body = OllamaRequest {
-        model: model.to_string(),
-        prompt: prompt.to_string(),
-        stream: false,
-    };
-
-    let client = reqwest::Client::new();
-    let response = client
-        .post("http://localhost:11434/api/generate")
-        .header("Content-Type", "application/json")
-        .timeout(std::time::Duration::from_secs(300))
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request to Ollama: {}", e))?;
-
-    if !response.status().is_success() {
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        println!("[OLLAMA] DEBUG: Ollama API error: {}", error_text);
-        return Err(format!("Ollama API error: {}", error_text));
-    }
-
-    let response_body: OllamaResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
-
-    println!(
-        "[OLLAMA] DEBUG: Ollama API response length: {} characters",
-        response_body.response.len()
-    );
-    Ok(response_body.response.trim().to_string())
+    Ok(crate::llm::ollama_provider(model)
+        .complete(None, prompt)
+        .await?)
 }
 
-// Synchronous wrapper for compatibility
-pub fn call_ollama_with_model(prompt: &str, model: &str) -> Result<String, String> {
-    let rt =
-        tokio::runtime::Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
-    rt.block_on(call_ollama_with_model_async(prompt, model))
+// Compute embeddings for a batch of texts with a local Ollama embedding model
+pub async fn get_ollama_embeddings(texts: &[String], model: &str) -> Result<Vec<Vec<f32>>, String> {
+    Ok(crate::llm::ollama_provider(model).embed(texts).await?)
 }
 
 // Function to check if Ollama is running
-pub fn check_ollama_status() -> Result<bool, String> {
-    use reqwest::blocking::Client as BlockingClient;
-
-    let client = BlockingClient::new();
-
-    let response = client
-        .get("http://localhost:11434/api/tags")
+pub async fn check_ollama_status() -> Result<bool, String> {
+    let response = http_client()
+        .get(format!("{}/api/tags", crate::llm::ollama_base_url()))
         .timeout(std::time::Duration::from_secs(5)) // 5 second timeout
-        .send();
+        .send()
+        .await;
 
     match response {
         Ok(resp) => Ok(resp.status().is_success()),
@@ -126,364 +90,38 @@ pub fn check_ollama_status() -> Result<bool, String> {
     }
 }
 
-pub fn call_openai(prompt: &str) -> Result<String, String> {
-    // Use the provided key if present, otherwise check env
-    let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "".to_string());
-    // Set env for future calls
-    std::env::set_var("OPENAI_API_KEY", &api_key);
-
-    use reqwest::blocking::Client as BlockingClient;
-
-    let client = BlockingClient::new();
-
-    let request_body = OpenAIRequest {
-        model: "gpt-4o-mini".to_string(),
-        messages: vec![
-            OpenAIMessage {
-                role: "system".to_string(),
-                content: "You are a friendly, personal AI assistant who helps analyze your digital life through ScreenPipe data. You have access to your screenshots (OCR text), voice recordings (transcriptions), and app usage patterns. 
-
-Your personality:
-- Warm, conversational, and genuinely helpful
-- Use 'you' and speak directly to the person
-- Show empathy and understanding of their digital habits
-- Be encouraging and positive about their productivity
-- Ask follow-up questions when relevant
-- Use casual language like 'looks like', 'I can see', 'it seems'
-- Share insights that feel personal and meaningful
-
-When analyzing data:
-- Look for patterns in their daily routine
-- Identify productivity trends and time management
-- Notice what apps/websites they use most
-- Understand their work style and preferences
-- Find interesting insights about their digital behavior
-- Suggest improvements or observations gently
-
-Always be supportive and make them feel understood. You're here to help them understand their digital life better!".to_string(),
-            },
-            OpenAIMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            },
-        ],
-        max_tokens: 2000,
-        temperature: 0.7,
-    };
-
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
-
-    if !response.status().is_success() {
-        let error_text = response
-            .text()
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("OpenAI API error: {}", error_text));
-    }
-
-    let response_body = response
-        .json::<OpenAIResponse>()
-        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
-
-    if response_body.choices.is_empty() {
-        return Err("No response from OpenAI".to_string());
-    }
-
-    Ok(response_body.choices[0].message.content.clone())
-}
-
-pub async fn call_openai_async(prompt: &str) -> Result<String, String> {
-    // Use the provided key if present, otherwise check env
-    let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "".to_string());
-    // Set env for future calls
-    std::env::set_var("OPENAI_API_KEY", &api_key);
-
-    use reqwest::Client;
-
-    let client = Client::new();
-
-    let request_body = OpenAIRequest {
-        model: "gpt-4o-mini".to_string(),
-        messages: vec![
-            OpenAIMessage {
-                role: "system".to_string(),
-                content: "You are a friendly, personal AI assistant who helps analyze your digital life through ScreenPipe data. You have access to your screenshots (OCR text), voice recordings (transcriptions), and app usage patterns. 
-
-Your personality:
-- Warm, conversational, and genuinely helpful
-- Use 'you' and speak directly to the person
-- Show empathy and understanding of their digital habits
-- Be encouraging and positive about their productivity
-- Ask follow-up questions when relevant
-- Use casual language like 'looks like', 'I can see', 'it seems'
-- Share insights that feel personal and meaningful
-
-When analyzing data:
-- Look for patterns in their daily routine
-- Identify productivity trends and time management
-- Notice what apps/websites they use most
-- Understand their work style and preferences
-- Find interesting insights about their digital behavior
-- Suggest improvements or observations gently
-
-Always be supportive and make them feel understood. You're here to help them understand their digital life better!".to_string(),
-            },
-            OpenAIMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            },
-        ],
-        max_tokens: 2000,
-        temperature: 0.7,
-    };
-
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
-
-    if !response.status().is_success() {
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("OpenAI API error: {}", error_text));
-    }
-
-    let response_body = response
-        .json::<OpenAIResponse>()
-        .await
-        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
-
-    if response_body.choices.is_empty() {
-        return Err("No response from OpenAI".to_string());
-    }
-
-    Ok(response_body.choices[0].message.content.clone())
-}
-
-pub fn call_openai_with_agent(prompt: &str, agent_type: &str) -> Result<String, String> {
-    println!("[OPENAI] DEBUG: call_openai_with_agent started");
-    println!("[OPENAI] DEBUG: Agent type: {}", agent_type);
-    println!("[OPENAI] DEBUG: Prompt length: {} characters", prompt.len());
-    // Use the provided key if present, otherwise check env
-    let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "".to_string());
-    std::env::set_var("OPENAI_API_KEY", &api_key);
-
-    println!("[OPENAI] DEBUG: OpenAI API key retrieved");
-
-    // For now, let's skip OpenAI and go directly to Ollama to avoid runtime conflicts
-    println!("[OPENAI] DEBUG: Skipping OpenAI due to runtime conflicts, will use Ollama instead");
-    Err("OpenAI temporarily disabled due to runtime conflicts".to_string())
+pub async fn call_ai_async(prompt: &str) -> Result<ProviderAnswer, String> {
+    complete_with_fallback(
+        Some(ASSISTANT_SYSTEM_PROMPT),
+        prompt,
+        &CompletionOptions::default(),
+        None,
+    )
+    .await
 }
 
-pub async fn call_openai_with_agent_async(
+async fn call_ai_stream_with_system(
+    system_content: &str,
     prompt: &str,
-    agent_type: &str,
-) -> Result<String, String> {
-    println!("[OPENAI] DEBUG: call_openai_with_agent_async started");
-    println!("[OPENAI] DEBUG: Agent type: {}", agent_type);
-    println!("[OPENAI] DEBUG: Prompt length: {} characters", prompt.len());
-
-    // Use the provided key if present, otherwise check env
-    let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "".to_string());
-    std::env::set_var("OPENAI_API_KEY", &api_key);
-
-    println!("[OPENAI] DEBUG: OpenAI API key retrieved");
-
-    use reqwest::Client;
-
-    let client = Client::new();
-
-    // Customize system prompt based on agent type
-    let system_content = match agent_type {
-        "productivity" => "You are a productivity AI assistant analyzing digital activity data. Focus on time management, work efficiency, and productive habits. Provide actionable insights about productivity patterns and suggest improvements.",
-        "app_usage" => "You are an app usage AI assistant analyzing digital activity data. Focus on application usage patterns, time spent in different apps, and digital behavior analysis. Provide insights about app usage trends and habits.",
-        "data_insights" => "You are a data insights AI assistant analyzing digital activity data. Focus on finding interesting patterns, trends, and correlations in the data. Provide analytical insights and data-driven observations.",
-        "conversation" => "You are a personal AI assistant having a natural conversation with the user. You have access to their digital activity data including screenshots, voice recordings, and app usage patterns. 
-
-Your personality:
-- Warm, friendly, and conversational
-- Speak naturally as if talking to a friend
-- Show genuine interest in their digital life and activities
-- Be helpful, supportive, and encouraging
-- Ask follow-up questions when appropriate
-- Use casual, natural language
-- Share personal insights about their patterns and habits
-- Be empathetic and understanding
-
-When discussing their data:
-- Reference their actual activities naturally
-- Point out interesting patterns in their routine
-- Suggest helpful observations about their productivity
-- Ask about their goals and help them achieve them
-- Be conversational about their app usage and online behavior
-- Offer gentle suggestions for improvement when relevant
-
-Always maintain a natural conversation flow. You're their personal AI companion who understands their digital life!",
-        _ => "You are a helpful AI assistant analyzing digital activity data. Provide clear, insightful analysis based on the provided context."
-    };
-
-    let request_body = OpenAIRequest {
-        model: "gpt-4o-mini".to_string(),
-        messages: vec![
-            OpenAIMessage {
-                role: "system".to_string(),
-                content: system_content.to_string(),
-            },
-            OpenAIMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            },
-        ],
-        max_tokens: 2000,
-        temperature: 0.7,
-    };
-
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request to OpenAI: {}", e))?;
-
-    if !response.status().is_success() {
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("OpenAI API error: {}", error_text));
-    }
-
-    let response_body = response
-        .json::<OpenAIResponse>()
-        .await
-        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
-
-    if response_body.choices.is_empty() {
-        return Err("No response from OpenAI".to_string());
-    }
-
-    Ok(response_body.choices[0].message.content.clone())
-}
-
-pub fn call_ai(prompt: &str) -> Result<String, String> {
-    // Try OpenAI first, fallback to Ollama
-    match call_openai(prompt) {
-        Ok(response) => Ok(response),
-        Err(openai_error) => {
-            println!("OpenAI failed: {}. Falling back to Ollama...", openai_error);
-            call_ollama(prompt)
-        }
-    }
-}
-
-pub async fn call_ai_async(prompt: &str) -> Result<String, String> {
-    // Try OpenAI first, fallback to Ollama (async version)
-    match call_openai_async(prompt).await {
-        Ok(response) => Ok(response),
-        Err(openai_error) => {
-            println!("OpenAI failed: {}. Falling back to Ollama...", openai_error);
-            // Get the selected model or use default
-            let model = get_selected_model().unwrap_or_else(|| "gemma3n:latest".to_string());
-            call_ollama_with_model_async(prompt, &model).await
-        }
-    }
+    options: &CompletionOptions,
+    on_delta: DeltaSink<'_>,
+) -> Result<ProviderAnswer, String> {
+    complete_with_fallback(Some(system_content), prompt, options, Some(on_delta)).await
 }
 
-pub fn call_ai_with_agent(prompt: &str, agent_type: &str) -> Result<String, String> {
-    println!("[AI_AGENT] DEBUG: call_ai_with_agent started");
-    println!("[AI_AGENT] DEBUG: Agent type: {}", agent_type);
-    println!(
-        "[AI_AGENT] DEBUG: Prompt length: {} characters",
-        prompt.len()
-    );
-
-    // Try OpenAI first, fallback to Ollama
-    println!("[AI_AGENT] DEBUG: About to try OpenAI first");
-    match call_openai_with_agent(prompt, agent_type) {
-        Ok(response) => {
-            println!(
-                "[AI_AGENT] DEBUG: OpenAI succeeded, response length: {}",
-                response.len()
-            );
-            Ok(response)
-        }
-        Err(openai_error) => {
-            println!(
-                "[AI_AGENT] DEBUG: OpenAI failed: {}. Falling back to Ollama...",
-                openai_error
-            );
-            println!("[AI_AGENT] DEBUG: About to call Ollama");
-            let ollama_result = call_ollama(prompt);
-            match &ollama_result {
-                Ok(response) => {
-                    println!(
-                        "[AI_AGENT] DEBUG: Ollama succeeded, response length: {}",
-                        response.len()
-                    );
-                }
-                Err(e) => {
-                    println!("[AI_AGENT] DEBUG: Ollama failed: {}", e);
-                }
-            }
-            ollama_result
-        }
-    }
-}
-
-pub async fn call_ai_with_agent_async(prompt: &str, agent_type: &str) -> Result<String, String> {
-    println!("[AI_AGENT] DEBUG: call_ai_with_agent_async started");
-    println!("[AI_AGENT] DEBUG: Agent type: {}", agent_type);
-    println!(
-        "[AI_AGENT] DEBUG: Prompt length: {} characters",
-        prompt.len()
-    );
-
-    // Try OpenAI first, fallback to Ollama (async version)
-    println!("[AI_AGENT] DEBUG: About to try OpenAI first");
-    match call_openai_with_agent_async(prompt, agent_type).await {
-        Ok(response) => {
-            println!(
-                "[AI_AGENT] DEBUG: OpenAI succeeded, response length: {}",
-                response.len()
-            );
-            Ok(response)
-        }
-        Err(openai_error) => {
-            println!(
-                "[AI_AGENT] DEBUG: OpenAI failed: {}. Falling back to Ollama...",
-                openai_error
-            );
-            println!("[AI_AGENT] DEBUG: About to call Ollama");
-            // Get the selected model or use default
-            let model = get_selected_model().unwrap_or_else(|| "gemma3n:latest".to_string());
-            let ollama_result = call_ollama_with_model_async(prompt, &model).await;
-            match &ollama_result {
-                Ok(response) => {
-                    println!(
-                        "[AI_AGENT] DEBUG: Ollama succeeded, response length: {}",
-                        response.len()
-                    );
-                }
-                Err(e) => {
-                    println!("[AI_AGENT] DEBUG: Ollama failed: {}", e);
-                }
-            }
-            ollama_result
-        }
-    }
+/// Stream an answer as the given agent: its system prompt, preferred provider and model settings
+pub async fn call_ai_with_agent_stream(
+    prompt: &str,
+    agent: &Agent,
+    on_delta: DeltaSink<'_>,
+) -> Result<ProviderAnswer, String> {
+    call_ai_stream_with_system(
+        &agent.system_prompt,
+        prompt,
+        &agent.completion_options(),
+        on_delta,
+    )
+    .await
 }
 
 pub fn prepare_ai_context(data_files: &[serde_json::Value]) -> Result<String, String> {
@@ -541,218 +179,41 @@ pub fn prepare_ai_context(data_files: &[serde_json::Value]) -> Result<String, St
     Ok(context_parts.join("\n"))
 }
 
-// Time range enum for AI analysis
-#[derive(Debug, Clone)]
-pub enum TimeRange {
-    Daily,
-    Weekly,
-    Monthly,
-    Yearly,
-    AllTime,
-}
-
-impl TimeRange {
-    pub fn to_sql_filter(&self) -> String {
-        match self {
-            TimeRange::Daily => "f.timestamp > datetime('now', '-1 day')".to_string(),
-            TimeRange::Weekly => "f.timestamp > datetime('now', '-7 days')".to_string(),
-            TimeRange::Monthly => "f.timestamp > datetime('now', '-30 days')".to_string(),
-            TimeRange::Yearly => "f.timestamp > datetime('now', '-1 year')".to_string(),
-            TimeRange::AllTime => "1=1".to_string(), // No time filter
-        }
-    }
-
-    fn to_string(&self) -> &'static str {
-        match self {
-            TimeRange::Daily => "daily",
-            TimeRange::Weekly => "weekly",
-            TimeRange::Monthly => "monthly",
-            TimeRange::Yearly => "yearly",
-            TimeRange::AllTime => "all time",
-        }
-    }
-}
-
-// New function to prepare AI context using SQL queries
-pub fn prepare_ai_context_sql(time_range: TimeRange) -> Result<String, String> {
-    let client = Client::new();
-
-    // Build SQL query based on time range
-    let time_filter = time_range.to_sql_filter();
-    let query = format!(
-        r#"
-        SELECT 
-            f.id AS frame_id, 
-            f.timestamp, 
-            f.name AS video_file, 
-            f.window_name, 
-            f.app_name, 
-            f.browser_url, 
-            ac.file_path AS audio_file, 
-            at.transcription, 
-            at.device, 
-            at.is_input_device, 
-            at.transcription_engine, 
-            at.start_time, 
-            at.end_time, 
-            o.text AS ocr_text, 
-            o.text_length AS ocr_text_length, 
-            COUNT(*) OVER () AS total_count 
-        FROM frames f 
-        LEFT JOIN audio_chunks ac ON f.video_chunk_id = ac.id 
-        LEFT JOIN audio_transcriptions at ON at.audio_chunk_id = ac.id 
-        LEFT JOIN ocr_text o ON o.frame_id = f.id 
-        WHERE {} 
-        ORDER BY f.timestamp DESC 
-        LIMIT 1000;
-        "#,
-        time_filter
-    );
-
-    // Create a runtime for async operations
-    let rt = Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
-
-    let response = rt
-        .block_on(async {
-            client
-                .post("http://localhost:3030/raw_sql")
-                .header("Content-Type", "application/json")
-                .timeout(std::time::Duration::from_secs(30))
-                .json(&serde_json::json!({ "query": query }))
-                .send()
-                .await
-        })
-        .map_err(|e| format!("Failed to send SQL query: {}", e))?;
-
-    if !response.status().is_success() {
-        let error_text = rt
-            .block_on(async { response.text().await })
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("SQL API error: {}", error_text));
-    }
-
-    let combined_data: Vec<serde_json::Value> = rt
-        .block_on(async { response.json().await })
-        .map_err(|e| format!("Failed to parse SQL response: {}", e))?;
-
-    // Build context summary
-    let mut context_parts = Vec::new();
-
-    // Add time range header
-    context_parts.push(format!("Time Period: {}", time_range.to_string()));
-
-    // Process combined data
-    if !combined_data.is_empty() {
-        // Extract OCR texts
-        let ocr_texts: Vec<String> = combined_data
-            .iter()
-            .filter_map(|item| item["ocr_text"].as_str())
-            .filter(|text| !text.is_empty())
-            .take(50) // Limit to first 50 items for context
-            .map(|s| s.to_string())
-            .collect();
-
-        if !ocr_texts.is_empty() {
-            context_parts.push(format!(
-                "OCR Text Data ({} items): {}",
-                ocr_texts.len(),
-                ocr_texts.join(" | ")
-            ));
-        }
-
-        // Extract audio transcriptions
-        let audio_texts: Vec<String> = combined_data
-            .iter()
-            .filter_map(|item| item["transcription"].as_str())
-            .filter(|text| !text.is_empty())
-            .take(25) // Limit to first 25 items for context
-            .map(|s| s.to_string())
-            .collect();
-
-        if !audio_texts.is_empty() {
-            context_parts.push(format!(
-                "Audio Transcriptions ({} items): {}",
-                audio_texts.len(),
-                audio_texts.join(" | ")
-            ));
-        }
-
-        // Extract app usage patterns
-        let app_usage: std::collections::HashMap<String, u64> = combined_data
-            .iter()
-            .filter_map(|item| {
-                let app_name = item["app_name"].as_str()?;
-                if !app_name.is_empty() {
-                    Some(app_name.to_string())
-                } else {
-                    None
-                }
-            })
-            .fold(std::collections::HashMap::new(), |mut acc, app_name| {
-                *acc.entry(app_name).or_insert(0) += 1;
-                acc
-            });
-
-        if !app_usage.is_empty() {
-            let app_summary: Vec<String> = app_usage
-                .iter()
-                .map(|(app, count)| format!("{} ({} times)", app, count))
-                .take(20) // Limit to top 20 apps
-                .collect();
-
-            context_parts.push(format!("App Usage Summary: {}", app_summary.join(", ")));
-        }
-
-        // Extract website usage patterns
-        let website_usage: std::collections::HashMap<String, u64> = combined_data
-            .iter()
-            .filter_map(|item| {
-                let url = item["browser_url"].as_str()?;
-                if !url.is_empty() {
-                    // Extract domain from URL
-                    url.split("//")
-                        .nth(1)?
-                        .split("/")
-                        .next()
-                        .map(|s| s.to_string())
-                } else {
-                    None
-                }
-            })
-            .fold(std::collections::HashMap::new(), |mut acc, domain| {
-                *acc.entry(domain).or_insert(0) += 1;
-                acc
-            });
-
-        if !website_usage.is_empty() {
-            let website_summary: Vec<String> = website_usage
-                .iter()
-                .map(|(domain, count)| format!("{} ({} visits)", domain, count))
-                .take(15) // Limit to top 15 websites
-                .collect();
-
-            context_parts.push(format!(
-                "Website Usage Summary: {}",
-                website_summary.join(", ")
-            ));
-        }
-    }
-
-    Ok(context_parts.join("\n\n"))
+fn format_source_entry(chunk: &DataChunk) -> String {
+    let source_info = match chunk.metadata.source_type.as_str() {
+        "ocr" => format!(
+            "[OCR - {}]",
+            chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
+        ),
+        "audio" => format!(
+            "[Audio - {}]",
+            chunk.metadata.speaker_id.as_deref().unwrap_or("Unknown")
+        ),
+        "app_usage" => format!(
+            "[App Usage - {}]",
+            chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
+        ),
+        _ => format!("[{}]", chunk.metadata.source_type),
+    };
+    format!("{} {}", source_info, chunk.content)
 }
 
-// RAG-based context preparation function
+// RAG-based context preparation function; returns the context and the chunks it was built from.
+// `fixed_prompt` is the rest of the prompt the context goes into; chunks are packed to fit beside it
 pub async fn prepare_ai_context_rag(
     user_question: &str,
-    time_range: TimeRange,
-) -> Result<String, String> {
+    window: TimeWindow,
+    agent_filters: &RAGFilters,
+    budget: &ContextBudget,
+    fixed_prompt: &str,
+) -> Result<(String, Vec<DataChunk>, QueryTrace), String> {
     println!("[AI] prepare_ai_context_rag started");
     println!("[AI] User question: {}", user_question);
-    println!("[AI] Time range: {:?}", time_range);
+    println!("[AI] Time range: {}", window.describe());
 
     // First, ensure RAG data is ingested for this time range
-    println!("[AI] Ingesting RAG data for time range: {:?}", time_range);
-    let ingest_result = ingest_sql_data_rag(Some(time_range.to_string().to_string()), None).await;
+    let ingest_result =
+        ingest_sql_data_rag(Some(window), SqlIngestMode::Detailed, None, None).await;
     match ingest_result {
         Ok(result) => println!("[AI] RAG ingestion successful: {}", result),
         Err(e) => println!("[AI] RAG ingestion failed: {}", e),
@@ -763,6 +224,17 @@ pub async fn prepare_ai_context_rag(
         query: user_question.to_string(),
         top_k: 15,                 // Get top 100 most relevant chunks
         similarity_threshold: 0.3, // Lower threshold to get more diverse results
+        retrieval_mode: RetrievalMode::Hybrid,
+        rerank: false,
+        // Only retrieve chunks from the requested window, not everything indexed earlier
+        filters: RAGFilters {
+            start_time: Some(window.start.to_rfc3339()),
+            end_time: Some(window.end.to_rfc3339()),
+            ..agent_filters.clone()
+        },
+        collection: None,
+        include_trace: true,
+        templates: Vec::new(),
     };
 
     println!(
@@ -770,63 +242,51 @@ pub async fn prepare_ai_context_rag(
         rag_query.top_k, rag_query.similarity_threshold
     );
 
-    // Query the RAG system
-    let rag_response = query_rag_system(
-        rag_query.query,
-        Some(rag_query.top_k),
-        Some(rag_query.similarity_threshold),
-        None,
-        None,
-    )
-    .await
-    .map_err(|e| format!("RAG query failed: {}", e))?;
+    // Retrieve only; the answer is generated from the prompt this context goes into
+    let retrieval = retrieve_rag_context(&rag_query)
+        .await
+        .map_err(|e| format!("RAG query failed: {}", e))?;
 
     println!(
         "[AI] RAG system returned {} context chunks",
-        rag_response.context_chunks.len()
+        retrieval.chunks.len()
     );
 
     // Build context from RAG results
     let mut context_parts = Vec::new();
 
     // Add time range header
-    context_parts.push(format!("Time Period: {}", time_range.to_string()));
+    context_parts.push(format!("Time Period: {}", window.describe()));
+
+    // Keep the best-ranked chunks that fit next to the rest of the prompt
+    let entries: Vec<(String, String)> = retrieval
+        .chunks
+        .iter()
+        .map(|chunk| (chunk.id.clone(), format_source_entry(chunk)))
+        .collect();
+    let (kept, budget_report) = budget.pack(
+        &format!("{}\n{}", fixed_prompt, context_parts.join("\n")),
+        &entries,
+    );
+    let chunks: Vec<DataChunk> = kept.iter().map(|&i| retrieval.chunks[i].clone()).collect();
+    let scores: Vec<f32> = kept.iter().map(|&i| retrieval.scores[i]).collect();
 
     // Add RAG context chunks
-    if !rag_response.context_chunks.is_empty() {
-        let context_text = rag_response
-            .context_chunks
+    if !chunks.is_empty() {
+        let context_text = kept
             .iter()
-            .map(|chunk| {
-                let source_info = match chunk.metadata.source_type.as_str() {
-                    "ocr" => format!(
-                        "[OCR - {}]",
-                        chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
-                    ),
-                    "audio" => format!(
-                        "[Audio - {}]",
-                        chunk.metadata.speaker_id.as_deref().unwrap_or("Unknown")
-                    ),
-                    "app_usage" => format!(
-                        "[App Usage - {}]",
-                        chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
-                    ),
-                    _ => format!("[{}]", chunk.metadata.source_type),
-                };
-                format!("{} {}", source_info, chunk.content)
-            })
+            .map(|&i| entries[i].1.as_str())
             .collect::<Vec<_>>()
             .join("\n\n");
 
         context_parts.push(format!(
             "Relevant Data ({} chunks found):\n{}",
-            rag_response.context_chunks.len(),
+            chunks.len(),
             context_text
         ));
 
         // Add similarity scores info
-        let avg_score = rag_response.similarity_scores.iter().sum::<f32>()
-            / rag_response.similarity_scores.len() as f32;
+        let avg_score = scores.iter().sum::<f32>() / scores.len() as f32;
         context_parts.push(format!(
             "Relevance Score: {:.2} (average similarity)",
             avg_score
@@ -834,7 +294,7 @@ pub async fn prepare_ai_context_rag(
 
         println!(
             "[AI] Context generated with {} chunks, average relevance: {:.2}",
-            rag_response.context_chunks.len(),
+            chunks.len(),
             avg_score
         );
     } else {
@@ -848,44 +308,11 @@ pub async fn prepare_ai_context_rag(
         final_context.len()
     );
 
-    // Log the full context to a file
-    use chrono::Utc;
-    use std::fs::OpenOptions;
-    use std::io::Write;
-
-    let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-    let log_filename = format!("ai_context_{}.log", timestamp);
-    let mut log_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .append(false)
-        .open(&log_filename)
-        .map_err(|e| format!("Failed to create log file: {}", e))?;
-
-    writeln!(log_file, "=== AI CONTEXT LOG ===")
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "Timestamp: {}", Utc::now())
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "User Question: {}", user_question)
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "Time Range: {:?}", time_range)
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(
-        log_file,
-        "Context Length: {} characters",
-        final_context.len()
-    )
-    .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "").map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "=== FULL CONTEXT ===")
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "{}", final_context)
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "").map_err(|e| format!("Failed to write to log file: {}", e))?;
-    writeln!(log_file, "=== END AI CONTEXT LOG ===")
-        .map_err(|e| format!("Failed to write to log file: {}", e))?;
-
-    println!("[AI] Context logged to: {}", log_filename);
+    // The trace describes the prompt this context goes into
+    let mut trace = retrieval.trace;
+    trace.set_budget(budget_report);
+    crate::trace::record(&trace);
+
     println!(
         "[AI] First 300 chars of context: {}",
         &final_context[..final_context.len().min(300)]
@@ -894,5 +321,5 @@ pub async fn prepare_ai_context_rag(
         println!("[AI] ... (truncated)");
     }
 
-    Ok(final_context)
+    Ok((final_context, chunks, trace))
 }
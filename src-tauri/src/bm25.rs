@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+// Standard Okapi BM25 parameters
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Tokenized inverted index with BM25 scoring.
+///
+/// Documents are identified by the position they were added at, which the RAG system
+/// keeps aligned with its chunk list.
+#[derive(Clone, Default)]
+pub struct Bm25Index {
+    // term -> (document, term frequency), documents in insertion order
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    doc_lengths: Vec<u32>,
+    total_length: u64,
+}
+
+impl Bm25Index {
+    pub fn clear(&mut self) {
+        self.postings.clear();
+        self.doc_lengths.clear();
+        self.total_length = 0;
+    }
+
+    /// Index a document and return its position
+    pub fn add_document(&mut self, text: &str) -> usize {
+        let doc = self.doc_lengths.len();
+        let tokens = tokenize(text);
+
+        let mut term_counts: HashMap<String, u32> = HashMap::new();
+        for token in &tokens {
+            *term_counts.entry(token.clone()).or_insert(0) += 1;
+        }
+        for (term, tf) in term_counts {
+            self.postings.entry(term).or_default().push((doc, tf));
+        }
+
+        self.doc_lengths.push(tokens.len() as u32);
+        self.total_length += tokens.len() as u64;
+        doc
+    }
+
+    /// BM25 score of every document matching at least one query term, as (document, score)
+    pub fn search(&self, query: &str) -> Vec<(usize, f32)> {
+        if self.doc_lengths.is_empty() {
+            return Vec::new();
+        }
+
+        let doc_count = self.doc_lengths.len() as f32;
+        let avg_length = (self.total_length as f32 / doc_count).max(1.0);
+
+        let mut query_terms = tokenize(query);
+        query_terms.sort();
+        query_terms.dedup();
+
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for term in &query_terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+
+            let doc_freq = postings.len() as f32;
+            let idf = ((doc_count - doc_freq + 0.5) / (doc_freq + 0.5) + 1.0).ln();
+
+            for &(doc, tf) in postings {
+                let tf = tf as f32;
+                let length_norm = 1.0 - B + B * self.doc_lengths[doc] as f32 / avg_length;
+                *scores.entry(doc).or_insert(0.0) +=
+                    idf * tf * (K1 + 1.0) / (tf + K1 * length_norm);
+            }
+        }
+
+        scores.into_iter().collect()
+    }
+}
+
+/// Lowercase alphanumeric terms; single characters carry too little signal to index
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.chars().count() > 1)
+        .map(|token| token.to_lowercase())
+        .collect()
+}
@@ -0,0 +1,242 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::chunking::estimate_tokens;
+use crate::llm::CompletionOptions;
+
+// Largest window we ask Ollama to allocate; the KV cache for more does not fit on most laptops
+const MAX_OLLAMA_CONTEXT_TOKENS: usize = 8192;
+// For models neither Ollama nor `known_context_length` knows about
+const DEFAULT_CONTEXT_TOKENS: usize = 4096;
+// Smallest room left for the answer, however small the window
+const MIN_ANSWER_TOKENS: usize = 512;
+// `estimate_tokens` undercounts OCR noise, URLs and code, so part of the window stays unused
+const ESTIMATE_MARGIN_PERCENT: usize = 10;
+// Numbering and separators added around each packed entry
+const ENTRY_OVERHEAD_TOKENS: usize = 4;
+
+// Context length per model, resolved once per run
+static CONTEXT_LENGTHS: Lazy<Mutex<HashMap<String, usize>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Token budget of one prompt for one model
+#[derive(Debug, Clone)]
+pub struct ContextBudget {
+    pub model: String,
+    pub context_tokens: usize,
+    /// Kept free for the generated answer
+    pub answer_tokens: usize,
+}
+
+/// What went into a prompt and what had to be left out, returned with the response
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BudgetReport {
+    pub model: String,
+    pub context_tokens: usize,
+    pub answer_tokens: usize,
+    /// Estimated size of the prompt as sent
+    pub prompt_tokens: usize,
+    pub kept: usize,
+    /// Ids of ranked entries that did not fit, best ranked first
+    pub dropped_ids: Vec<String>,
+}
+
+impl ContextBudget {
+    pub async fn for_model(model: &str) -> Self {
+        let context_tokens = context_length(model).await;
+        // A quarter of the window for the answer, but never more than half of it
+        let answer_tokens = (context_tokens / 4)
+            .max(MIN_ANSWER_TOKENS)
+            .min(context_tokens / 2);
+        Self {
+            model: model.to_string(),
+            context_tokens,
+            answer_tokens,
+        }
+    }
+
+    /// Budget for the model answers are generated with. OpenAI is tried first, but prompts
+    /// must still fit the local model that takes over when it fails.
+    pub async fn for_answer_model() -> Self {
+        let model = crate::ai::get_selected_model()
+            .unwrap_or_else(|| crate::llm::DEFAULT_OLLAMA_MODEL.to_string());
+        Self::for_model(&model).await
+    }
+
+    /// Budget for a completion made with `options`: the model of the provider they pin, else
+    /// the default answer model, with their `max_tokens` kept free for the answer
+    pub async fn for_options(options: &CompletionOptions) -> Self {
+        let pinned = options.provider.as_ref().and_then(|preferred| {
+            crate::llm::provider_chain(options)
+                .into_iter()
+                .next()
+                .filter(|provider| &provider.name == preferred)
+        });
+        let mut budget = match pinned {
+            Some(provider) => Self::for_model(&provider.resolved_model()).await,
+            None => Self::for_answer_model().await,
+        };
+        if let Some(max_tokens) = options.max_tokens {
+            // Never more than half the window, as for the default reservation
+            budget.answer_tokens = (max_tokens as usize).min(budget.context_tokens / 2);
+        }
+        budget
+    }
+
+    /// Tokens left for retrieved context once the rest of the prompt and the answer are accounted for
+    pub fn available(&self, fixed_prompt: &str) -> usize {
+        let usable =
+            (self.context_tokens - self.answer_tokens) * (100 - ESTIMATE_MARGIN_PERCENT) / 100;
+        usable.saturating_sub(estimate_tokens(fixed_prompt))
+    }
+
+    /// Keep the entries that fit next to `fixed_prompt`, in rank order.
+    /// `entries` are (id, text) pairs, best ranked first; an entry too large for the space left
+    /// is skipped so smaller lower-ranked ones can still be used.
+    /// Returns the indices of the kept entries.
+    pub fn pack(
+        &self,
+        fixed_prompt: &str,
+        entries: &[(String, String)],
+    ) -> (Vec<usize>, BudgetReport) {
+        let available = self.available(fixed_prompt);
+        let mut used = 0;
+        let mut kept = Vec::new();
+        let mut dropped_ids = Vec::new();
+
+        for (i, (id, text)) in entries.iter().enumerate() {
+            let tokens = estimate_tokens(text) + ENTRY_OVERHEAD_TOKENS;
+            if used + tokens <= available {
+                used += tokens;
+                kept.push(i);
+            } else {
+                dropped_ids.push(id.clone());
+            }
+        }
+
+        if !dropped_ids.is_empty() {
+            println!(
+                "[BUDGET] {}: kept {} of {} entries ({} of {} context tokens), dropped {}",
+                self.model,
+                kept.len(),
+                entries.len(),
+                used,
+                available,
+                dropped_ids.len()
+            );
+        }
+
+        let report = BudgetReport {
+            model: self.model.clone(),
+            context_tokens: self.context_tokens,
+            answer_tokens: self.answer_tokens,
+            prompt_tokens: estimate_tokens(fixed_prompt) + used,
+            kept: kept.len(),
+            dropped_ids,
+        };
+        (kept, report)
+    }
+}
+
+/// Context window of a model in tokens. Ollama models are asked via `/api/show` and capped
+/// at what we let Ollama allocate; other models come from a table of known limits.
+pub async fn context_length(model: &str) -> usize {
+    if let Some(length) = cached_context_length(model) {
+        return length;
+    }
+
+    let length = match query_ollama_context_length(model).await {
+        Ok(length) => length.min(MAX_OLLAMA_CONTEXT_TOKENS),
+        Err(e) => {
+            println!(
+                "[BUDGET] Ollama has no context length for {}, using defaults: {}",
+                model, e
+            );
+            known_context_length(model).unwrap_or(DEFAULT_CONTEXT_TOKENS)
+        }
+    };
+
+    CONTEXT_LENGTHS
+        .lock()
+        .unwrap()
+        .insert(model.to_string(), length);
+    length
+}
+
+// Context length resolved earlier in this run, if any
+fn cached_context_length(model: &str) -> Option<usize> {
+    CONTEXT_LENGTHS.lock().unwrap().get(model).copied()
+}
+
+// Trained context length, or the model's own `num_ctx` parameter when it sets one
+async fn query_ollama_context_length(model: &str) -> Result<usize, String> {
+    let response = crate::llm::http_client()
+        .post(format!("{}/api/show", crate::llm::ollama_base_url()))
+        .timeout(std::time::Duration::from_secs(5))
+        .json(&serde_json::json!({ "model": model }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to query Ollama: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Ollama returned {}", response.status()));
+    }
+    let info: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Ollama model info: {}", e))?;
+
+    let num_ctx = info["parameters"].as_str().and_then(|parameters| {
+        parameters.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => value.parse::<usize>().ok(),
+                _ => None,
+            }
+        })
+    });
+    let trained = info["model_info"].as_object().and_then(|model_info| {
+        model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|value| value as usize)
+    });
+
+    num_ctx
+        .or(trained)
+        .ok_or_else(|| "no context length in model info".to_string())
+}
+
+fn known_context_length(model: &str) -> Option<usize> {
+    let model = model.to_lowercase();
+    // More specific names first ("gpt-4o" before "gpt-4")
+    let known: [(&str, usize); 14] = [
+        ("gpt-4o", 128_000),
+        ("gpt-4.1", 1_000_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4", 8_192),
+        ("gpt-3.5", 16_385),
+        ("gemma3n", 32_768),
+        ("gemma3", 131_072),
+        ("llama3.1", 131_072),
+        ("llama3.2", 131_072),
+        ("llama3.3", 131_072),
+        ("qwen2.5", 32_768),
+        ("mistral", 32_768),
+        ("phi3:mini", 4_096),
+        ("tinyllama", 2_048),
+    ];
+    let length = known
+        .iter()
+        .find(|(name, _)| model.contains(name))
+        .map(|(_, length)| *length)?;
+
+    // Anything not served by OpenAI runs in Ollama
+    if model.starts_with("gpt-") {
+        Some(length)
+    } else {
+        Some(length.min(MAX_OLLAMA_CONTEXT_TOKENS))
+    }
+}
@@ -0,0 +1,241 @@
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::chunking::{chunk_tokens_for_model, estimate_tokens};
+use crate::llm::{complete_with_fallback, CompletionOptions};
+
+const SESSIONS_DIR_NAME: &str = "chat_sessions";
+// Only the latest turns are needed to resolve a follow-up question
+const REWRITE_HISTORY_TURNS: usize = 6;
+const TITLE_MAX_CHARS: usize = 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatTurn {
+    pub role: ChatRole,
+    pub content: String,
+    pub timestamp: String,
+    /// Standalone query the user turn was rewritten to for retrieval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewritten_query: Option<String>,
+}
+
+/// A conversation with the assistant, persisted as JSON in the app data dir
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatSession {
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub agent_type: Option<String>,
+    #[serde(default)]
+    pub turns: Vec<ChatTurn>,
+}
+
+/// Session listing entry without the turns
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatSessionSummary {
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub turn_count: usize,
+}
+
+impl ChatSession {
+    pub fn new(title: Option<String>, agent_type: Option<String>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.unwrap_or_default(),
+            created_at: now.clone(),
+            updated_at: now,
+            agent_type,
+            turns: Vec::new(),
+        }
+    }
+
+    /// Record a completed exchange. The first question becomes the title if there is none.
+    pub fn add_exchange(&mut self, question: &str, rewritten_query: Option<String>, answer: &str) {
+        let now = Utc::now().to_rfc3339();
+        if self.title.is_empty() {
+            self.title = question.chars().take(TITLE_MAX_CHARS).collect();
+        }
+
+        self.turns.push(ChatTurn {
+            role: ChatRole::User,
+            content: question.to_string(),
+            timestamp: now.clone(),
+            rewritten_query,
+        });
+        self.turns.push(ChatTurn {
+            role: ChatRole::Assistant,
+            content: answer.to_string(),
+            timestamp: now.clone(),
+            rewritten_query: None,
+        });
+        self.updated_at = now;
+    }
+
+    /// The latest turns that fit in `max_tokens`, oldest first, formatted for a prompt
+    pub fn history_for_prompt(&self, max_tokens: usize) -> String {
+        let mut lines = Vec::new();
+        let mut used_tokens = 0;
+
+        for turn in self.turns.iter().rev() {
+            let line = format_turn(turn);
+            let tokens = estimate_tokens(&line);
+            if used_tokens + tokens > max_tokens {
+                break;
+            }
+            used_tokens += tokens;
+            lines.push(line);
+        }
+
+        lines.reverse();
+        lines.join("\n")
+    }
+
+    pub fn summary(&self) -> ChatSessionSummary {
+        ChatSessionSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            created_at: self.created_at.clone(),
+            updated_at: self.updated_at.clone(),
+            turn_count: self.turns.len(),
+        }
+    }
+
+    fn file_path(id: &str) -> Result<PathBuf, String> {
+        // Ids are generated uuids; reject anything that could escape the sessions dir
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("Invalid chat session id: {}", id));
+        }
+        Ok(sessions_dir()?.join(format!("{}.json", id)))
+    }
+
+    pub fn load(id: &str) -> Result<Self, String> {
+        let path = Self::file_path(id)?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read chat session {}: {}", id, e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse chat session {}: {}", id, e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize chat session: {}", e))?;
+        fs::write(Self::file_path(&self.id)?, content)
+            .map_err(|e| format!("Failed to save chat session: {}", e))
+    }
+
+    pub fn delete(id: &str) -> Result<(), String> {
+        fs::remove_file(Self::file_path(id)?)
+            .map_err(|e| format!("Failed to delete chat session {}: {}", id, e))
+    }
+}
+
+fn sessions_dir() -> Result<PathBuf, String> {
+    let dir = crate::paths::get_app_data_dir()?.join(SESSIONS_DIR_NAME);
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create chat sessions directory: {}", e))?;
+    }
+    Ok(dir)
+}
+
+/// Every saved session, most recently updated first
+pub fn list_sessions() -> Result<Vec<ChatSessionSummary>, String> {
+    let entries = fs::read_dir(sessions_dir()?)
+        .map_err(|e| format!("Failed to read chat sessions directory: {}", e))?;
+
+    let mut sessions = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        match ChatSession::load(id) {
+            Ok(session) => sessions.push(session.summary()),
+            Err(e) => println!("[CHAT] Skipping unreadable session: {}", e),
+        }
+    }
+
+    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(sessions)
+}
+
+/// Tokens of conversation history to include in the answer prompt for a model
+pub fn history_token_budget(model: &str) -> usize {
+    // Room for about four chunks of history next to the retrieved context
+    chunk_tokens_for_model(model) * 4
+}
+
+/// Rewrite a follow-up question into a standalone search query using the conversation so far.
+/// Resolves pronouns and implicit time references ("and the afternoon?") so retrieval does
+/// not depend on earlier turns. Falls back to the question itself if the model is unavailable.
+pub async fn rewrite_query(session: &ChatSession, question: &str) -> String {
+    if session.turns.is_empty() {
+        return question.to_string();
+    }
+
+    let start = session.turns.len().saturating_sub(REWRITE_HISTORY_TURNS);
+    let history = session.turns[start..]
+        .iter()
+        .map(format_turn)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        "Rewrite the user's latest message as a single standalone search query for their digital activity history.\n\
+         Replace pronouns and vague references with what they refer to in the conversation, and keep any time period \
+         the conversation implies (for example \"the afternoon\" of the day being discussed). \
+         The current local time is {}.\n\
+         Reply with the rewritten query only.\n\n\
+         CONVERSATION:\n{}\n\n\
+         LATEST MESSAGE: {}\n\n\
+         Standalone query:",
+        Local::now().format("%A %Y-%m-%d %H:%M"),
+        history,
+        question
+    );
+
+    match complete_with_fallback(None, &prompt, &CompletionOptions::default(), None).await {
+        Ok(answer) => {
+            let rewritten = answer.text.trim().trim_matches('"').trim();
+            if rewritten.is_empty() {
+                question.to_string()
+            } else {
+                println!("[CHAT] Rewrote '{}' as '{}'", question, rewritten);
+                rewritten.to_string()
+            }
+        }
+        Err(e) => {
+            println!(
+                "[CHAT] Query rewrite failed, using the message as is: {}",
+                e
+            );
+            question.to_string()
+        }
+    }
+}
+
+fn format_turn(turn: &ChatTurn) -> String {
+    let speaker = match turn.role {
+        ChatRole::User => "User",
+        ChatRole::Assistant => "Assistant",
+    };
+    format!("{}: {}", speaker, turn.content)
+}
@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+
+const POLICY_FILE_NAME: &str = "chunking.json";
+// Consecutive transcript segments further apart than this start a new turn
+const MAX_TURN_GAP_SECS: f64 = 30.0;
+
+/// Rough token estimate (~4 characters per token for English text)
+pub fn estimate_tokens(text: &str) -> usize {
+    (text.chars().count() + 3) / 4
+}
+
+/// Chunk size in tokens for the model that will read the chunks.
+/// Small local models lose focus on long passages, larger ones can take more per chunk.
+pub fn chunk_tokens_for_model(model: &str) -> usize {
+    let model = model.to_lowercase();
+    let small = [
+        "gemma3n",
+        ":1b",
+        ":2b",
+        ":3b",
+        "0.5b",
+        "tinyllama",
+        "phi3:mini",
+        "phi4-mini",
+    ];
+    let large = [
+        "gpt-4",
+        "gpt-3.5",
+        "llama3.1",
+        "llama3.3",
+        "qwen2.5",
+        "mistral-nemo",
+        ":70b",
+    ];
+
+    if small.iter().any(|m| model.contains(m)) {
+        192
+    } else if large.iter().any(|m| model.contains(m)) {
+        512
+    } else {
+        256
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkingStrategy {
+    /// Word windows, ignoring sentence boundaries
+    Fixed,
+    /// Whole sentences packed up to the size limit
+    Sentence,
+    /// Whole paragraphs, falling back to sentences for long ones
+    Paragraph,
+    /// Audio segments grouped by speaker turn and time; single texts are split by sentence
+    Transcript,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkingConfig {
+    pub strategy: ChunkingStrategy,
+    /// Chunk size in tokens; None sizes chunks for the selected model
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// Tokens repeated from the end of one chunk at the start of the next
+    #[serde(default)]
+    pub overlap_tokens: usize,
+}
+
+impl ChunkingConfig {
+    pub fn new(strategy: ChunkingStrategy, overlap_tokens: usize) -> Self {
+        Self {
+            strategy,
+            max_tokens: None,
+            overlap_tokens,
+        }
+    }
+
+    pub fn resolved_max_tokens(&self, model: &str) -> usize {
+        self.max_tokens
+            .unwrap_or_else(|| chunk_tokens_for_model(model))
+            .max(16)
+    }
+
+    pub fn chunker(&self, model: &str) -> Box<dyn Chunker> {
+        let max_tokens = self.resolved_max_tokens(model);
+        // Overlap larger than half a chunk would mostly repeat the previous chunk
+        let overlap_tokens = self.overlap_tokens.min(max_tokens / 2);
+
+        match self.strategy {
+            ChunkingStrategy::Fixed => Box::new(FixedChunker {
+                max_tokens,
+                overlap_tokens,
+            }),
+            ChunkingStrategy::Sentence | ChunkingStrategy::Transcript => {
+                Box::new(SentenceChunker {
+                    max_tokens,
+                    overlap_tokens,
+                })
+            }
+            ChunkingStrategy::Paragraph => Box::new(ParagraphChunker {
+                max_tokens,
+                overlap_tokens,
+            }),
+        }
+    }
+}
+
+/// Splits one text into chunk contents
+pub trait Chunker {
+    fn chunk(&self, text: &str) -> Vec<String>;
+}
+
+pub struct FixedChunker {
+    max_tokens: usize,
+    overlap_tokens: usize,
+}
+
+impl Chunker for FixedChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        word_windows(text, self.max_tokens, self.overlap_tokens)
+    }
+}
+
+pub struct SentenceChunker {
+    max_tokens: usize,
+    overlap_tokens: usize,
+}
+
+impl Chunker for SentenceChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        let units = split_sentences(text)
+            .into_iter()
+            .flat_map(|sentence| fit_unit(sentence, self.max_tokens))
+            .collect();
+        pack(units, " ", self.max_tokens, self.overlap_tokens)
+    }
+}
+
+pub struct ParagraphChunker {
+    max_tokens: usize,
+    overlap_tokens: usize,
+}
+
+impl Chunker for ParagraphChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        let mut units = Vec::new();
+        for paragraph in split_paragraphs(text) {
+            if estimate_tokens(&paragraph) <= self.max_tokens {
+                units.push(paragraph);
+            } else {
+                for sentence in split_sentences(&paragraph) {
+                    units.extend(fit_unit(sentence, self.max_tokens));
+                }
+            }
+        }
+        pack(units, "\n\n", self.max_tokens, self.overlap_tokens)
+    }
+}
+
+/// Chunking strategy per source type ("ocr", "audio", ...), persisted in the app data dir
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkingPolicy {
+    pub default: ChunkingConfig,
+    #[serde(default)]
+    pub per_source: HashMap<String, ChunkingConfig>,
+}
+
+impl Default for ChunkingPolicy {
+    fn default() -> Self {
+        let mut per_source = HashMap::new();
+        // OCR keeps the screen's line/paragraph layout
+        per_source.insert(
+            "ocr".to_string(),
+            ChunkingConfig::new(ChunkingStrategy::Paragraph, 32),
+        );
+        per_source.insert(
+            "audio".to_string(),
+            ChunkingConfig::new(ChunkingStrategy::Transcript, 0),
+        );
+
+        Self {
+            default: ChunkingConfig::new(ChunkingStrategy::Sentence, 32),
+            per_source,
+        }
+    }
+}
+
+impl ChunkingPolicy {
+    pub fn for_source(&self, source_type: &str) -> &ChunkingConfig {
+        self.per_source.get(source_type).unwrap_or(&self.default)
+    }
+
+    pub fn set(&mut self, source_type: &str, config: ChunkingConfig) {
+        if source_type == "default" {
+            self.default = config;
+        } else {
+            self.per_source.insert(source_type.to_string(), config);
+        }
+    }
+
+    fn file_path() -> Result<PathBuf, String> {
+        Ok(crate::paths::get_app_data_dir()?.join(POLICY_FILE_NAME))
+    }
+
+    /// Load the saved policy, falling back to the defaults
+    pub fn load() -> Self {
+        let path = match Self::file_path() {
+            Ok(path) => path,
+            Err(_) => return Self::default(),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                println!("[CHUNKING] Ignoring invalid {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize chunking policy: {}", e))?;
+        fs::write(Self::file_path()?, content)
+            .map_err(|e| format!("Failed to save chunking policy: {}", e))
+    }
+}
+
+/// One transcription row, as seen by the transcript grouping
+pub struct TranscriptSegment<'a> {
+    pub audio_file: Option<&'a str>,
+    pub speaker: Option<&'a str>,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    pub text: &'a str,
+}
+
+/// Group consecutive segments into speaker turns.
+/// Segments must be ordered by audio file and start time. A turn ends when the speaker
+/// or audio file changes, when the gap to the next segment exceeds `MAX_TURN_GAP_SECS`,
+/// or when adding the next segment would exceed `max_tokens`.
+pub fn group_transcript(segments: &[TranscriptSegment], max_tokens: usize) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut tokens = 0;
+
+    for (i, segment) in segments.iter().enumerate() {
+        let segment_tokens = estimate_tokens(segment.text);
+
+        if i > start {
+            let previous = &segments[i - 1];
+            let gap = match (previous.end_time, segment.start_time) {
+                (Some(end), Some(next_start)) => next_start - end,
+                _ => 0.0,
+            };
+            let new_turn = previous.speaker != segment.speaker
+                || previous.audio_file != segment.audio_file
+                || gap > MAX_TURN_GAP_SECS
+                || tokens + segment_tokens > max_tokens;
+
+            if new_turn {
+                groups.push(start..i);
+                start = i;
+                tokens = 0;
+            }
+        }
+        tokens += segment_tokens;
+    }
+
+    if start < segments.len() {
+        groups.push(start..segments.len());
+    }
+    groups
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            push_trimmed(&mut sentences, &current);
+            current.clear();
+            continue;
+        }
+        current.push(c);
+        let at_boundary =
+            matches!(c, '.' | '!' | '?') && chars.peek().map_or(true, |next| next.is_whitespace());
+        if at_boundary {
+            push_trimmed(&mut sentences, &current);
+            current.clear();
+        }
+    }
+    push_trimmed(&mut sentences, &current);
+    sentences
+}
+
+fn split_paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            push_trimmed(&mut paragraphs, &current);
+            current.clear();
+        } else {
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(line.trim());
+        }
+    }
+    push_trimmed(&mut paragraphs, &current);
+    paragraphs
+}
+
+fn push_trimmed(units: &mut Vec<String>, unit: &str) {
+    let unit = unit.trim();
+    if !unit.is_empty() {
+        units.push(unit.to_string());
+    }
+}
+
+// A sentence longer than a whole chunk is split into word windows
+fn fit_unit(unit: String, max_tokens: usize) -> Vec<String> {
+    if estimate_tokens(&unit) <= max_tokens {
+        vec![unit]
+    } else {
+        word_windows(&unit, max_tokens, 0)
+    }
+}
+
+fn word_windows(text: &str, max_tokens: usize, overlap_tokens: usize) -> Vec<String> {
+    let words = text.split_whitespace().map(|w| w.to_string()).collect();
+    pack(words, " ", max_tokens, overlap_tokens)
+}
+
+/// Greedily pack units into chunks of at most `max_tokens`, starting each chunk with the
+/// trailing units of the previous one up to `overlap_tokens`
+fn pack(
+    units: Vec<String>,
+    separator: &str,
+    max_tokens: usize,
+    overlap_tokens: usize,
+) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut current_tokens = 0;
+    // Units in `current` that were carried over from the previous chunk
+    let mut carried_count = 0;
+
+    for unit in units {
+        let unit_tokens = estimate_tokens(&unit) + 1;
+
+        if current_tokens + unit_tokens > max_tokens && current.len() > carried_count {
+            chunks.push(current.join(separator));
+
+            let mut carried = Vec::new();
+            let mut carried_tokens = 0;
+            for previous in current.iter().rev() {
+                let tokens = estimate_tokens(previous) + 1;
+                if carried_tokens + tokens > overlap_tokens {
+                    break;
+                }
+                carried_tokens += tokens;
+                carried.push(previous.clone());
+            }
+            carried.reverse();
+
+            carried_count = carried.len();
+            current = carried;
+            current_tokens = carried_tokens;
+        }
+
+        current.push(unit);
+        current_tokens += unit_tokens;
+    }
+
+    if current.len() > carried_count {
+        chunks.push(current.join(separator));
+    }
+    chunks
+}
@@ -0,0 +1,54 @@
+use crate::bm25::tokenize;
+
+// Words per shingle; three keeps word order without being too sensitive to OCR noise
+const SHINGLE_SIZE: usize = 3;
+// Fingerprints differing in at most this many of 64 bits are treated as the same screen.
+// A one-word change in a typical OCR capture flips around five bits; unrelated text about half.
+const MAX_HAMMING_DISTANCE: u32 = 8;
+
+// FNV-1a: stable across Rust releases, unlike DefaultHasher, so persisted ids stay valid
+pub fn fnv1a_hash(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// 64-bit SimHash over word shingles. Texts that share most shingles end up
+/// with fingerprints a few bits apart, so a repeated capture of a mostly static
+/// screen (a ticking clock, a blinking cursor) still matches.
+pub fn simhash(text: &str) -> u64 {
+    let tokens = tokenize(text);
+    let shingles: Vec<String> = if tokens.len() <= SHINGLE_SIZE {
+        vec![tokens.join(" ")]
+    } else {
+        tokens
+            .windows(SHINGLE_SIZE)
+            .map(|window| window.join(" "))
+            .collect()
+    };
+
+    let mut weights = [0i32; 64];
+    for shingle in &shingles {
+        let hash = fnv1a_hash(shingle);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, &weight)| weight > 0)
+        .fold(0u64, |fingerprint, (bit, _)| fingerprint | (1 << bit))
+}
+
+pub fn is_near_duplicate(a: u64, b: u64) -> bool {
+    (a ^ b).count_ones() <= MAX_HAMMING_DISTANCE
+}
@@ -1,7 +1,8 @@
+use crate::privacy::ExclusionRules;
 use crate::screenpipe::{
     fetch_screenpipe_data_with_offset, fetch_screenpipe_data_with_query_and_offset,
 };
-use crate::types::{ExportProgress, ExportState};
+use crate::types::{ExportData, ExportProgress, ExportState};
 use serde_json;
 use std::fs;
 use std::path::Path;
@@ -142,6 +143,42 @@ pub fn emit_export_progress(app_handle: &tauri::AppHandle, export_progress: &Exp
     );
 }
 
+// Drop items the privacy exclusion rules leave out of exports
+fn remove_excluded(export_data: &mut ExportData, rules: &ExclusionRules) {
+    if rules.is_empty() {
+        return;
+    }
+    let before = export_data.ocr.len() + export_data.audio.len() + export_data.ui.len();
+
+    export_data.ocr.retain(|item| {
+        !rules.excludes(
+            "ocr",
+            Some(&item.app_name),
+            Some(&item.window_name),
+            item.raw_content["browser_url"].as_str(),
+        )
+    });
+    export_data
+        .audio
+        .retain(|item| !rules.excludes("audio", item.raw_content["app_name"].as_str(), None, None));
+    export_data.ui.retain(|item| {
+        !rules.excludes(
+            "ui",
+            item.raw_content["app_name"].as_str(),
+            item.raw_content["window_name"].as_str(),
+            item.raw_content["browser_url"].as_str(),
+        )
+    });
+
+    let after = export_data.ocr.len() + export_data.audio.len() + export_data.ui.len();
+    if after < before {
+        println!(
+            "Left out {} items excluded by privacy rules",
+            before - after
+        );
+    }
+}
+
 pub async fn perform_data_export(
     app_handle: &tauri::AppHandle,
     custom_query: Option<serde_json::Value>,
@@ -178,7 +215,7 @@ pub async fn perform_data_export(
     emit_export_progress(app_handle, &export_progress);
 
     // Fetch data from ScreenPipe with offset
-    let export_data = if let Some(query) = custom_query {
+    let mut export_data = if let Some(query) = custom_query {
         emit_progress(app_handle, 10, 100, "Fetching data with custom query.....");
         fetch_screenpipe_data_with_query_and_offset(query, export_state.last_offset).await?
     } else {
@@ -203,6 +240,9 @@ pub async fn perform_data_export(
 
     emit_progress(app_handle, 50, 100, "Processing data...");
 
+    // Offsets above count every fetched item, so paging is unaffected by what gets left out
+    remove_excluded(&mut export_data, &ExclusionRules::load());
+
     // Create batch data structure
     let batch_data = serde_json::json!({
         "batch_number": export_state.batch_number,
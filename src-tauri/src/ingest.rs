@@ -0,0 +1,258 @@
+// Source adapters for RAG ingestion. Each adapter only translates its input into
+// `Capture`s; the records built from a capture, dedup and chunking are the same for
+// every source (see `rag::ingest_from`).
+use serde_json::Value;
+
+/// A source of screenpipe captures to index
+pub trait Ingestor: Sync {
+    /// Short name for logs
+    fn source_name(&self) -> &'static str;
+    fn captures(&self) -> Vec<Capture>;
+}
+
+/// One screenpipe capture, normalized from whichever source it came from.
+/// Blank or "Unknown" text fields are None.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capture {
+    pub frame_id: Option<i64>,
+    pub timestamp: Option<String>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub browser_url: Option<String>,
+    pub video_file: Option<String>,
+    pub ocr_text: Option<String>,
+    pub ocr_length: Option<u64>,
+    pub transcription: Option<String>,
+    /// Audio device, used as the speaker
+    pub device: Option<String>,
+    pub audio_file: Option<String>,
+    pub transcription_engine: Option<String>,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+/// Text to index for one source type of a capture
+pub struct CaptureRecord {
+    pub source_type: &'static str,
+    /// Text as chunked; its hash is part of the source key
+    pub text: String,
+    /// The bare transcription, for audio records
+    pub transcription: Option<String>,
+    pub metadata: Value,
+}
+
+impl Capture {
+    /// A row from screenpipe's `/raw_sql`, in the shape `ingest_sql_data_rag` selects
+    pub fn from_sql_row(row: &Value) -> Self {
+        Self {
+            frame_id: row["frame_id"].as_i64(),
+            timestamp: text(&row["timestamp"]),
+            app_name: text(&row["app_name"]),
+            window_name: text(&row["window_name"]),
+            browser_url: text(&row["browser_url"]),
+            video_file: text(&row["video_file"]),
+            ocr_text: text(&row["ocr_text"]),
+            ocr_length: row["ocr_text_length"].as_u64(),
+            transcription: text(&row["transcription"]),
+            device: text(&row["device"]),
+            audio_file: text(&row["audio_file"]),
+            transcription_engine: text(&row["transcription_engine"]),
+            start_time: row["start_time"].as_f64(),
+            end_time: row["end_time"].as_f64(),
+        }
+    }
+
+    /// The `content` of a screenpipe `/search` item. UI items are not indexed.
+    pub fn from_search_item(content_type: &str, content: &Value) -> Option<Self> {
+        match content_type.to_lowercase().as_str() {
+            "ocr" => Some(Self {
+                frame_id: content["frame_id"].as_i64(),
+                timestamp: text(&content["timestamp"]),
+                app_name: text(&content["app_name"]),
+                window_name: text(&content["window_name"]),
+                browser_url: text(&content["browser_url"]),
+                video_file: text(&content["file_path"]),
+                ocr_length: content["text"].as_str().map(|t| t.len() as u64),
+                ocr_text: text(&content["text"]),
+                ..Self::default()
+            }),
+            "audio" => Some(Self {
+                timestamp: text(&content["timestamp"]),
+                transcription: text(&content["transcription"]),
+                device: text(&content["device_name"]).or_else(|| text(&content["speaker_id"])),
+                audio_file: text(&content["file_path"]),
+                start_time: content["start_time"].as_f64(),
+                end_time: content["end_time"].as_f64(),
+                ..Self::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Records to index: OCR text, the transcription and the app activity, whichever are present.
+    /// The texts are what source keys hash, so they must not change between releases.
+    pub fn records(&self) -> Vec<CaptureRecord> {
+        let app_name = self.app_name.as_deref().unwrap_or("Unknown");
+        let mut records = Vec::new();
+
+        if let Some(ocr_text) = &self.ocr_text {
+            records.push(CaptureRecord {
+                source_type: "ocr",
+                text: format!("OCR from {}: {}", app_name, ocr_text),
+                transcription: None,
+                metadata: serde_json::json!({
+                    "app_name": app_name,
+                    "timestamp": self.timestamp,
+                    "window_name": self.window_name,
+                    "browser_url": self.browser_url.as_deref().unwrap_or(""),
+                    "frame_id": self.frame_id,
+                    "video_file": self.video_file,
+                    "source_type": "ocr",
+                    "ocr_length": self.ocr_length.unwrap_or(0)
+                }),
+            });
+        }
+
+        if let Some(transcription) = &self.transcription {
+            records.push(CaptureRecord {
+                source_type: "audio",
+                text: format!(
+                    "Audio from {} ({}): {}",
+                    app_name,
+                    self.device.as_deref().unwrap_or("Unknown"),
+                    transcription
+                ),
+                transcription: Some(transcription.clone()),
+                metadata: serde_json::json!({
+                    "app_name": app_name,
+                    "timestamp": self.timestamp,
+                    "speaker_id": self.device,
+                    "transcription_engine": self.transcription_engine.as_deref().unwrap_or("Unknown"),
+                    "start_time": self.start_time,
+                    "end_time": self.end_time,
+                    "frame_id": self.frame_id,
+                    "video_file": self.video_file,
+                    "audio_file": self.audio_file,
+                    "source_type": "audio"
+                }),
+            });
+        }
+
+        if let Some(app_name) = &self.app_name {
+            let url_info = match &self.browser_url {
+                Some(url) => format!(" - URL: {}", url),
+                None => String::new(),
+            };
+            records.push(CaptureRecord {
+                source_type: "app_usage",
+                text: format!(
+                    "App Activity: {} - Window: {} - Timestamp: {}{}",
+                    app_name,
+                    self.window_name.as_deref().unwrap_or("Unknown"),
+                    self.timestamp.as_deref().unwrap_or("Unknown"),
+                    url_info
+                ),
+                transcription: None,
+                metadata: serde_json::json!({
+                    "app_name": app_name,
+                    "timestamp": self.timestamp,
+                    "window_name": self.window_name,
+                    "browser_url": self.browser_url.as_deref().unwrap_or(""),
+                    "frame_id": self.frame_id,
+                    "video_file": self.video_file,
+                    "source_type": "app_usage"
+                }),
+            });
+        }
+
+        records
+    }
+}
+
+/// Export batch files written by `export.rs`: `ocr` and `audio` items wrapping the original
+/// `/search` content under `raw_content`, plus the older single `app_usage` object
+pub struct ExportIngestor<'a>(pub &'a [Value]);
+
+impl Ingestor for ExportIngestor<'_> {
+    fn source_name(&self) -> &'static str {
+        "export"
+    }
+
+    fn captures(&self) -> Vec<Capture> {
+        let mut captures = Vec::new();
+        for file in self.0 {
+            for item in file["ocr"].as_array().into_iter().flatten() {
+                let mut capture =
+                    Capture::from_search_item("ocr", &item["raw_content"]).unwrap_or_default();
+                // Older exports have no raw content, only the top-level fields
+                capture.frame_id = item["frame_id"].as_i64().or(capture.frame_id);
+                capture.ocr_text = text(&item["text"]).or(capture.ocr_text);
+                capture.app_name = text(&item["app_name"]).or(capture.app_name);
+                capture.window_name = text(&item["window_name"]).or(capture.window_name);
+                capture.timestamp = text(&item["timestamp"]).or(capture.timestamp);
+                if capture.ocr_length.is_none() {
+                    capture.ocr_length = capture.ocr_text.as_ref().map(|t| t.len() as u64);
+                }
+                captures.push(capture);
+            }
+
+            for item in file["audio"].as_array().into_iter().flatten() {
+                let mut capture =
+                    Capture::from_search_item("audio", &item["raw_content"]).unwrap_or_default();
+                capture.frame_id = item["frame_id"]
+                    .as_i64()
+                    .or_else(|| item["raw_content"]["frame_id"].as_i64());
+                capture.transcription = text(&item["transcription"]).or(capture.transcription);
+                capture.device = capture.device.or_else(|| text(&item["speaker_id"]));
+                captures.push(capture);
+            }
+
+            if let Some(app_name) = text(&file["app_usage"]["app_name"]) {
+                captures.push(Capture {
+                    app_name: Some(app_name),
+                    ..Capture::default()
+                });
+            }
+        }
+        captures
+    }
+}
+
+/// Rows returned by screenpipe's `/raw_sql`
+pub struct SqlRowIngestor<'a>(pub &'a [Value]);
+
+impl Ingestor for SqlRowIngestor<'_> {
+    fn source_name(&self) -> &'static str {
+        "sql"
+    }
+
+    fn captures(&self) -> Vec<Capture> {
+        self.0.iter().map(Capture::from_sql_row).collect()
+    }
+}
+
+/// Items of screenpipe `/search` responses (`{"type": ..., "content": {...}}`)
+pub struct SearchIngestor<'a>(pub &'a [Value]);
+
+impl Ingestor for SearchIngestor<'_> {
+    fn source_name(&self) -> &'static str {
+        "search"
+    }
+
+    fn captures(&self) -> Vec<Capture> {
+        self.0
+            .iter()
+            .filter_map(|item| {
+                Capture::from_search_item(item["type"].as_str().unwrap_or(""), &item["content"])
+            })
+            .collect()
+    }
+}
+
+// Screenpipe uses "" (and sometimes "Unknown") for missing text columns
+fn text(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|s| !s.trim().is_empty() && *s != "Unknown")
+        .map(|s| s.to_string())
+}
@@ -0,0 +1,911 @@
+use once_cell::sync::Lazy;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::ai::DeltaSink;
+use crate::types::{OpenAIMessage, OpenAIRequest, OpenAIResponse, OpenAIStreamChunk};
+
+const PROVIDERS_FILE_NAME: &str = "providers.json";
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+pub const DEFAULT_OLLAMA_MODEL: &str = "gemma3n:latest";
+
+// One pooled client for every model server request, so connections are reused across calls
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .build()
+        .unwrap_or_else(|_| Client::new())
+});
+
+// Failures in a row per provider name, and until when it is skipped
+static CIRCUITS: Lazy<Mutex<HashMap<String, CircuitState>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub type LlmFuture<'a> = Pin<Box<dyn Future<Output = Result<String, ProviderError>> + Send + 'a>>;
+pub type EmbedFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<Vec<f32>>, ProviderError>> + Send + 'a>>;
+
+#[derive(Debug)]
+pub struct ProviderError {
+    pub message: String,
+    /// Whether trying again can help: the server was unreachable, overloaded or rate limiting
+    pub retryable: bool,
+}
+
+impl ProviderError {
+    fn retryable(message: String) -> Self {
+        Self {
+            message,
+            retryable: true,
+        }
+    }
+}
+
+// Anything not marked retryable, like a parse error or a rejected key, fails the same way again
+impl From<String> for ProviderError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            retryable: false,
+        }
+    }
+}
+
+impl From<ProviderError> for String {
+    fn from(error: ProviderError) -> Self {
+        error.message
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A model server prompts can be sent to
+pub trait LlmProvider: Send + Sync {
+    /// Whether its `base_url` is local also picks the redaction level prompts are masked with
+    fn config(&self) -> &ProviderConfig;
+    fn model(&self) -> String {
+        self.config().resolved_model()
+    }
+    fn complete<'a>(&'a self, system: Option<&'a str>, prompt: &'a str) -> LlmFuture<'a>;
+    /// Pass each piece of the answer to `on_delta` as it arrives; returns the full answer
+    fn complete_stream<'a>(
+        &'a self,
+        system: Option<&'a str>,
+        prompt: &'a str,
+        on_delta: DeltaSink<'a>,
+    ) -> LlmFuture<'a>;
+    /// One embedding per text, in order; only servers with an embedding endpoint have one
+    fn embed<'a>(&'a self, _texts: &'a [String]) -> EmbedFuture<'a> {
+        Box::pin(
+            async move { Err(format!("{} does not serve embeddings", self.config().name).into()) },
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ProviderKind {
+    #[serde(rename = "ollama")]
+    Ollama,
+    /// Anything serving `/chat/completions`: OpenAI, LM Studio, llama.cpp server, vLLM, Azure
+    #[serde(rename = "openai_compatible")]
+    OpenAICompatible,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub kind: ProviderKind,
+    /// "http://localhost:11434" for Ollama, "https://api.openai.com/v1" for OpenAI
+    pub base_url: String,
+    /// None uses the model selected in the app
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Environment variable read for the key when `api_key` is not set
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Header the key is sent in as is ("api-key" for Azure); `Authorization: Bearer` by default
+    #[serde(default)]
+    pub api_key_header: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Added to every request URL ("api-version" for Azure)
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Extra attempts after a failure that may be temporary
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Failed requests in a row after which the provider is skipped for `circuit_cooldown_secs`
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub circuit_failure_threshold: u32,
+    #[serde(default = "default_circuit_cooldown_secs")]
+    pub circuit_cooldown_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    300
+}
+
+fn default_max_tokens() -> u32 {
+    2000
+}
+
+fn default_temperature() -> f32 {
+    0.7
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_retries() -> u32 {
+    1
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    3
+}
+
+fn default_circuit_cooldown_secs() -> u64 {
+    60
+}
+
+/// Model servers answers are generated with, tried in order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProviderSettings {
+    pub providers: Vec<ProviderConfig>,
+}
+
+impl Default for ProviderSettings {
+    fn default() -> Self {
+        Self {
+            providers: vec![
+                ProviderConfig {
+                    name: "openai".to_string(),
+                    kind: ProviderKind::OpenAICompatible,
+                    base_url: "https://api.openai.com/v1".to_string(),
+                    model: Some("gpt-4o-mini".to_string()),
+                    api_key: None,
+                    api_key_env: Some("OPENAI_API_KEY".to_string()),
+                    api_key_header: None,
+                    headers: HashMap::new(),
+                    query: HashMap::new(),
+                    timeout_secs: default_timeout_secs(),
+                    max_tokens: default_max_tokens(),
+                    temperature: default_temperature(),
+                    enabled: true,
+                    max_retries: default_max_retries(),
+                    retry_backoff_ms: default_retry_backoff_ms(),
+                    circuit_failure_threshold: default_circuit_failure_threshold(),
+                    circuit_cooldown_secs: default_circuit_cooldown_secs(),
+                },
+                ProviderConfig::ollama(),
+            ],
+        }
+    }
+}
+
+impl ProviderSettings {
+    fn file_path() -> Result<PathBuf, String> {
+        Ok(crate::paths::get_app_data_dir()?.join(PROVIDERS_FILE_NAME))
+    }
+
+    /// Load the saved providers; OpenAI then local Ollama if there are none or they cannot be read
+    pub fn load() -> Self {
+        let path = match Self::file_path() {
+            Ok(path) => path,
+            Err(_) => return Self::default(),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                println!("[AI] Ignoring invalid {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        for (index, provider) in self.providers.iter().enumerate() {
+            if provider.name.trim().is_empty() {
+                return Err(format!("Provider {} has no name", index + 1));
+            }
+            if self.providers[..index]
+                .iter()
+                .any(|earlier| earlier.name == provider.name)
+            {
+                return Err(format!("Provider name {} is used twice", provider.name));
+            }
+            if !provider.base_url.starts_with("http://")
+                && !provider.base_url.starts_with("https://")
+            {
+                return Err(format!(
+                    "Provider {} needs an http(s) base URL",
+                    provider.name
+                ));
+            }
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize providers: {}", e))?;
+        fs::write(Self::file_path()?, content)
+            .map_err(|e| format!("Failed to save providers: {}", e))
+    }
+}
+
+impl ProviderConfig {
+    /// The local Ollama server with default settings
+    pub fn ollama() -> Self {
+        Self {
+            name: "ollama".to_string(),
+            kind: ProviderKind::Ollama,
+            base_url: DEFAULT_OLLAMA_URL.to_string(),
+            model: None,
+            api_key: None,
+            api_key_env: None,
+            api_key_header: None,
+            headers: HashMap::new(),
+            query: HashMap::new(),
+            timeout_secs: default_timeout_secs(),
+            max_tokens: default_max_tokens(),
+            temperature: default_temperature(),
+            enabled: true,
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            circuit_failure_threshold: default_circuit_failure_threshold(),
+            circuit_cooldown_secs: default_circuit_cooldown_secs(),
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn LlmProvider> {
+        match self.kind {
+            ProviderKind::Ollama => Box::new(OllamaProvider {
+                config: self.clone(),
+            }),
+            ProviderKind::OpenAICompatible => Box::new(OpenAICompatibleProvider {
+                config: self.clone(),
+            }),
+        }
+    }
+
+    /// The model requests go to: its own, else the one selected in the app
+    pub fn resolved_model(&self) -> String {
+        self.model
+            .clone()
+            .or_else(crate::ai::get_selected_model)
+            .unwrap_or_else(|| DEFAULT_OLLAMA_MODEL.to_string())
+    }
+
+    // Why the provider cannot be asked at all, such as a key that was never set
+    fn unavailable_reason(&self) -> Option<String> {
+        let needs_key = self.api_key.is_some() || self.api_key_env.is_some();
+        if needs_key && self.api_key().is_none() {
+            Some("no API key set".to_string())
+        } else {
+            None
+        }
+    }
+
+    fn api_key(&self) -> Option<String> {
+        let key = match &self.api_key {
+            Some(key) => key.clone(),
+            None => std::env::var(self.api_key_env.as_deref()?).ok()?,
+        };
+        if key.is_empty() {
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    // POST with the configured timeout, key, headers and query parameters
+    fn post(&self, path: &str) -> RequestBuilder {
+        let mut request = http_client()
+            .post(self.url(path))
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .header("Content-Type", "application/json");
+        if let Some(key) = self.api_key() {
+            request = match &self.api_key_header {
+                Some(header) => request.header(header.as_str(), key),
+                None => request.bearer_auth(key),
+            };
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if !self.query.is_empty() {
+            request = request.query(&self.query);
+        }
+        request
+    }
+}
+
+/// Shared HTTP client for model servers
+pub fn http_client() -> Client {
+    HTTP_CLIENT.clone()
+}
+
+/// Per-request settings layered over the provider configuration
+#[derive(Debug, Clone, Default)]
+pub struct CompletionOptions {
+    /// Provider tried first, ahead of the configured order
+    pub provider: Option<String>,
+    /// Model for the preferred provider; model names are specific to one server
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+/// Enabled providers in the order they are tried, with `options` applied
+pub fn provider_chain(options: &CompletionOptions) -> Vec<ProviderConfig> {
+    let mut chain: Vec<ProviderConfig> = ProviderSettings::load()
+        .providers
+        .into_iter()
+        .filter(|provider| provider.enabled)
+        .collect();
+
+    if let Some(preferred) = &options.provider {
+        match chain
+            .iter()
+            .position(|provider| &provider.name == preferred)
+        {
+            Some(index) => {
+                let mut provider = chain.remove(index);
+                if options.model.is_some() {
+                    provider.model = options.model.clone();
+                }
+                chain.insert(0, provider);
+            }
+            None => println!(
+                "[AI] Preferred provider {} is not enabled, using the configured order",
+                preferred
+            ),
+        }
+    }
+
+    for provider in chain.iter_mut() {
+        if let Some(temperature) = options.temperature {
+            provider.temperature = temperature;
+        }
+        if let Some(max_tokens) = options.max_tokens {
+            provider.max_tokens = max_tokens;
+        }
+    }
+    chain
+}
+
+// First configured Ollama server, or the local default
+fn ollama_config() -> ProviderConfig {
+    ProviderSettings::load()
+        .providers
+        .into_iter()
+        .find(|provider| provider.kind == ProviderKind::Ollama)
+        .unwrap_or_else(ProviderConfig::ollama)
+}
+
+/// Base URL of the Ollama server used for generation, embeddings and model info
+pub fn ollama_base_url() -> String {
+    ollama_config().base_url.trim_end_matches('/').to_string()
+}
+
+/// The configured Ollama server with a specific model
+pub fn ollama_provider(model: &str) -> Box<dyn LlmProvider> {
+    let mut config = ollama_config();
+    config.model = Some(model.to_string());
+    config.build()
+}
+
+// Ollama API request structure
+#[derive(Serialize)]
+struct OllamaRequest {
+    model: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    // Ollama's default window is smaller than most models support; prompts are budgeted for this one
+    num_ctx: usize,
+    temperature: f32,
+    num_predict: u32,
+}
+
+// Ollama API response structure
+#[derive(Deserialize)]
+struct OllamaResponse {
+    response: String,
+}
+
+// One line of Ollama's NDJSON stream
+#[derive(Deserialize)]
+struct OllamaStreamChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+// Batch /api/embed endpoint
+#[derive(Serialize)]
+struct OllamaEmbedRequest<'a> {
+    model: String,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+struct OllamaProvider {
+    config: ProviderConfig,
+}
+
+impl OllamaProvider {
+    async fn send(
+        &self,
+        system: Option<&str>,
+        prompt: &str,
+        stream: bool,
+    ) -> Result<reqwest::Response, ProviderError> {
+        let model = self.model();
+        let request_body = OllamaRequest {
+            prompt: crate::redact::prepare_for_provider(&self.config, prompt),
+            system: system.map(str::to_string),
+            stream,
+            options: OllamaOptions {
+                num_ctx: crate::budget::context_length(&model).await,
+                temperature: self.config.temperature,
+                num_predict: self.config.max_tokens,
+            },
+            model,
+        };
+
+        let response = self
+            .config
+            .post("/api/generate")
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| send_error("Ollama", e))?;
+
+        if !response.status().is_success() {
+            return Err(status_error("Ollama", response).await);
+        }
+        Ok(response)
+    }
+}
+
+impl LlmProvider for OllamaProvider {
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    fn complete<'a>(&'a self, system: Option<&'a str>, prompt: &'a str) -> LlmFuture<'a> {
+        Box::pin(async move {
+            println!(
+                "[OLLAMA] Generating with model {} ({} characters)",
+                self.model(),
+                prompt.len()
+            );
+            let response_body: OllamaResponse = self
+                .send(system, prompt, false)
+                .await?
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+
+            println!(
+                "[OLLAMA] Response length: {} characters",
+                response_body.response.len()
+            );
+            Ok(response_body.response.trim().to_string())
+        })
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        system: Option<&'a str>,
+        prompt: &'a str,
+        on_delta: DeltaSink<'a>,
+    ) -> LlmFuture<'a> {
+        Box::pin(async move {
+            println!("[OLLAMA] Streaming response from model: {}", self.model());
+            let response = self.send(system, prompt, true).await?;
+
+            let mut full_response = String::new();
+            read_stream_lines(response, |line| {
+                let chunk: OllamaStreamChunk = serde_json::from_str(line)
+                    .map_err(|e| format!("Failed to parse Ollama stream: {}", e))?;
+                if let Some(error) = chunk.error {
+                    return Err(format!("Ollama API error: {}", error));
+                }
+                if !chunk.response.is_empty() {
+                    on_delta(&chunk.response);
+                    full_response.push_str(&chunk.response);
+                }
+                Ok(!chunk.done)
+            })
+            .await?;
+
+            println!(
+                "[OLLAMA] Streamed response length: {} characters",
+                full_response.len()
+            );
+            Ok(full_response.trim().to_string())
+        })
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> EmbedFuture<'a> {
+        Box::pin(async move {
+            if texts.is_empty() {
+                return Ok(Vec::new());
+            }
+            // Chunks are raw screen text and transcripts, so they get the same policy as prompts
+            let input = crate::redact::prepare_batch_for_provider(&self.config, texts);
+            let response = self
+                .config
+                .post("/api/embed")
+                .json(&OllamaEmbedRequest {
+                    model: self.model(),
+                    input: &input,
+                })
+                .send()
+                .await
+                .map_err(|e| send_error("Ollama", e))?;
+            if !response.status().is_success() {
+                return Err(status_error("Ollama", response).await);
+            }
+
+            let response_body: OllamaEmbedResponse = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse Ollama embedding response: {}", e))?;
+            if response_body.embeddings.len() != texts.len() {
+                return Err(format!(
+                    "Ollama returned {} embeddings for {} inputs",
+                    response_body.embeddings.len(),
+                    texts.len()
+                )
+                .into());
+            }
+            Ok(response_body.embeddings)
+        })
+    }
+}
+
+struct OpenAICompatibleProvider {
+    config: ProviderConfig,
+}
+
+impl OpenAICompatibleProvider {
+    async fn send(
+        &self,
+        system: Option<&str>,
+        prompt: &str,
+        stream: bool,
+    ) -> Result<reqwest::Response, ProviderError> {
+        let mut messages = Vec::new();
+        if let Some(system) = system {
+            messages.push(OpenAIMessage {
+                role: "system".to_string(),
+                content: system.to_string(),
+            });
+        }
+        messages.push(OpenAIMessage {
+            role: "user".to_string(),
+            // Retrieved screen text and transcripts are in here; mask them per the provider's policy
+            content: crate::redact::prepare_for_provider(&self.config, prompt),
+        });
+
+        let request_body = OpenAIRequest {
+            model: self.model(),
+            messages,
+            max_tokens: self.config.max_tokens,
+            temperature: self.config.temperature,
+            stream,
+        };
+
+        let response = self
+            .config
+            .post("/chat/completions")
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| send_error(&self.config.name, e))?;
+
+        if !response.status().is_success() {
+            return Err(status_error(&self.config.name, response).await);
+        }
+        Ok(response)
+    }
+}
+
+impl LlmProvider for OpenAICompatibleProvider {
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    fn complete<'a>(&'a self, system: Option<&'a str>, prompt: &'a str) -> LlmFuture<'a> {
+        Box::pin(async move {
+            let response_body = self
+                .send(system, prompt, false)
+                .await?
+                .json::<OpenAIResponse>()
+                .await
+                .map_err(|e| format!("Failed to parse {} response: {}", self.config.name, e))?;
+
+            match response_body.choices.into_iter().next() {
+                Some(choice) => Ok(choice.message.content),
+                None => Err(format!("No response from {}", self.config.name).into()),
+            }
+        })
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        system: Option<&'a str>,
+        prompt: &'a str,
+        on_delta: DeltaSink<'a>,
+    ) -> LlmFuture<'a> {
+        Box::pin(async move {
+            let response = self.send(system, prompt, true).await?;
+
+            let mut full_response = String::new();
+            read_stream_lines(response, |line| {
+                // Only `data:` lines carry payloads; comments and keep-alives are skipped
+                let Some(data) = line.strip_prefix("data:") else {
+                    return Ok(true);
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    return Ok(false);
+                }
+
+                let chunk: OpenAIStreamChunk = serde_json::from_str(data)
+                    .map_err(|e| format!("Failed to parse {} stream: {}", self.config.name, e))?;
+                if let Some(content) = chunk
+                    .choices
+                    .first()
+                    .and_then(|choice| choice.delta.content.as_deref())
+                {
+                    on_delta(content);
+                    full_response.push_str(content);
+                }
+                Ok(true)
+            })
+            .await?;
+
+            if full_response.is_empty() {
+                return Err(format!("No response from {}", self.config.name).into());
+            }
+            Ok(full_response)
+        })
+    }
+}
+
+// A request that never got an answer; worth retrying unless it ran out the full timeout
+fn send_error(provider: &str, error: reqwest::Error) -> ProviderError {
+    let message = format!("Failed to send request to {}: {}", provider, error);
+    if error.is_timeout() {
+        message.into()
+    } else {
+        ProviderError::retryable(message)
+    }
+}
+
+async fn status_error(provider: &str, response: reqwest::Response) -> ProviderError {
+    let status = response.status();
+    let error_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    let message = format!("{} API error ({}): {}", provider, status, error_text);
+    if status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+    {
+        ProviderError::retryable(message)
+    } else {
+        message.into()
+    }
+}
+
+// Feed each complete line of a streamed response body to `on_line` until it returns false
+async fn read_stream_lines(
+    mut response: reqwest::Response,
+    mut on_line: impl FnMut(&str) -> Result<bool, String>,
+) -> Result<(), ProviderError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(bytes) = response
+        .chunk()
+        .await
+        .map_err(|e| ProviderError::retryable(format!("Failed to read response stream: {}", e)))?
+    {
+        buffer.extend_from_slice(&bytes);
+        // Split on raw bytes so multi-byte characters cut across network chunks stay intact
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() && !on_line(line.trim())? {
+                return Ok(());
+            }
+        }
+    }
+
+    let rest = String::from_utf8_lossy(&buffer);
+    if !rest.trim().is_empty() {
+        on_line(rest.trim())?;
+    }
+    Ok(())
+}
+
+/// An answer and the provider that gave it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProviderAnswer {
+    pub text: String,
+    pub provider: String,
+    pub model: String,
+}
+
+struct CircuitState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+// Seconds left before a provider that kept failing is tried again
+fn circuit_open_for(name: &str) -> Option<u64> {
+    let circuits = CIRCUITS.lock().unwrap();
+    let open_until = circuits.get(name)?.open_until?;
+    let remaining = open_until.checked_duration_since(Instant::now())?;
+    Some(remaining.as_secs() + 1)
+}
+
+fn record_outcome(config: &ProviderConfig, succeeded: bool) {
+    let mut circuits = CIRCUITS.lock().unwrap();
+    if succeeded {
+        circuits.remove(&config.name);
+        return;
+    }
+
+    let state = circuits.entry(config.name.clone()).or_insert(CircuitState {
+        failures: 0,
+        open_until: None,
+    });
+    state.failures += 1;
+    // Once open, a single failed trial request after the cooldown opens it again
+    if config.circuit_failure_threshold > 0 && state.failures >= config.circuit_failure_threshold {
+        println!(
+            "[AI] {} failed {} times in a row, skipping it for {} s",
+            config.name, state.failures, config.circuit_cooldown_secs
+        );
+        state.open_until = Some(Instant::now() + Duration::from_secs(config.circuit_cooldown_secs));
+    }
+}
+
+/// Ask the enabled providers in order until one answers. Providers without
+/// a key or whose circuit is open are skipped; temporary failures are retried with
+/// exponential backoff. When streaming, a provider that already sent part of its answer is
+/// never retried or replaced, so listeners never see two partial answers.
+pub async fn complete_with_fallback(
+    system: Option<&str>,
+    prompt: &str,
+    options: &CompletionOptions,
+    mut on_delta: Option<DeltaSink<'_>>,
+) -> Result<ProviderAnswer, String> {
+    let mut errors = Vec::new();
+
+    for config in provider_chain(options) {
+        let provider = config.build();
+        let config = provider.config();
+        if let Some(reason) = config.unavailable_reason() {
+            errors.push(format!("{}: {}", config.name, reason));
+            continue;
+        }
+        if let Some(seconds) = circuit_open_for(&config.name) {
+            errors.push(format!(
+                "{}: skipped after repeated failures, retrying in {} s",
+                config.name, seconds
+            ));
+            continue;
+        }
+
+        let mut attempt = 0;
+        let error = loop {
+            let mut streamed = false;
+            let result = match on_delta.as_mut() {
+                Some(on_delta) => {
+                    let mut tracking_sink = |delta: &str| {
+                        streamed = true;
+                        on_delta(delta);
+                    };
+                    provider
+                        .complete_stream(system, prompt, &mut tracking_sink)
+                        .await
+                }
+                None => provider.complete(system, prompt).await,
+            };
+
+            match result {
+                Ok(text) => {
+                    record_outcome(config, true);
+                    println!("[AI] Answered by {} ({})", config.name, provider.model());
+                    return Ok(ProviderAnswer {
+                        text,
+                        provider: config.name.clone(),
+                        model: provider.model(),
+                    });
+                }
+                Err(e) if streamed => {
+                    record_outcome(config, false);
+                    return Err(e.message);
+                }
+                Err(e) if e.retryable && attempt < config.max_retries => {
+                    let backoff = config.retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+                    attempt += 1;
+                    println!(
+                        "[AI] {} failed: {}. Retry {} of {} in {} ms",
+                        config.name, e, attempt, config.max_retries, backoff
+                    );
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                }
+                Err(e) => break e,
+            }
+        };
+
+        record_outcome(config, false);
+        println!(
+            "[AI] {} failed: {}. Trying the next provider...",
+            config.name, error
+        );
+        errors.push(format!("{}: {}", config.name, error));
+    }
+
+    if errors.is_empty() {
+        Err("No AI provider is enabled".to_string())
+    } else {
+        Err(format!(
+            "No AI provider could answer: {}",
+            errors.join("; ")
+        ))
+    }
+}
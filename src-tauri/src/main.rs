@@ -11,18 +11,37 @@ use base64::Engine;
 use std::process::Command;
 
 // Import modules
+mod agents;
 mod ai;
 mod app_discovery;
+mod bm25;
+mod budget;
+mod chat;
+mod chunking;
+mod dedup;
 mod export;
 mod icons;
+mod ingest;
 mod install;
+mod llm;
+mod paths;
+mod privacy;
+mod prompts;
 mod rag;
+#[cfg(test)]
+mod rag_eval;
+mod rag_store;
+mod redact;
 mod screenpipe;
+mod summarize;
 mod system;
+mod time_expr;
+mod trace;
 mod types;
 
-use ai::{call_ai_with_agent_async, prepare_ai_context_rag, TimeRange};
+use ai::{call_ai_with_agent_stream, prepare_ai_context_rag};
 use app_discovery::AppDiscovery;
+use budget::ContextBudget;
 use export::{get_export_files, get_export_status};
 use install::{install_ollama, install_ollama_model, install_screenpipe};
 
@@ -106,54 +125,171 @@ fn get_export_files_cmd() -> Result<Vec<serde_json::Value>, String> {
 async fn analyze_data_with_ai(
     app_handle: tauri::AppHandle,
     message: String,
-    time_range: String, // "daily", "weekly", "monthly", "yearly", "all_time"
+    time_range: String, // A preset ("daily", "weekly", ...) or a phrase like "yesterday afternoon"
     agent_type: String,
+    request_id: Option<String>, // Tags the streamed ai-response events; generated if missing
+    session_id: Option<String>, // Chat session to continue; None answers the message on its own
 ) -> Result<String, String> {
     println!("Received AI analysis request");
     println!("Message: {}", message);
     println!("Time Range: {}", time_range);
     println!("Agent Type: {}", agent_type);
 
-    // Convert string to TimeRange enum
-    let time_range_enum =
-        match time_range.as_str() {
-            "daily" => TimeRange::Daily,
-            "weekly" => TimeRange::Weekly,
-            "monthly" => TimeRange::Monthly,
-            "yearly" => TimeRange::Yearly,
-            "all_time" => TimeRange::AllTime,
-            _ => return Err(format!(
-                "Invalid time range: {}. Must be one of: daily, weekly, monthly, yearly, all_time",
-                time_range
-            )),
-        };
+    let window = time_expr::parse_time_expression(&time_range)?;
+    println!("Resolved time range: {}", window.describe());
 
-    // Perform AI analysis in a separate thread to avoid blocking
-    let app_handle_clone = app_handle.clone();
-    thread::spawn(move || {
-        // Create a runtime for async operations in a separate thread
-        let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap();
-
-        rt.block_on(async {
-            if let Err(e) =
-                perform_ai_analysis(&app_handle_clone, message, time_range_enum, agent_type).await
-            {
-                let _ = app_handle_clone.emit(
-                    "ai-response",
-                    serde_json::json!({
-                        "error": e
-                    }),
-                );
-            }
-        });
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // Run the analysis in the background; its answer is streamed through ai-response events
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = perform_ai_analysis(
+            &app_handle,
+            &request_id,
+            session_id,
+            message,
+            window,
+            agent_type,
+        )
+        .await
+        {
+            let _ = app_handle.emit(
+                "ai-response",
+                serde_json::json!({
+                    "request_id": request_id,
+                    "done": true,
+                    "error": e
+                }),
+            );
+        }
     });
 
     Ok("AI analysis started".to_string())
 }
 
+#[tauri::command]
+fn create_chat_session_cmd(
+    title: Option<String>,
+    agent_type: Option<String>,
+) -> Result<chat::ChatSession, String> {
+    let session = chat::ChatSession::new(title, agent_type);
+    session.save()?;
+    Ok(session)
+}
+
+#[tauri::command]
+fn list_chat_sessions_cmd() -> Result<Vec<chat::ChatSessionSummary>, String> {
+    chat::list_sessions()
+}
+
+#[tauri::command]
+fn get_chat_session_cmd(session_id: String) -> Result<chat::ChatSession, String> {
+    chat::ChatSession::load(&session_id)
+}
+
+#[tauri::command]
+fn delete_chat_session_cmd(session_id: String) -> Result<String, String> {
+    chat::ChatSession::delete(&session_id)?;
+    Ok(format!("Chat session {} deleted", session_id))
+}
+
+#[tauri::command]
+fn get_redaction_config_cmd() -> redact::RedactionConfig {
+    redact::RedactionConfig::load()
+}
+
+#[tauri::command]
+fn set_redaction_config_cmd(config: redact::RedactionConfig) -> Result<String, String> {
+    config.save()?;
+    Ok("Redaction settings updated".to_string())
+}
+
+/// Show what would be masked in `text` before it is sent to `provider`
+#[tauri::command]
+fn preview_redaction_cmd(text: String, provider: String) -> Result<serde_json::Value, String> {
+    let provider = llm::ProviderSettings::load()
+        .providers
+        .into_iter()
+        .find(|config| config.name == provider)
+        .ok_or_else(|| format!("Unknown provider '{}'", provider))?;
+    let (redacted, report) = redact::redact_for_provider(&provider, &text);
+    Ok(serde_json::json!({ "text": redacted, "report": report }))
+}
+
+/// What was masked in recent prompts, newest first
+#[tauri::command]
+fn list_redaction_reports_cmd() -> Vec<redact::RedactionReport> {
+    redact::recent_reports()
+}
+
+#[tauri::command]
+fn get_exclusion_rules_cmd() -> privacy::ExclusionRules {
+    privacy::ExclusionRules::load()
+}
+
+/// Apps, windows, URLs and source types to keep out of ingestion, analytics and exports.
+/// Chunks already in the index stay stored but are no longer retrieved.
+#[tauri::command]
+fn set_exclusion_rules_cmd(rules: privacy::ExclusionRules) -> Result<String, String> {
+    rules.save()?;
+    rag::set_exclusion_rules(&rules);
+    Ok("Privacy exclusion rules updated".to_string())
+}
+
+#[tauri::command]
+fn list_agents_cmd() -> Vec<agents::Agent> {
+    agents::list_agents()
+}
+
+#[tauri::command]
+fn get_agent_cmd(name: String) -> Result<agents::Agent, String> {
+    agents::get_agent(&name)
+}
+
+/// Create an agent, or update the one with the same name
+#[tauri::command]
+fn save_agent_cmd(agent: agents::Agent) -> Result<String, String> {
+    let name = agent.name.clone();
+    agents::save_agent(agent)?;
+    Ok(format!("Agent {} saved", name))
+}
+
+#[tauri::command]
+fn delete_agent_cmd(name: String) -> Result<String, String> {
+    agents::delete_agent(&name)?;
+    Ok(format!("Agent {} deleted", name))
+}
+
+/// Built-in prompt templates and the user versions saved over them
+#[tauri::command]
+fn list_prompt_templates_cmd() -> Vec<prompts::PromptTemplate> {
+    prompts::list_templates()
+}
+
+/// Save a user version of a template; the highest version of a name is the one used
+#[tauri::command]
+fn save_prompt_template_cmd(name: String, version: String, body: String) -> Result<String, String> {
+    prompts::save_template(&name, &version, &body)?;
+    Ok(format!("Template {}@{} saved", name, version))
+}
+
+#[tauri::command]
+fn delete_prompt_template_cmd(name: String, version: String) -> Result<String, String> {
+    prompts::delete_template(&name, &version)?;
+    Ok(format!("Template {}@{} deleted", name, version))
+}
+
+/// Model servers answers are generated with, in the order they are tried
+#[tauri::command]
+fn get_llm_providers_cmd() -> llm::ProviderSettings {
+    llm::ProviderSettings::load()
+}
+
+#[tauri::command]
+fn set_llm_providers_cmd(settings: llm::ProviderSettings) -> Result<String, String> {
+    settings.save()?;
+    Ok("AI providers updated".to_string())
+}
+
 #[tauri::command]
 fn set_openai_api_key(api_key: String) -> Result<String, String> {
     std::env::set_var("OPENAI_API_KEY", api_key);
@@ -236,13 +372,13 @@ fn start_screenpipe_cmd() -> Result<String, String> {
 }
 
 #[tauri::command]
-fn call_ollama_with_model_cmd(prompt: String, model: String) -> Result<String, String> {
-    ai::call_ollama_with_model(&prompt, &model)
+async fn call_ollama_with_model_cmd(prompt: String, model: String) -> Result<String, String> {
+    ai::call_ollama_with_model_async(&prompt, &model).await
 }
 
 #[tauri::command]
-fn check_ollama_status_cmd() -> Result<bool, String> {
-    ai::check_ollama_status()
+async fn check_ollama_status_cmd() -> Result<bool, String> {
+    ai::check_ollama_status().await
 }
 
 #[tauri::command]
@@ -264,58 +400,130 @@ fn clear_selected_model_cmd() -> Result<String, String> {
 
 async fn perform_ai_analysis(
     app_handle: &tauri::AppHandle,
+    request_id: &str,
+    session_id: Option<String>,
     user_message: String,
-    time_range: TimeRange,
+    window: time_expr::TimeWindow,
     agent_type: String,
 ) -> Result<(), String> {
-    println!("[AI] Starting AI analysis for time range: {:?}", time_range);
+    println!(
+        "[AI] Starting AI analysis for time range: {}",
+        window.describe()
+    );
     println!("[AI] User message: {}", user_message);
     println!("[AI] Agent type: {}", agent_type);
+    let agent = agents::resolve(&agent_type);
+
+    let mut session = match session_id {
+        Some(id) => Some(chat::ChatSession::load(&id)?),
+        None => None,
+    };
+
+    // Follow-up questions are rewritten into standalone queries before retrieval
+    let search_query = match &session {
+        Some(session) => chat::rewrite_query(session, &user_message).await,
+        None => user_message.clone(),
+    };
+    // The prompt is sized for the model the agent answers with and the answer length it allows
+    let budget = ContextBudget::for_options(&agent.completion_options()).await;
+    let history = match &session {
+        Some(session) => session.history_for_prompt(chat::history_token_budget(&budget.model)),
+        None => String::new(),
+    };
+    let history_section = if history.is_empty() {
+        String::new()
+    } else {
+        format!("CONVERSATION SO FAR:\n{}\n\n", history)
+    };
 
     // Prepare context using RAG for intelligent content retrieval
     println!("[AI] Preparing RAG context...");
     println!("[AI] DEBUG: About to call prepare_ai_context_rag");
-    let context = prepare_ai_context_rag(&user_message, time_range).await?;
+    // The context is packed into whatever the model's window leaves after the rest of the prompt
+    let template = prompts::template(prompts::ANALYSIS);
+    let time_range = window.describe();
+    let fixed_prompt = format!(
+        "{}\n{}",
+        agent.system_prompt,
+        analysis_prompt(
+            &template,
+            "",
+            &history_section,
+            &user_message,
+            &time_range,
+            &agent
+        )
+    );
+    let (context, sources, mut trace) = prepare_ai_context_rag(
+        &search_query,
+        window,
+        &agent.filters,
+        &budget,
+        &fixed_prompt,
+    )
+    .await?;
     println!("[AI] DEBUG: prepare_ai_context_rag completed successfully");
     println!("[AI] RAG context prepared successfully");
 
-    let prompt = format!(
-        r#"You are an assistant secretary. When answering, provide polished, professional summaries and responses as if you are briefing an executive. Do not reference how you know the information, the user, or any context or data source. Simply present the information as a knowledgeable assistant secretary would, focusing on clarity, professionalism, and helpfulness.
-
-If asked for a summary of activities, list the applications, tasks, or events in a clear and concise manner. If asked for details, provide them directly and succinctly. If information is not available, state so politely and professionally.
-
-Always use clear, concise, and formal language. Do not mention 'context', 'user', or how the answer was derived.
-
-SUMMARY DATA:
-{context}
-
-QUESTION:
-{user_message}
-"#
+    let prompt = analysis_prompt(
+        &template,
+        &context,
+        &history_section,
+        &user_message,
+        &time_range,
+        &agent,
     );
 
     println!("[AI] Calling AI with agent-specific analysis...");
-    println!("[AI] DEBUG: About to call call_ai_with_agent_async");
-    // Call AI with agent-specific analysis
-    let ai_response = call_ai_with_agent_async(&prompt, &agent_type).await?;
-    println!("[AI] DEBUG: call_ai_with_agent_async completed successfully");
+    println!("[AI] DEBUG: About to call call_ai_with_agent_stream");
+    // Call AI with agent-specific analysis, forwarding the answer as it streams in
+    let mut emit_delta = |delta: &str| emit_ai_delta(app_handle, request_id, delta);
+    let generation_started = std::time::Instant::now();
+    let answer = call_ai_with_agent_stream(&prompt, &agent, &mut emit_delta).await?;
+    let ai_response = answer.text;
+    trace.provider = Some(answer.provider.clone());
+    trace.model = Some(answer.model.clone());
+    trace.generation_ms = generation_started.elapsed().as_millis() as u64;
+    trace.total_ms = trace.retrieval_ms + trace.generation_ms;
+    trace.templates.push(template.usage());
+    trace::record(&trace);
+    println!("[AI] DEBUG: call_ai_with_agent_stream completed successfully");
     println!("[AI] AI response received successfully");
 
     // Print AI analysis to terminal
     println!("=== AI ANALYSIS RESULT ===");
     println!("Agent Type: {}", agent_type);
+    println!("Answered by: {} ({})", answer.provider, answer.model);
     println!("User Message: {}", user_message);
     println!("AI Response:");
     println!("{}", ai_response);
     println!("==========================");
 
+    let session_id = match session.as_mut() {
+        Some(session) => {
+            let rewritten = (search_query != user_message).then(|| search_query.clone());
+            session.add_exchange(&user_message, rewritten, &ai_response);
+            session.save()?;
+            Some(session.id.clone())
+        }
+        None => None,
+    };
+
     // Send response back to frontend
     println!("[AI] Sending response to frontend...");
     app_handle
         .emit(
             "ai-response",
             serde_json::json!({
-                "message": ai_response
+                "request_id": request_id,
+                "session_id": session_id,
+                "done": true,
+                "message": ai_response,
+                "sources": sources,
+                "budget": trace.budget,
+                "trace_id": trace.id,
+                "provider": answer.provider,
+                "model": answer.model
             }),
         )
         .map_err(|e| format!("Failed to emit AI response: {}", e))?;
@@ -324,6 +532,35 @@ QUESTION:
     Ok(())
 }
 
+fn analysis_prompt(
+    template: &prompts::PromptTemplate,
+    context: &str,
+    history_section: &str,
+    user_message: &str,
+    time_range: &str,
+    agent: &agents::Agent,
+) -> String {
+    template.render(&[
+        ("context", context),
+        ("history", history_section),
+        ("question", user_message),
+        ("time_range", time_range),
+        ("agent", &agent.name),
+    ])
+}
+
+// Chunk events carry only `delta`; the final event is the one with `message`
+fn emit_ai_delta(app_handle: &tauri::AppHandle, request_id: &str, delta: &str) {
+    let _ = app_handle.emit(
+        "ai-response",
+        serde_json::json!({
+            "request_id": request_id,
+            "done": false,
+            "delta": delta
+        }),
+    );
+}
+
 #[tauri::command]
 async fn get_app_icon_handler(
     app_name: String,
@@ -367,31 +604,52 @@ async fn get_app_icon_handler(
 
 // RAG Command Functions
 #[tauri::command]
-async fn initialize_rag_cmd() -> Result<String, String> {
-    match rag::initialize_rag().await {
+async fn initialize_rag_cmd(collection: Option<String>) -> Result<String, String> {
+    match rag::initialize_rag(collection).await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to initialize RAG: {}", e)),
     }
 }
 
 #[tauri::command]
-async fn ingest_data_rag_cmd(data_files: Vec<serde_json::Value>) -> Result<String, String> {
-    match rag::ingest_data_rag(data_files).await {
+async fn ingest_data_rag_cmd(
+    data_files: Vec<serde_json::Value>,
+    collection: Option<String>,
+) -> Result<String, String> {
+    match rag::ingest_data_rag(data_files, collection).await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to ingest data: {}", e)),
     }
 }
 
+// Tauri maps each frontend argument to a parameter, so the list grows with the query options
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn query_rag_system_cmd(
     query: String,
     top_k: Option<usize>,
     similarity_threshold: Option<f32>,
     custom_query: Option<String>,
-    time_range: Option<String>,
+    retrieval_mode: Option<rag::RetrievalMode>,
+    rerank: Option<bool>,
+    filters: Option<rag::RAGFilters>,
+    collection: Option<String>,
+    include_trace: Option<bool>,
 ) -> Result<serde_json::Value, String> {
-    match rag::query_rag_system(query, top_k, similarity_threshold, custom_query, time_range).await
-    {
+    let mut rag_query = rag::RAGQuery::new(query);
+    if let Some(top_k) = top_k {
+        rag_query.top_k = top_k;
+    }
+    if let Some(similarity_threshold) = similarity_threshold {
+        rag_query.similarity_threshold = similarity_threshold;
+    }
+    rag_query.retrieval_mode = retrieval_mode.unwrap_or_default();
+    rag_query.rerank = rerank.unwrap_or(false);
+    rag_query.filters = filters.unwrap_or_default();
+    rag_query.collection = collection;
+    rag_query.include_trace = include_trace.unwrap_or(false);
+
+    match rag::query_rag_system(rag_query, custom_query).await {
         Ok(response) => {
             let json_response = serde_json::to_value(response)
                 .map_err(|e| format!("Failed to serialize RAG response: {}", e))?;
@@ -401,17 +659,92 @@ async fn query_rag_system_cmd(
     }
 }
 
+/// Answer a RAG query in the background, streaming it as `ai-response` events.
+/// Returns the request id the events are tagged with.
 #[tauri::command]
-async fn clear_rag_data_cmd() -> Result<String, String> {
-    match rag::clear_rag_data().await {
+async fn stream_rag_query_cmd(
+    app_handle: tauri::AppHandle,
+    rag_query: rag::RAGQuery,
+    request_id: Option<String>,
+) -> Result<String, String> {
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let task_request_id = request_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let request_id = task_request_id;
+        let mut emit_delta = |delta: &str| emit_ai_delta(&app_handle, &request_id, delta);
+        let result = rag::stream_rag_query(rag_query, &mut emit_delta)
+            .await
+            .and_then(|response| Ok(serde_json::to_value(response)?));
+        let payload = match result {
+            // The whole response, with the fields the final event of perform_ai_analysis uses
+            Ok(mut response) => {
+                response["request_id"] = serde_json::json!(request_id);
+                response["done"] = serde_json::json!(true);
+                response["message"] = response["answer"].clone();
+                response["sources"] = response["context_chunks"].clone();
+                response
+            }
+            Err(e) => serde_json::json!({
+                "request_id": request_id,
+                "done": true,
+                "error": format!("Failed to query RAG system: {}", e)
+            }),
+        };
+        let _ = app_handle.emit("ai-response", payload);
+    });
+
+    Ok(request_id)
+}
+
+#[tauri::command]
+async fn keyword_search_rag_cmd(
+    query: String,
+    top_k: Option<usize>,
+    filters: Option<rag::RAGFilters>,
+    collection: Option<String>,
+) -> Result<serde_json::Value, String> {
+    match rag::keyword_search_rag(query, top_k, filters, collection).await {
+        Ok(response) => {
+            let json_response = serde_json::to_value(response)
+                .map_err(|e| format!("Failed to serialize keyword search results: {}", e))?;
+            Ok(json_response)
+        }
+        Err(e) => Err(format!("Failed to run keyword search: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn get_chunking_config_cmd() -> Result<serde_json::Value, String> {
+    match rag::get_chunking_policy().await {
+        Ok(policy) => serde_json::to_value(policy)
+            .map_err(|e| format!("Failed to serialize chunking config: {}", e)),
+        Err(e) => Err(format!("Failed to get chunking config: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn set_chunking_config_cmd(
+    source_type: String,
+    config: chunking::ChunkingConfig,
+) -> Result<String, String> {
+    match rag::set_chunking_config(source_type, config).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to set chunking config: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn clear_rag_data_cmd(collection: Option<String>) -> Result<String, String> {
+    match rag::clear_rag_data(collection).await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to clear RAG data: {}", e)),
     }
 }
 
 #[tauri::command]
-async fn get_rag_stats_cmd() -> Result<serde_json::Value, String> {
-    match rag::get_rag_stats().await {
+async fn get_rag_stats_cmd(collection: Option<String>) -> Result<serde_json::Value, String> {
+    match rag::get_rag_stats(collection).await {
         Ok(stats) => {
             let json_stats = serde_json::to_value(stats)
                 .map_err(|e| format!("Failed to serialize RAG stats: {}", e))?;
@@ -424,52 +757,101 @@ async fn get_rag_stats_cmd() -> Result<serde_json::Value, String> {
 #[tauri::command]
 async fn ingest_sql_data_rag(
     time_range: Option<String>,
+    mode: Option<rag::SqlIngestMode>,
     sql_query: Option<String>,
+    collection: Option<String>,
 ) -> Result<String, String> {
-    match rag::ingest_sql_data_rag(time_range, sql_query).await {
+    let window = time_range
+        .map(|time_range| time_expr::parse_time_expression(&time_range))
+        .transpose()?;
+    let mode = mode.unwrap_or_default();
+    match rag::ingest_sql_data_rag(window, mode, sql_query, collection).await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to ingest SQL data: {}", e)),
     }
 }
 
+#[tauri::command]
+async fn ingest_search_rag_cmd(
+    query: Option<String>,
+    content_type: Option<String>,
+    time_range: Option<String>,
+    limit: Option<usize>,
+    collection: Option<String>,
+) -> Result<String, String> {
+    match rag::ingest_search_rag(query, content_type, time_range, limit, collection).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to ingest search results: {}", e)),
+    }
+}
+
+/// Past queries with their retrieval traces, newest first
+#[tauri::command]
+fn list_query_history_cmd(limit: Option<usize>) -> Vec<trace::QueryTraceSummary> {
+    trace::list_traces(limit)
+}
+
+#[tauri::command]
+fn get_query_trace_cmd(trace_id: String) -> Result<trace::QueryTrace, String> {
+    trace::get_trace(&trace_id)
+}
+
+#[tauri::command]
+fn clear_query_history_cmd() -> Result<String, String> {
+    trace::clear_traces()?;
+    Ok("Query history cleared".to_string())
+}
+
+#[tauri::command]
+async fn create_rag_collection_cmd(name: String) -> Result<String, String> {
+    match rag::create_collection(name).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to create RAG collection: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn list_rag_collections_cmd() -> Result<serde_json::Value, String> {
+    match rag::list_collections().await {
+        Ok(collections) => serde_json::to_value(collections)
+            .map_err(|e| format!("Failed to serialize RAG collections: {}", e)),
+        Err(e) => Err(format!("Failed to list RAG collections: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn drop_rag_collection_cmd(name: String) -> Result<String, String> {
+    match rag::drop_collection(name).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to drop RAG collection: {}", e)),
+    }
+}
+
 #[tauri::command]
 async fn deep_analysis_rag(
     sql_query: String,
     analysis_query: String,
     top_k: Option<usize>,
     similarity_threshold: Option<f32>,
+    collection: Option<String>,
 ) -> Result<String, String> {
     // First, initialize RAG system
-    match rag::initialize_rag().await {
+    match rag::initialize_rag(collection.clone()).await {
         Ok(_) => (),
         Err(e) => return Err(format!("Failed to initialize RAG: {}", e)),
     }
 
-    // Clear existing data and ingest new data
-    match rag::clear_rag_data().await {
-        Ok(_) => (),
-        Err(e) => return Err(format!("Failed to clear RAG data: {}", e)),
-    }
-
-    // Ingest the SQL data into RAG system
-    match rag::ingest_sql_data_rag(Some(sql_query), None).await {
-        Ok(_) => (),
-        Err(e) => return Err(format!("Failed to ingest SQL data: {}", e)),
-    }
-
     // Query the RAG system for analysis
     let top_k = top_k.unwrap_or(10);
     let similarity_threshold = similarity_threshold.unwrap_or(0.3);
 
-    match rag::query_rag_system(
-        analysis_query,
-        Some(top_k),
-        Some(similarity_threshold),
-        None,
-        None,
-    )
-    .await
-    {
+    let mut rag_query = rag::RAGQuery::new(analysis_query);
+    rag_query.top_k = top_k;
+    rag_query.similarity_threshold = similarity_threshold;
+    rag_query.collection = collection;
+
+    // `sql_query` is a complete SELECT; the answer only draws on the frames it returns
+    match rag::query_rag_system(rag_query, Some(sql_query)).await {
         Ok(response) => {
             // Serialize the RAGResponse to JSON string for the frontend
             serde_json::to_string(&response)
@@ -501,6 +883,7 @@ async fn quick_action_rag(
     action_type: String,
     time_range: String,
     custom_prompt: Option<String>,
+    collection: Option<String>,
 ) -> Result<String, String> {
     println!("=== QUICK ACTION RAG DEBUG ===");
     println!("Action Type: {}", action_type);
@@ -508,71 +891,70 @@ async fn quick_action_rag(
     println!("Custom Prompt: {:?}", custom_prompt);
 
     // Initialize RAG system
-    match rag::initialize_rag().await {
+    match rag::initialize_rag(collection.clone()).await {
         Ok(_) => println!("RAG system initialized successfully"),
         Err(e) => return Err(format!("Failed to initialize RAG: {}", e)),
     }
 
-    // Clear existing data
-    match rag::clear_rag_data().await {
-        Ok(_) => println!("RAG data cleared successfully"),
-        Err(e) => return Err(format!("Failed to clear RAG data: {}", e)),
-    }
-
-    // Build SQL query based on time range
-    let sql_query = time_range.clone();
+    // The time range is a phrase like "yesterday afternoon" or a preset name
+    let window = time_expr::parse_time_expression(&time_range)?;
+    let time_period = window.describe();
 
-    println!("Final SQL Query:");
-    println!("{}", sql_query);
+    println!("Window: {}", time_period);
     println!("=== END DEBUG ===");
 
-    // Ingest the SQL data into RAG system
-    match rag::ingest_sql_data_rag(Some(sql_query), None).await {
+    // Ingest the SQL data into RAG system; only frames not yet in the persistent index are added
+    let ingest_mode = rag::SqlIngestMode::for_quick_action(&action_type);
+    match rag::ingest_sql_data_rag(Some(window), ingest_mode, None, collection.clone()).await {
         Ok(_) => (),
         Err(e) => return Err(format!("Failed to ingest SQL data: {}", e)),
     }
 
-    // Build analysis query based on action type with time range context
-    let analysis_query = match action_type.as_str() {
-        "meeting_analysis" => format!("Analyze all meeting activities for the time period: {}. Look for meeting durations, participants, topics discussed, and any important decisions or action items from meetings.", time_range),
-        "coding_activity" => format!("Analyze all coding and development activities for the time period: {}. Look for programming languages used, development tools, code repositories, debugging sessions, and any technical work or problem-solving activities.", time_range),
-        "productivity_analysis" => format!("Analyze overall productivity patterns for the time period: {}. Look for focused work sessions, application usage patterns, task switching frequency, and identify the most productive periods and activities.", time_range),
-        "communication_analysis" => format!("Analyze all communication activities for the time period: {}. Look for emails, chat messages, social media, and messaging platforms. Look for important conversations, communication patterns, and any urgent or significant messages.", time_range),
-        "research_analysis" => format!("Analyze research and learning activities for the time period: {}. Look for documentation reading, tutorial watching, educational content consumption, and any research or learning-related activities.", time_range),
-        "web_browsing" => format!("Analyze web browsing patterns and activities for the time period: {}. Look for websites visited, browsing purposes, research activities, and any important information gathered from web browsing.", time_range),
-        "time_usage" => format!("Analyze time usage patterns for the time period: {}. Provide insights into how time was distributed, most time-consuming activities, and efficiency patterns.", time_range),
-        "daily_analysis" => format!("Provide a comprehensive activity analysis for the time period: {}. Cover all digital activities, productivity patterns, important events, and give a complete overview of the digital footprint.", time_range),
-        "statistical_analysis" => format!("Provide statistical analysis of digital activities for the time period: {}. Include application usage statistics, time distribution, activity patterns, and quantitative insights about digital behavior.", time_range),
-        "past_hour" => format!("Analyze all digital activity for the time period: {}. Look for applications used, websites visited, communication activities, work tasks, and any important actions or decisions made.", time_range),
-        "github_activity" => format!("Analyze GitHub activities for the time period: {}. Look for repositories accessed, code commits, pull requests, issues, and any development work related to GitHub and version control.", time_range),
-        "slack_activity" => format!("Analyze Slack activities for the time period: {}. Look for channels accessed, messages sent/received, file sharing, and any important communications or collaborations in Slack.", time_range),
-        "discord_activity" => format!("Analyze Discord activities for the time period: {}. Look for servers accessed, channels used, messages sent/received, voice chat participation, and any gaming or community activities.", time_range),
-        "gitlab_activity" => format!("Analyze GitLab activities for the time period: {}. Look for repositories accessed, merge requests, issues, CI/CD activities, and any development work related to GitLab.", time_range),
-        "app_usage" => format!("Analyze application usage patterns for the time period: {}. Look for most used applications, time spent in each app, application switching patterns, and productivity insights based on app usage.", time_range),
-        "audio_files" => format!("Analyze audio recordings and transcriptions for the time period: {}. Look for conversations, meetings, voice notes, and any important audio content that was captured.", time_range),
-        "search_for_text" => format!("Search for and analyze all content for the time period: {}. Look for mentions, discussions, and related activities across all applications and websites.", time_range),
-        "screenshots_with_text" => format!("Find and analyze all screenshots and visual content for the time period: {}. Look for context, applications used, and related activities.", time_range),
-        "this_week_activity" => format!("Analyze all activity for the time period: {}. Provide a comprehensive overview of work patterns, productivity, communication, and key accomplishments across all applications and websites.", time_range),
-        "custom" => {
-            if let Some(prompt) = custom_prompt {
-                // Remove technical time range info from user-facing prompt
-                prompt.to_string()
-            } else {
-                "Analyze the digital activity data and provide insights about productivity, activities, and patterns. If no context or data is available, inform the user that no digital activity was recorded for the specified time period.".to_string()
-            }
-        },
-        _ => {
-            if let Some(prompt) = custom_prompt {
-                // Remove technical time range info from user-facing prompt
-                prompt.to_string()
-            } else {
-                "Analyze the digital activity data and provide insights about productivity, activities, and patterns. If no context or data is available, inform the user that no digital activity was recorded for the specified time period.".to_string()
-            }
-        }
+    // Build analysis query from the action's template, filled in with the time range
+    let action_template = match prompts::find(&format!("quick_action.{}", action_type)) {
+        Some(template) if action_type != "default" => Some(template),
+        _ => None,
     };
+    let template = match (action_template, &custom_prompt) {
+        (Some(template), _) => Some(template),
+        // Custom and unknown actions ask the user's prompt as written
+        (None, Some(_)) => None,
+        (None, None) => Some(prompts::template(prompts::QUICK_ACTION_DEFAULT)),
+    };
+    let analysis_query = match &template {
+        Some(template) => template.render(&[("time_range", &time_period)]),
+        None => custom_prompt.clone().unwrap_or_default(),
+    };
+
+    // Whole-period overviews read every capture through rolled-up summaries, not just the top chunks
+    if matches!(
+        action_type.as_str(),
+        "this_week_activity" | "daily_analysis"
+    ) {
+        return match summarize::answer_from_summaries(
+            &analysis_query,
+            window,
+            collection.as_deref(),
+        )
+        .await
+        {
+            Ok(result) => Ok(result.answer),
+            Err(e) => Err(format!("Failed to summarize activity: {}", e)),
+        };
+    }
+
+    // Restrict retrieval to the sources the action is about
+    let mut rag_query = rag::RAGQuery::new(analysis_query);
+    rag_query.top_k = 10;
+    rag_query.similarity_threshold = 0.1;
+    rag_query.filters = rag::RAGFilters::for_quick_action(&action_type);
+    rag_query.filters.start_time = Some(window.start.to_rfc3339());
+    rag_query.filters.end_time = Some(window.end.to_rfc3339());
+    rag_query.collection = collection;
+    rag_query.templates = template.iter().map(|template| template.usage()).collect();
 
     // Query the RAG system for analysis
-    match rag::query_rag_system(analysis_query.to_string(), Some(10), Some(0.1), None, None).await {
+    match rag::query_rag_system(rag_query, None).await {
         Ok(response) => {
             // Return just the answer from the RAG response
             Ok(response.answer)
@@ -629,24 +1011,60 @@ async fn check_for_updates() -> Result<serde_json::Value, String> {
     }))
 }
 
+// Summarize a long time range hour by hour, rolled up to days and weeks
+#[tauri::command]
+async fn summarize_time_range_cmd(
+    time_range: String,
+    question: Option<String>,
+    collection: Option<String>,
+) -> Result<summarize::SummaryAnswer, String> {
+    let window = time_expr::parse_time_expression(&time_range)?;
+
+    match rag::ingest_sql_data_rag(
+        Some(window),
+        rag::SqlIngestMode::Detailed,
+        None,
+        collection.clone(),
+    )
+    .await
+    {
+        Ok(_) => (),
+        Err(e) => return Err(format!("Failed to ingest SQL data: {}", e)),
+    }
+
+    let question = question.unwrap_or_else(|| {
+        "Summarize what I worked on, the main activities and any key accomplishments.".to_string()
+    });
+    match summarize::answer_from_summaries(&question, window, collection.as_deref()).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to summarize activity: {}", e)),
+    }
+}
+
+// Resolve a time phrase so the UI can show which window an analysis will cover
+#[tauri::command]
+fn parse_time_expression_cmd(expression: String) -> Result<serde_json::Value, String> {
+    let window = time_expr::parse_time_expression(&expression)?;
+    Ok(serde_json::json!({
+        "start": window.start.to_rfc3339(),
+        "end": window.end.to_rfc3339(),
+        "description": window.describe()
+    }))
+}
+
 #[tauri::command]
 async fn get_deep_app_usage_analytics(time_range: String) -> Result<serde_json::Value, String> {
-    // Convert time range string to TimeRange enum
-    let time_range_enum = match time_range.as_str() {
-        "daily" => TimeRange::Daily,
-        "weekly" => TimeRange::Weekly,
-        "monthly" => TimeRange::Monthly,
-        "yearly" => TimeRange::Yearly,
-        "all_time" => TimeRange::AllTime,
-        _ => {
-            return Err(
-                "Invalid time range. Use: daily, weekly, monthly, yearly, all_time".to_string(),
-            )
-        }
-    };
+    let window = time_expr::parse_time_expression(&time_range)?;
 
     // Build comprehensive SQL query for app usage analytics
-    let time_filter = time_range_enum.to_sql_filter();
+    let time_filter = window.to_sql_filter();
+    let exclusions = privacy::ExclusionRules::load();
+    // Excluding app usage as a source leaves nothing to analyze here
+    let privacy_filter = if exclusions.excludes_source("app_usage") {
+        "0".to_string()
+    } else {
+        exclusions.sql_condition()
+    };
     let query = format!(
         r#"
         WITH frame_durations AS (
@@ -661,7 +1079,7 @@ async fn get_deep_app_usage_analytics(time_range: String) -> Result<serde_json::
                     ELSE 0
                 END as calculated_duration_ms
             FROM frames f 
-            WHERE {} AND f.app_name IS NOT NULL AND f.app_name != ''
+            WHERE {} AND {} AND f.app_name IS NOT NULL AND f.app_name != ''
         ),
         app_usage_stats AS (
             SELECT 
@@ -729,7 +1147,7 @@ async fn get_deep_app_usage_analytics(time_range: String) -> Result<serde_json::
         ORDER BY aus.usage_count DESC, aus.total_duration_ms DESC
         LIMIT 100;
         "#,
-        time_filter
+        time_filter, privacy_filter
     );
 
     // Execute the query
@@ -805,6 +1223,8 @@ async fn get_deep_app_usage_analytics(time_range: String) -> Result<serde_json::
 
     let result = serde_json::json!({
         "time_range": time_range,
+        "start": window.start.to_rfc3339(),
+        "end": window.end.to_rfc3339(),
         "summary": {
             "total_apps": total_apps,
             "total_events": total_events,
@@ -838,6 +1258,10 @@ fn main() {
             get_export_files_cmd,
             get_export_status,
             analyze_data_with_ai,
+            create_chat_session_cmd,
+            list_chat_sessions_cmd,
+            get_chat_session_cmd,
+            delete_chat_session_cmd,
             set_openai_api_key,
             open_app_by_name,
             get_video_file,
@@ -852,9 +1276,35 @@ fn main() {
             initialize_rag_cmd,
             ingest_data_rag_cmd,
             query_rag_system_cmd,
+            stream_rag_query_cmd,
+            keyword_search_rag_cmd,
+            get_chunking_config_cmd,
+            set_chunking_config_cmd,
             clear_rag_data_cmd,
             get_rag_stats_cmd,
             ingest_sql_data_rag,
+            ingest_search_rag_cmd,
+            list_query_history_cmd,
+            get_query_trace_cmd,
+            clear_query_history_cmd,
+            get_redaction_config_cmd,
+            set_redaction_config_cmd,
+            preview_redaction_cmd,
+            list_redaction_reports_cmd,
+            get_exclusion_rules_cmd,
+            set_exclusion_rules_cmd,
+            get_llm_providers_cmd,
+            set_llm_providers_cmd,
+            list_agents_cmd,
+            get_agent_cmd,
+            save_agent_cmd,
+            delete_agent_cmd,
+            list_prompt_templates_cmd,
+            save_prompt_template_cmd,
+            delete_prompt_template_cmd,
+            create_rag_collection_cmd,
+            list_rag_collections_cmd,
+            drop_rag_collection_cmd,
             deep_analysis_rag,
             pure_rust_analysis,
             quick_action_rag,
@@ -866,6 +1316,8 @@ fn main() {
             get_app_categories,
             // Analytics commands
             get_deep_app_usage_analytics,
+            parse_time_expression_cmd,
+            summarize_time_range_cmd,
             // Update commands
             check_for_updates
         ])
@@ -0,0 +1,33 @@
+use std::fs;
+use std::path::PathBuf;
+
+// Must match "identifier" in tauri.conf.json so we share Tauri's app directories
+const APP_IDENTIFIER: &str = "com.one.projectone";
+
+/// Per-user data directory for the app, e.g. `%APPDATA%\com.one.projectone` on Windows.
+/// The directory is created if it does not exist yet.
+pub fn get_app_data_dir() -> Result<PathBuf, String> {
+    let base = dirs::data_dir().ok_or("Failed to resolve the user data directory")?;
+    let dir = base.join(APP_IDENTIFIER);
+
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+
+    Ok(dir)
+}
+
+/// Per-user config directory for the app, e.g. `%APPDATA%\com.one.projectone` on Windows and
+/// `~/.config/com.one.projectone` on Linux. The directory is created if it does not exist yet.
+pub fn get_app_config_dir() -> Result<PathBuf, String> {
+    let base = dirs::config_dir().ok_or("Failed to resolve the user config directory")?;
+    let dir = base.join(APP_IDENTIFIER);
+
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create app config directory: {}", e))?;
+    }
+
+    Ok(dir)
+}
@@ -0,0 +1,242 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::rag::ChunkMetadata;
+
+const RULES_FILE_NAME: &str = "privacy.json";
+
+/// Captures that are never ingested, analyzed or exported, persisted in the app data dir.
+/// App, window and URL patterns match case-insensitively anywhere in the value
+/// ("1password", "incognito", "mybank.com"); a `*` makes the pattern match the whole
+/// value instead ("*- Private Browsing").
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExclusionRules {
+    pub apps: Vec<String>,
+    pub window_patterns: Vec<String>,
+    pub url_patterns: Vec<String>,
+    /// "ocr", "audio", "app_usage", "ui"
+    pub source_types: Vec<String>,
+}
+
+impl ExclusionRules {
+    fn file_path() -> Result<PathBuf, String> {
+        Ok(crate::paths::get_app_data_dir()?.join(RULES_FILE_NAME))
+    }
+
+    /// Load the saved rules; no rules if there are none or they cannot be read
+    pub fn load() -> Self {
+        let path = match Self::file_path() {
+            Ok(path) => path,
+            Err(_) => return Self::default(),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                println!("[PRIVACY] Ignoring invalid {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize exclusion rules: {}", e))?;
+        fs::write(Self::file_path()?, content)
+            .map_err(|e| format!("Failed to save exclusion rules: {}", e))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+            && self.window_patterns.is_empty()
+            && self.url_patterns.is_empty()
+            && self.source_types.is_empty()
+    }
+
+    pub fn excludes_source(&self, source_type: &str) -> bool {
+        self.source_types
+            .iter()
+            .any(|excluded| excluded.eq_ignore_ascii_case(source_type))
+    }
+
+    /// Whether a capture from this app, window and URL must be left out, whatever its source type
+    pub fn excludes_context(
+        &self,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        url: Option<&str>,
+    ) -> bool {
+        let any_match = |patterns: &[String], value: Option<&str>| match value {
+            Some(value) if !value.is_empty() => patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, value)),
+            _ => false,
+        };
+        any_match(&self.apps, app_name)
+            || any_match(&self.window_patterns, window_name)
+            || any_match(&self.url_patterns, url)
+    }
+
+    pub fn excludes(
+        &self,
+        source_type: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        url: Option<&str>,
+    ) -> bool {
+        self.excludes_source(source_type) || self.excludes_context(app_name, window_name, url)
+    }
+
+    /// Whether an indexed chunk must be left out of retrieval. Time usage, screen time and
+    /// summary chunks are built from app usage, so they go with it.
+    pub fn excludes_chunk(&self, metadata: &ChunkMetadata) -> bool {
+        let source_type = match metadata.source_type.as_str() {
+            "time_usage" | "total_screen_time" | "summary" => "app_usage",
+            source_type => source_type,
+        };
+        self.excludes(
+            source_type,
+            metadata.app_name.as_deref(),
+            metadata.window_name.as_deref(),
+            metadata.browser_url.as_deref(),
+        )
+    }
+
+    /// `excludes_context` for a screenpipe row with `app_name`, `window_name` and `browser_url` columns
+    pub fn excludes_row(&self, row: &serde_json::Value) -> bool {
+        self.excludes_context(
+            row["app_name"].as_str(),
+            row["window_name"].as_str(),
+            row["browser_url"].as_str(),
+        )
+    }
+
+    /// Remove the columns of a screenpipe row that belong to an excluded source type
+    pub fn strip_excluded_sources(&self, row: &mut serde_json::Value) {
+        let Some(row) = row.as_object_mut() else {
+            return;
+        };
+        if self.excludes_source("ocr") {
+            row.remove("ocr_text");
+            row.remove("ocr_text_length");
+        }
+        if self.excludes_source("audio") {
+            row.remove("transcription");
+            row.remove("audio_file");
+        }
+    }
+
+    /// SQL condition on screenpipe's `frames f` that leaves out excluded apps, windows and URLs.
+    /// Source types cannot be told apart per frame and are left to the caller.
+    pub fn sql_condition(&self) -> String {
+        let mut conditions = Vec::new();
+        for (column, patterns) in [
+            ("f.app_name", &self.apps),
+            ("f.window_name", &self.window_patterns),
+            ("f.browser_url", &self.url_patterns),
+        ] {
+            for pattern in patterns.iter().filter(|p| !p.trim().is_empty()) {
+                conditions.push(format!(
+                    "COALESCE({}, '') LIKE '{}' ESCAPE '\\'",
+                    column,
+                    like_pattern(pattern)
+                ));
+            }
+        }
+
+        if conditions.is_empty() {
+            "1 = 1".to_string()
+        } else {
+            format!("NOT ({})", conditions.join(" OR "))
+        }
+    }
+}
+
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    if pattern.is_empty() {
+        return false;
+    }
+    let value = value.to_lowercase();
+    if !pattern.contains('*') {
+        return value.contains(&pattern);
+    }
+
+    let glob = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    Regex::new(&format!("^{}$", glob))
+        .map(|regex| regex.is_match(&value))
+        .unwrap_or(false)
+}
+
+// Same matching as `matches_pattern`; SQLite's LIKE is already case-insensitive for ASCII
+fn like_pattern(pattern: &str) -> String {
+    let escaped = pattern
+        .trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+        .replace('\'', "''");
+    if escaped.contains('*') {
+        escaped.replace('*', "%")
+    } else {
+        format!("%{}%", escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_anywhere_unless_they_have_a_wildcard() {
+        assert!(matches_pattern("1password", "1Password 7"));
+        assert!(matches_pattern(" incognito ", "New Tab - Incognito"));
+        assert!(!matches_pattern("", "anything"));
+
+        assert!(matches_pattern(
+            "*- private browsing",
+            "Bank - Private Browsing"
+        ));
+        assert!(!matches_pattern(
+            "*- private browsing",
+            "Private Browsing tips"
+        ));
+        assert!(matches_pattern("my*.com", "mybank.com"));
+        assert!(!matches_pattern("my*.com", "www.mybank.com/login"));
+        // Regex characters in a pattern are literal
+        assert!(matches_pattern("a.b*", "a.b.c"));
+        assert!(!matches_pattern("a.b*", "axb"));
+    }
+
+    #[test]
+    fn like_patterns_escape_sql_and_wildcard_characters() {
+        assert_eq!(like_pattern("mybank.com"), "%mybank.com%");
+        assert_eq!(like_pattern("*- Private Browsing"), "%- Private Browsing");
+        assert_eq!(like_pattern("100%_done"), "%100\\%\\_done%");
+        assert_eq!(like_pattern("C:\\Users"), "%C:\\\\Users%");
+        assert_eq!(like_pattern("O'Brien"), "%O''Brien%");
+    }
+
+    #[test]
+    fn sql_condition_leaves_out_every_pattern() {
+        assert_eq!(ExclusionRules::default().sql_condition(), "1 = 1");
+
+        let rules = ExclusionRules {
+            apps: vec!["1password".to_string(), " ".to_string()],
+            url_patterns: vec!["*.bank.com".to_string()],
+            ..ExclusionRules::default()
+        };
+        assert_eq!(
+            rules.sql_condition(),
+            "NOT (COALESCE(f.app_name, '') LIKE '%1password%' ESCAPE '\\' \
+             OR COALESCE(f.browser_url, '') LIKE '%.bank.com' ESCAPE '\\')"
+        );
+    }
+}
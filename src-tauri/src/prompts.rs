@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Answer prompt of the chat analysis; variables `{context}`, `{history}`, `{question}`,
+/// `{time_range}` and `{agent}`
+pub const ANALYSIS: &str = "analysis";
+/// Answer prompt of RAG queries; variables `{context}` and `{question}`
+pub const RAG_ANSWER: &str = "rag_answer";
+/// Query of a quick action without its own template; variable `{time_range}`
+pub const QUICK_ACTION_DEFAULT: &str = "quick_action.default";
+
+const TEMPLATES_DIR_NAME: &str = "templates";
+
+// (name, version, body); quick action templates are named "quick_action.<action type>"
+const BUILTIN_TEMPLATES: &[(&str, &str, &str)] = &[
+    (
+        ANALYSIS,
+        "1",
+        r#"You are an assistant secretary. When answering, provide polished, professional summaries and responses as if you are briefing an executive. Do not reference how you know the information, the user, or any context or data source. Simply present the information as a knowledgeable assistant secretary would, focusing on clarity, professionalism, and helpfulness.
+
+If asked for a summary of activities, list the applications, tasks, or events in a clear and concise manner. If asked for details, provide them directly and succinctly. If information is not available, state so politely and professionally.
+
+Always use clear, concise, and formal language. Do not mention 'context', 'user', or how the answer was derived.
+
+SUMMARY DATA:
+{context}
+
+{history}QUESTION:
+{question}
+"#,
+    ),
+    (
+        RAG_ANSWER,
+        "1",
+        r#"You are a helpful AI assistant analyzing digital activity data. Use the following context to answer the user's question accurately and insightfully.
+
+CONTEXT DATA:
+{context}
+
+USER QUESTION: {question}
+
+Please provide a comprehensive answer based on the context data above. Be specific and reference the actual content when possible. Cite the context entries that support each statement with their numbers in square brackets, e.g. [1] or [2][3]. If the context doesn't contain enough information to answer the question, say so clearly.
+
+Answer:"#,
+    ),
+    (
+        "quick_action.meeting_analysis",
+        "1",
+        r#"Analyze all meeting activities for the time period: {time_range}. Look for meeting durations, participants, topics discussed, and any important decisions or action items from meetings."#,
+    ),
+    (
+        "quick_action.coding_activity",
+        "1",
+        r#"Analyze all coding and development activities for the time period: {time_range}. Look for programming languages used, development tools, code repositories, debugging sessions, and any technical work or problem-solving activities."#,
+    ),
+    (
+        "quick_action.productivity_analysis",
+        "1",
+        r#"Analyze overall productivity patterns for the time period: {time_range}. Look for focused work sessions, application usage patterns, task switching frequency, and identify the most productive periods and activities."#,
+    ),
+    (
+        "quick_action.communication_analysis",
+        "1",
+        r#"Analyze all communication activities for the time period: {time_range}. Look for emails, chat messages, social media, and messaging platforms. Look for important conversations, communication patterns, and any urgent or significant messages."#,
+    ),
+    (
+        "quick_action.research_analysis",
+        "1",
+        r#"Analyze research and learning activities for the time period: {time_range}. Look for documentation reading, tutorial watching, educational content consumption, and any research or learning-related activities."#,
+    ),
+    (
+        "quick_action.web_browsing",
+        "1",
+        r#"Analyze web browsing patterns and activities for the time period: {time_range}. Look for websites visited, browsing purposes, research activities, and any important information gathered from web browsing."#,
+    ),
+    (
+        "quick_action.time_usage",
+        "1",
+        r#"Analyze time usage patterns for the time period: {time_range}. Provide insights into how time was distributed, most time-consuming activities, and efficiency patterns."#,
+    ),
+    (
+        "quick_action.daily_analysis",
+        "1",
+        r#"Provide a comprehensive activity analysis for the time period: {time_range}. Cover all digital activities, productivity patterns, important events, and give a complete overview of the digital footprint."#,
+    ),
+    (
+        "quick_action.statistical_analysis",
+        "1",
+        r#"Provide statistical analysis of digital activities for the time period: {time_range}. Include application usage statistics, time distribution, activity patterns, and quantitative insights about digital behavior."#,
+    ),
+    (
+        "quick_action.past_hour",
+        "1",
+        r#"Analyze all digital activity for the time period: {time_range}. Look for applications used, websites visited, communication activities, work tasks, and any important actions or decisions made."#,
+    ),
+    (
+        "quick_action.github_activity",
+        "1",
+        r#"Analyze GitHub activities for the time period: {time_range}. Look for repositories accessed, code commits, pull requests, issues, and any development work related to GitHub and version control."#,
+    ),
+    (
+        "quick_action.slack_activity",
+        "1",
+        r#"Analyze Slack activities for the time period: {time_range}. Look for channels accessed, messages sent/received, file sharing, and any important communications or collaborations in Slack."#,
+    ),
+    (
+        "quick_action.discord_activity",
+        "1",
+        r#"Analyze Discord activities for the time period: {time_range}. Look for servers accessed, channels used, messages sent/received, voice chat participation, and any gaming or community activities."#,
+    ),
+    (
+        "quick_action.gitlab_activity",
+        "1",
+        r#"Analyze GitLab activities for the time period: {time_range}. Look for repositories accessed, merge requests, issues, CI/CD activities, and any development work related to GitLab."#,
+    ),
+    (
+        "quick_action.app_usage",
+        "1",
+        r#"Analyze application usage patterns for the time period: {time_range}. Look for most used applications, time spent in each app, application switching patterns, and productivity insights based on app usage."#,
+    ),
+    (
+        "quick_action.audio_files",
+        "1",
+        r#"Analyze audio recordings and transcriptions for the time period: {time_range}. Look for conversations, meetings, voice notes, and any important audio content that was captured."#,
+    ),
+    (
+        "quick_action.search_for_text",
+        "1",
+        r#"Search for and analyze all content for the time period: {time_range}. Look for mentions, discussions, and related activities across all applications and websites."#,
+    ),
+    (
+        "quick_action.screenshots_with_text",
+        "1",
+        r#"Find and analyze all screenshots and visual content for the time period: {time_range}. Look for context, applications used, and related activities."#,
+    ),
+    (
+        "quick_action.this_week_activity",
+        "1",
+        r#"Analyze all activity for the time period: {time_range}. Provide a comprehensive overview of work patterns, productivity, communication, and key accomplishments across all applications and websites."#,
+    ),
+    (
+        QUICK_ACTION_DEFAULT,
+        "1",
+        r#"Analyze the digital activity data and provide insights about productivity, activities, and patterns. If no context or data is available, inform the user that no digital activity was recorded for the specified time period."#,
+    ),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateSource {
+    Builtin,
+    User,
+}
+
+/// A prompt with `{variable}` placeholders
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: String,
+    pub source: TemplateSource,
+    pub body: String,
+}
+
+/// Which template and version produced a prompt
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TemplateUse {
+    pub name: String,
+    pub version: String,
+    pub source: TemplateSource,
+}
+
+impl PromptTemplate {
+    /// Fill in the placeholders named in `vars` in one pass; values are never scanned again,
+    /// so retrieved text containing `{question}` stays as it is. Other braces are kept.
+    pub fn render(&self, vars: &[(&str, &str)]) -> String {
+        let mut rendered = String::with_capacity(self.body.len());
+        let mut rest = self.body.as_str();
+        while let Some(open) = rest.find('{') {
+            rendered.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            let value = after.find('}').and_then(|close| {
+                vars.iter()
+                    .find(|(name, _)| *name == &after[..close])
+                    .map(|(_, value)| (*value, close))
+            });
+            match value {
+                Some((value, close)) => {
+                    rendered.push_str(value);
+                    rest = &after[close + 1..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = after;
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    pub fn usage(&self) -> TemplateUse {
+        TemplateUse {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            source: self.source,
+        }
+    }
+}
+
+/// User templates live in `<app config dir>/templates` as `<name>@<version>.txt`,
+/// e.g. `rag_answer@2.txt`; the highest version of a name overrides the built-in one
+pub fn templates_dir() -> Result<PathBuf, String> {
+    let dir = crate::paths::get_app_config_dir()?.join(TEMPLATES_DIR_NAME);
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create templates directory: {}", e))?;
+    }
+    Ok(dir)
+}
+
+fn builtin(name: &str) -> Option<PromptTemplate> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(builtin_name, _, _)| *builtin_name == name)
+        .map(|(name, version, body)| PromptTemplate {
+            name: name.to_string(),
+            version: version.to_string(),
+            source: TemplateSource::Builtin,
+            body: body.to_string(),
+        })
+}
+
+// Where a user version of a built-in template lives. Both parts come from the frontend, so
+// they are checked before they become a path.
+fn user_template_path(name: &str, version: &str) -> Result<PathBuf, String> {
+    if builtin(name).is_none() {
+        return Err(format!("No built-in template named {}", name));
+    }
+    if !valid_tag(version) {
+        return Err("Template versions may only use letters, digits, '.', '-' and '_'".to_string());
+    }
+    Ok(templates_dir()?.join(format!("{}@{}.txt", name, version)))
+}
+
+fn valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+}
+
+// Every `<name>@<version>.txt` in the templates directory
+fn user_templates() -> Vec<PromptTemplate> {
+    let entries = match templates_dir().and_then(|dir| {
+        fs::read_dir(dir).map_err(|e| format!("Failed to read templates directory: {}", e))
+    }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut templates = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let Some((name, version)) = stem.rsplit_once('@') else {
+            println!(
+                "[PROMPTS] Ignoring {}: no @version in the name",
+                path.display()
+            );
+            continue;
+        };
+        match fs::read_to_string(&path) {
+            Ok(body) => templates.push(PromptTemplate {
+                name: name.to_string(),
+                version: version.to_string(),
+                source: TemplateSource::User,
+                body,
+            }),
+            Err(e) => println!("[PROMPTS] Ignoring {}: {}", path.display(), e),
+        }
+    }
+    templates
+}
+
+// Numbers in a version tag compare numerically, so "10" is newer than "9" and "2.1" than "2"
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+/// The template in use under `name`: the newest user version, or the built-in one
+pub fn find(name: &str) -> Option<PromptTemplate> {
+    user_templates()
+        .into_iter()
+        .filter(|template| template.name == name)
+        .max_by(|a, b| {
+            version_key(&a.version)
+                .cmp(&version_key(&b.version))
+                .then_with(|| a.version.cmp(&b.version))
+        })
+        .or_else(|| builtin(name))
+}
+
+/// `find` for the built-in template names, which always resolve
+pub fn template(name: &str) -> PromptTemplate {
+    find(name).unwrap_or_else(|| {
+        println!("[PROMPTS] No template named {}", name);
+        PromptTemplate {
+            name: name.to_string(),
+            version: "missing".to_string(),
+            source: TemplateSource::Builtin,
+            body: String::new(),
+        }
+    })
+}
+
+/// Built-in templates followed by every user version
+pub fn list_templates() -> Vec<PromptTemplate> {
+    let mut templates: Vec<PromptTemplate> = BUILTIN_TEMPLATES
+        .iter()
+        .filter_map(|(name, _, _)| builtin(name))
+        .collect();
+    let mut user = user_templates();
+    user.sort_by(|a, b| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| version_key(&a.version).cmp(&version_key(&b.version)))
+    });
+    templates.extend(user);
+    templates
+}
+
+/// Write a user version of a built-in template; it takes over if it is the newest version
+pub fn save_template(name: &str, version: &str, body: &str) -> Result<(), String> {
+    let path = user_template_path(name, version)?;
+    fs::write(path, body).map_err(|e| format!("Failed to save template: {}", e))
+}
+
+pub fn delete_template(name: &str, version: &str) -> Result<(), String> {
+    let path = user_template_path(name, version)?;
+    if !path.exists() {
+        return Err(format!("No user template {}@{}", name, version));
+    }
+    fs::remove_file(path).map_err(|e| format!("Failed to delete template: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_template_paths_stay_in_the_templates_directory() {
+        for (name, version) in [
+            ("../../secrets", "1"),
+            ("unknown_template", "1"),
+            (RAG_ANSWER, ""),
+            (RAG_ANSWER, "../../../notes"),
+            (RAG_ANSWER, "v1/../../x"),
+            (RAG_ANSWER, "v1\\..\\x"),
+        ] {
+            assert!(
+                user_template_path(name, version).is_err(),
+                "{}@{} should be rejected",
+                name,
+                version
+            );
+        }
+    }
+}
@@ -1,11 +1,119 @@
 use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 use uuid::Uuid;
 
+use crate::ai::DeltaSink;
+use crate::bm25::Bm25Index;
+use crate::budget::{BudgetReport, ContextBudget};
+use crate::chunking::{
+    group_transcript, ChunkingConfig, ChunkingPolicy, ChunkingStrategy, TranscriptSegment,
+};
+use crate::dedup::{fnv1a_hash, is_near_duplicate, simhash};
+use crate::ingest::{Capture, ExportIngestor, Ingestor, SearchIngestor, SqlRowIngestor};
+use crate::llm::{complete_with_fallback, CompletionOptions, LlmProvider, ProviderAnswer};
+use crate::privacy::ExclusionRules;
+use crate::prompts::{self, PromptTemplate, TemplateUse};
+use crate::rag_store::ChunkStore;
+use crate::time_expr::TimeWindow;
+use crate::trace::{QueryTrace, TraceChunk};
+
 // RAG Configuration
-const CHUNK_SIZE: usize = 1000;
+// Model chunk sizes are matched to when none has been selected yet
+const DEFAULT_CHUNK_MODEL: &str = "gemma3n:latest";
+const DEFAULT_TOP_K: usize = 5;
+const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.1;
+const EMBEDDING_MODEL: &str = "nomic-embed-text";
+const EMBEDDING_BATCH_SIZE: usize = 32;
+// Standard RRF damping constant
+const RRF_K: f32 = 60.0;
+// How many fused candidates the judge model sees, and how much of each
+const RERANK_CANDIDATES: usize = 20;
+const RERANK_PASSAGE_CHARS: usize = 500;
+// Most `/search` results indexed per call, fetched a page at a time
+const SEARCH_INGEST_LIMIT: usize = 1000;
+const SEARCH_PAGE_SIZE: usize = 100;
+// Rows per `/raw_sql` request when indexing every capture of a window
+const SQL_PAGE_SIZE: usize = 1000;
+
+// Filter out SQL-specific terms and common words that don't help with keyword matching
+const STOP_WORDS: &[&str] = &[
+    "f.timestamp",
+    "datetime",
+    "now",
+    "hour",
+    "day",
+    "month",
+    "year",
+    "the",
+    "for",
+    "and",
+    "or",
+    "in",
+    "on",
+    "at",
+    "to",
+    "from",
+    "with",
+    "by",
+    "of",
+    "a",
+    "an",
+    "is",
+    "are",
+    "was",
+    "were",
+    "be",
+    "been",
+    "have",
+    "has",
+    "had",
+    "do",
+    "does",
+    "did",
+    "will",
+    "would",
+    "could",
+    "should",
+    "may",
+    "might",
+    "can",
+    "must",
+    "shall",
+    "period:",
+    "look",
+    "used,",
+    "visited,",
+    "activities,",
+    "tasks,",
+    "made.",
+    "analyze",
+    "all",
+    "digital",
+    "activity",
+    "applications",
+    "websites",
+    "communication",
+    "work",
+    "important",
+    "actions",
+    "decisions",
+];
+
+// Used when a query has no meaningful keywords left after stop-word filtering
+const FALLBACK_SEARCH_TERMS: &[&str] = &[
+    "app",
+    "activity",
+    "time",
+    "work",
+    "communication",
+    "development",
+    "meeting",
+    "coding",
+];
 
 // Data structures for RAG
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -13,6 +121,9 @@ pub struct DataChunk {
     pub id: String,
     pub content: String,
     pub metadata: ChunkMetadata,
+    // Stored alongside the chunk but never sent to the frontend
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,13 +134,266 @@ pub struct ChunkMetadata {
     pub window_name: Option<String>,
     pub speaker_id: Option<String>,
     pub file_index: Option<usize>,
+    #[serde(default)]
+    pub frame_id: Option<i64>,
+    #[serde(default)]
+    pub browser_url: Option<String>,
+    // Provenance back to the screenpipe recording, so the UI can jump to the exact moment
+    #[serde(default)]
+    pub video_file: Option<String>,
+    #[serde(default)]
+    pub audio_file: Option<String>,
+    /// Offsets in seconds into `audio_file`
+    #[serde(default)]
+    pub audio_start_time: Option<f64>,
+    #[serde(default)]
+    pub audio_end_time: Option<f64>,
+    #[serde(default)]
+    pub transcription_engine: Option<String>,
+    #[serde(default)]
+    pub ocr_length: Option<u64>,
+    /// Source keys of further transcript rows merged into this chunk
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged_keys: Vec<String>,
+    // Set once near-identical captures have been collapsed into this chunk
+    #[serde(default)]
+    pub repeat_count: Option<u32>,
+    #[serde(default)]
+    pub first_seen: Option<String>,
+    #[serde(default)]
+    pub last_seen: Option<String>,
+}
+
+impl ChunkMetadata {
+    /// Count another capture of the same content seen at `timestamp`
+    fn record_repeat(&mut self, timestamp: Option<&str>) {
+        self.repeat_count = Some(self.repeat_count.unwrap_or(1) + 1);
+        if self.first_seen.is_none() {
+            self.first_seen = self.timestamp.clone();
+        }
+        if self.last_seen.is_none() {
+            self.last_seen = self.timestamp.clone();
+        }
+
+        let Some(timestamp) = timestamp else {
+            return;
+        };
+        if self
+            .first_seen
+            .as_deref()
+            .map_or(true, |first| is_earlier(timestamp, first))
+        {
+            self.first_seen = Some(timestamp.to_string());
+        }
+        if self
+            .last_seen
+            .as_deref()
+            .map_or(true, |last| is_earlier(last, timestamp))
+        {
+            self.last_seen = Some(timestamp.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RetrievalMode {
+    /// Embedding similarity only
+    Semantic,
+    /// BM25 keyword matching only
+    Lexical,
+    /// Both signals merged with reciprocal rank fusion
+    #[default]
+    Hybrid,
+}
+
+// Optional (start, end) bounds of a time filter
+type TimeBounds = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Structured filters applied to chunk metadata before any scoring.
+/// Empty lists mean "no restriction"; app and domain matching is case-insensitive.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RAGFilters {
+    /// Only chunks whose app name contains one of these
+    pub apps: Vec<String>,
+    /// Drop chunks whose app name contains one of these
+    pub exclude_apps: Vec<String>,
+    /// e.g. "ocr", "audio", "app_usage"
+    pub source_types: Vec<String>,
+    /// Inclusive RFC 3339 bounds on the chunk timestamp
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    /// Browser domains; subdomains match too ("github.com" matches "gist.github.com")
+    pub domains: Vec<String>,
+    /// Only chunks of these screenpipe frames; unlike the lists above, empty matches nothing
+    pub frame_ids: Option<Vec<i64>>,
+}
+
+impl RAGFilters {
+    /// Filters for quick actions that target a specific app, site or source
+    pub fn for_quick_action(action_type: &str) -> Self {
+        let mut filters = Self::default();
+        match action_type {
+            "slack_activity" => filters.apps = vec!["slack".to_string()],
+            "discord_activity" => filters.apps = vec!["discord".to_string()],
+            "github_activity" => filters.domains = vec!["github.com".to_string()],
+            "gitlab_activity" => filters.domains = vec!["gitlab.com".to_string()],
+            "audio_files" => filters.source_types = vec!["audio".to_string()],
+            "screenshots_with_text" => filters.source_types = vec!["ocr".to_string()],
+            "app_usage" => {
+                filters.source_types = vec!["app_usage".to_string(), "time_usage".to_string()]
+            }
+            _ => {}
+        }
+        filters
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+            && self.exclude_apps.is_empty()
+            && self.source_types.is_empty()
+            && self.start_time.is_none()
+            && self.end_time.is_none()
+            && self.domains.is_empty()
+            && self.frame_ids.is_none()
+    }
+
+    fn time_bounds(&self) -> Result<TimeBounds> {
+        let parse_bound = |bound: &Option<String>| -> Result<Option<DateTime<Utc>>> {
+            match bound {
+                Some(value) => parse_chunk_time(value)
+                    .map(Some)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid filter timestamp: {}", value)),
+                None => Ok(None),
+            }
+        };
+        Ok((parse_bound(&self.start_time)?, parse_bound(&self.end_time)?))
+    }
+
+    fn matches(
+        &self,
+        metadata: &ChunkMetadata,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> bool {
+        if !self.source_types.is_empty()
+            && !self
+                .source_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(&metadata.source_type))
+        {
+            return false;
+        }
+
+        let app = metadata.app_name.as_deref().unwrap_or("").to_lowercase();
+        if !self.apps.is_empty() && !self.apps.iter().any(|a| app.contains(&a.to_lowercase())) {
+            return false;
+        }
+        if self
+            .exclude_apps
+            .iter()
+            .any(|a| app.contains(&a.to_lowercase()))
+        {
+            return false;
+        }
+
+        if !self.domains.is_empty() {
+            let domain = metadata
+                .browser_url
+                .as_deref()
+                .and_then(extract_domain)
+                .map(|d| d.to_lowercase());
+            let Some(domain) = domain else {
+                return false;
+            };
+            let matches_domain = self.domains.iter().any(|d| {
+                let d = d.to_lowercase();
+                domain == d || domain.ends_with(&format!(".{}", d))
+            });
+            if !matches_domain {
+                return false;
+            }
+        }
+
+        if let Some(frame_ids) = &self.frame_ids {
+            if !metadata
+                .frame_id
+                .map_or(false, |frame_id| frame_ids.contains(&frame_id))
+            {
+                return false;
+            }
+        }
+
+        if start.is_some() || end.is_some() {
+            let Some(timestamp) = metadata.timestamp.as_deref().and_then(parse_chunk_time) else {
+                return false;
+            };
+            if start.map_or(false, |start| timestamp < start)
+                || end.map_or(false, |end| timestamp > end)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RAGQuery {
     pub query: String,
+    #[serde(default = "default_top_k")]
     pub top_k: usize,
+    #[serde(default = "default_similarity_threshold")]
     pub similarity_threshold: f32,
+    #[serde(default)]
+    pub retrieval_mode: RetrievalMode,
+    // Rerank fused candidates with the selected Ollama model before taking top_k
+    #[serde(default)]
+    pub rerank: bool,
+    #[serde(default)]
+    pub filters: RAGFilters,
+    /// Collection to search; None is the default collection
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Return the retrieval trace with the response. It is kept in the query history either way.
+    #[serde(default)]
+    pub include_trace: bool,
+    /// Prompt templates the query text was rendered from, recorded in the trace
+    #[serde(skip)]
+    pub templates: Vec<TemplateUse>,
+}
+
+fn default_top_k() -> usize {
+    DEFAULT_TOP_K
+}
+
+fn default_similarity_threshold() -> f32 {
+    DEFAULT_SIMILARITY_THRESHOLD
+}
+
+impl RAGQuery {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            top_k: DEFAULT_TOP_K,
+            similarity_threshold: DEFAULT_SIMILARITY_THRESHOLD,
+            retrieval_mode: RetrievalMode::default(),
+            rerank: false,
+            filters: RAGFilters::default(),
+            collection: None,
+            include_trace: false,
+            templates: Vec::new(),
+        }
+    }
+}
+
+/// Chunks retrieved for a query, best first, before any answer is generated
+pub struct RAGRetrieval {
+    pub chunks: Vec<DataChunk>,
+    pub scores: Vec<f32>,
+    pub trace: QueryTrace,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,102 +401,458 @@ pub struct RAGResponse {
     pub answer: String,
     pub context_chunks: Vec<DataChunk>,
     pub similarity_scores: Vec<f32>,
+    #[serde(default)]
+    pub citations: Vec<Citation>,
+    /// How the context was fitted to the model's window, including chunks left out
+    #[serde(default)]
+    pub budget: Option<BudgetReport>,
+    /// Provider and model that wrote the answer
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Retrieval trace, when the query asked for it
+    #[serde(default)]
+    pub trace: Option<QueryTrace>,
 }
 
-#[derive(Clone)]
-pub struct RAGSystem {
+/// A statement from the answer and the context chunks it cites.
+/// Provenance (frame, video/audio file, offsets, URL) is on each chunk's metadata.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Citation {
+    pub claim: String,
+    /// Positions in `RAGResponse::context_chunks`
+    pub chunk_indices: Vec<usize>,
+    pub chunk_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub chunk_count: usize,
+    /// Whether the collection is currently held in memory
+    pub loaded: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeywordSearchResponse {
+    pub chunks: Vec<DataChunk>,
+    pub scores: Vec<f32>,
+    pub total_matches: usize,
+}
+
+/// Searchable part of a collection, shared with queries as an immutable snapshot.
+/// Writers update it copy-on-write (`Arc::make_mut`), so a query never copies the chunks
+/// and only an ingestion that overlaps a running query pays for a clone.
+#[derive(Clone, Default)]
+pub struct RAGIndex {
     chunks: Vec<DataChunk>,
+    // BM25 index over chunk contents; document positions match `chunks`
+    lexical_index: Bm25Index,
+    // Model that embeds and answers for this collection; None uses the configured providers
+    model: Option<Arc<dyn LlmProvider>>,
+    // Privacy rules applied when ingesting and retrieving; the saved ones unless replaced
+    exclusions: ExclusionRules,
+}
+
+pub struct RAGSystem {
+    index: Arc<RAGIndex>,
+    // Persistent backing store; None means the index only lives in memory
+    store: Option<ChunkStore>,
+    // Source keys (see `frame_source_key`) already indexed, used for incremental ingestion
+    ingested_keys: HashSet<String>,
+    // OCR SimHash fingerprints per app/window, with the chunk ids of the capture they came from
+    ocr_fingerprints: HashMap<String, Vec<(u64, Vec<String>)>>,
+    chunking: ChunkingPolicy,
+}
+
+/// Outcome of adding a batch of chunks to the index
+#[derive(Debug, Default)]
+pub struct AddedChunks {
+    pub added: usize,
+    /// OCR captures collapsed into an earlier near-identical capture
+    pub duplicates_dropped: usize,
+}
+
+/// What one ingestion run did with the captures it was given
+#[derive(Debug, Default)]
+pub struct IngestStats {
+    pub captures: usize,
+    pub ocr_chunks: usize,
+    pub audio_chunks: usize,
+    pub app_chunks: usize,
+    /// Records skipped because their source key is already indexed
+    pub already_indexed: usize,
+    /// Records left out by the privacy exclusion rules
+    pub excluded: usize,
+    pub duplicates_dropped: usize,
+    /// Chunks new to the index
+    pub added: usize,
+}
+
+impl IngestStats {
+    /// Records chunked in this run
+    pub fn total(&self) -> usize {
+        self.ocr_chunks + self.audio_chunks + self.app_chunks
+    }
+
+    fn log(&self, source_name: &str) {
+        println!("[RAG] Ingestion Summary ({}):", source_name);
+        println!("[RAG]   Total rows processed: {}", self.captures);
+        println!("[RAG]   OCR chunks created: {}", self.ocr_chunks);
+        println!("[RAG]   Audio chunks created: {}", self.audio_chunks);
+        println!("[RAG]   App usage chunks created: {}", self.app_chunks);
+        println!(
+            "[RAG]   Already indexed (skipped): {}",
+            self.already_indexed
+        );
+        println!("[RAG]   Excluded by privacy rules: {}", self.excluded);
+        println!(
+            "[RAG]   Near-duplicate OCR collapsed: {}",
+            self.duplicates_dropped
+        );
+        println!("[RAG]   Total chunks: {}", self.total());
+    }
+}
+
+// Audio row waiting to be grouped into speaker turns
+struct PendingTranscript {
+    // Text the row would be chunked as on its own; its hash is the row's source key
+    text: String,
+    transcription: String,
+    metadata: serde_json::Value,
+    row_index: usize,
+}
+
+impl PendingTranscript {
+    fn source_key(&self) -> Option<String> {
+        frame_source_key(self.metadata["frame_id"].as_i64(), "audio", &self.text)
+    }
 }
 
 impl RAGSystem {
     pub fn new() -> Self {
-        Self { chunks: Vec::new() }
+        Self::with_model(None)
     }
 
-    pub fn ingest_data(&mut self, data_files: &[serde_json::Value]) -> Result<usize> {
-        let mut total_chunks = 0;
-
-        for (file_index, file_data) in data_files.iter().enumerate() {
-            // Process OCR data
-            if let Some(ocr_array) = file_data["ocr"].as_array() {
-                for ocr_item in ocr_array {
-                    if let Some(text) = ocr_item["text"].as_str() {
-                        let chunks = self.chunk_text(text, "ocr", file_index, ocr_item)?;
-                        let chunk_count = chunks.len();
-                        self.chunks.extend(chunks);
-                        total_chunks += chunk_count;
-                    }
-                }
+    /// In-memory RAG system that embeds and answers with this model, e.g. an offline one;
+    /// None uses the configured providers
+    pub fn with_model(model: Option<Arc<dyn LlmProvider>>) -> Self {
+        Self {
+            index: Arc::new(RAGIndex {
+                model,
+                ..RAGIndex::with_saved_rules()
+            }),
+            store: None,
+            ingested_keys: HashSet::new(),
+            ocr_fingerprints: HashMap::new(),
+            chunking: ChunkingPolicy::load(),
+        }
+    }
+
+    /// Build a RAG system on top of a persistent store, loading every chunk it holds
+    pub fn with_store(store: ChunkStore) -> Result<Self> {
+        let chunks = store.load_chunks()?;
+        let mut system = Self {
+            index: Arc::new(RAGIndex::with_saved_rules()),
+            store: Some(store),
+            ingested_keys: HashSet::new(),
+            ocr_fingerprints: HashMap::new(),
+            chunking: ChunkingPolicy::load(),
+        };
+        system.register_fingerprints(&chunks);
+        system.index_loaded(chunks);
+        if let Some(store) = &system.store {
+            system.ingested_keys.extend(store.load_source_keys()?);
+        }
+        println!(
+            "[RAG] Loaded {} chunks ({} sources) from persistent store",
+            system.index.chunks.len(),
+            system.ingested_keys.len()
+        );
+        Ok(system)
+    }
+
+    /// Open a collection's on-disk store, falling back to an in-memory index if it is unavailable
+    pub fn load_collection(name: &str) -> Self {
+        match ChunkStore::open_collection(name).and_then(Self::with_store) {
+            Ok(system) => system,
+            Err(e) => {
+                println!(
+                    "[RAG] Failed to open persistent store, using in-memory index: {}",
+                    e
+                );
+                Self::new()
             }
+        }
+    }
 
-            // Process Audio data
-            if let Some(audio_array) = file_data["audio"].as_array() {
-                for audio_item in audio_array {
-                    if let Some(transcription) = audio_item["transcription"].as_str() {
-                        let chunks =
-                            self.chunk_text(transcription, "audio", file_index, audio_item)?;
-                        let chunk_count = chunks.len();
-                        self.chunks.extend(chunks);
-                        total_chunks += chunk_count;
-                    }
+    fn index_loaded(&mut self, chunks: Vec<DataChunk>) {
+        let index = Arc::make_mut(&mut self.index);
+        for chunk in &chunks {
+            if chunk.metadata.frame_id.is_some() {
+                // Frame-backed ids are "<source key>:<chunk index>"
+                if let Some((key, _)) = chunk.id.rsplit_once(':') {
+                    self.ingested_keys.insert(key.to_string());
                 }
             }
+            self.ingested_keys
+                .extend(chunk.metadata.merged_keys.iter().cloned());
+            index.lexical_index.add_document(&chunk.content);
+        }
+        index.chunks.extend(chunks);
+    }
+
+    /// Whether this exact text from a screenpipe frame has already been ingested
+    pub fn is_frame_ingested(&self, frame_id: Option<i64>, source_type: &str, text: &str) -> bool {
+        match frame_source_key(frame_id, source_type, text) {
+            Some(key) => self.ingested_keys.contains(&key),
+            None => false,
+        }
+    }
+
+    /// Persist a batch of chunks and add them to the in-memory index.
+    /// Near-duplicate OCR captures are collapsed into the chunk they repeat.
+    pub fn add_chunks(&mut self, chunks: Vec<DataChunk>) -> Result<AddedChunks> {
+        if chunks.is_empty() {
+            return Ok(AddedChunks::default());
+        }
+
+        let (chunks, duplicates_dropped) = self.collapse_near_duplicates(chunks)?;
+
+        if let Some(store) = &self.store {
+            store.insert_chunks(&chunks)?;
+        }
+
+        let known_ids: HashSet<&str> = self.index.chunks.iter().map(|c| c.id.as_str()).collect();
+        let new_chunks: Vec<DataChunk> = chunks
+            .into_iter()
+            .filter(|c| !known_ids.contains(c.id.as_str()))
+            .collect();
+        let added = new_chunks.len();
+        self.index_loaded(new_chunks);
+        Ok(AddedChunks {
+            added,
+            duplicates_dropped,
+        })
+    }
+
+    fn register_fingerprints(&mut self, chunks: &[DataChunk]) {
+        for capture in group_by_capture(chunks.to_vec()) {
+            if capture[0].metadata.source_type != "ocr" {
+                continue;
+            }
+            let fingerprint = simhash(&capture_text(&capture));
+            self.ocr_fingerprints
+                .entry(dedup_scope(&capture[0].metadata))
+                .or_default()
+                .push((fingerprint, capture.iter().map(|c| c.id.clone()).collect()));
+        }
+    }
+
+    /// Drop OCR captures that nearly repeat an earlier capture of the same app/window,
+    /// either already indexed or earlier in this batch. The kept chunk records the
+    /// first/last seen timestamps and the repeat count.
+    fn collapse_near_duplicates(
+        &mut self,
+        chunks: Vec<DataChunk>,
+    ) -> Result<(Vec<DataChunk>, usize)> {
+        let positions: HashMap<String, usize> = self
+            .index
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(idx, chunk)| (chunk.id.clone(), idx))
+            .collect();
+
+        let mut kept: Vec<DataChunk> = Vec::new();
+        let mut updated_existing: HashSet<usize> = HashSet::new();
+        let mut dropped_keys = Vec::new();
+
+        for capture in group_by_capture(chunks) {
+            if capture[0].metadata.source_type != "ocr" {
+                kept.extend(capture);
+                continue;
+            }
 
-            // Process App Usage data
-            if let Some(app_usage) = file_data["app_usage"].as_object() {
-                if let Some(app_name) = app_usage["app_name"].as_str() {
-                    let usage_text = format!("App: {} - Usage data", app_name);
-                    let chunks = self.chunk_text(
-                        &usage_text,
-                        "app_usage",
-                        file_index,
-                        &serde_json::json!({
-                            "app_name": app_name
-                        }),
-                    )?;
-                    let chunk_count = chunks.len();
-                    self.chunks.extend(chunks);
-                    total_chunks += chunk_count;
+            let fingerprint = simhash(&capture_text(&capture));
+            let entries = self
+                .ocr_fingerprints
+                .entry(dedup_scope(&capture[0].metadata))
+                .or_default();
+
+            let original = entries
+                .iter()
+                .find(|(existing, _)| is_near_duplicate(*existing, fingerprint))
+                .map(|(_, ids)| ids.clone());
+
+            let Some(original_ids) = original else {
+                entries.push((fingerprint, capture.iter().map(|c| c.id.clone()).collect()));
+                kept.extend(capture);
+                continue;
+            };
+
+            let seen_at = capture[0].metadata.timestamp.as_deref();
+            for id in &original_ids {
+                if let Some(&idx) = positions.get(id) {
+                    Arc::make_mut(&mut self.index).chunks[idx]
+                        .metadata
+                        .record_repeat(seen_at);
+                    updated_existing.insert(idx);
+                } else if let Some(chunk) = kept.iter_mut().find(|c| &c.id == id) {
+                    chunk.metadata.record_repeat(seen_at);
                 }
             }
+            dropped_keys.push(capture_key(&capture[0]));
+        }
+
+        if let Some(store) = &self.store {
+            let updated: Vec<&DataChunk> = updated_existing
+                .iter()
+                .map(|&idx| &self.index.chunks[idx])
+                .collect();
+            store.update_metadata(&updated)?;
+            store.insert_source_keys(&dropped_keys)?;
         }
 
-        println!("[RAG] Ingested {} total chunks", total_chunks);
-        Ok(total_chunks)
+        let duplicates = dropped_keys.len();
+        if duplicates > 0 {
+            println!("[RAG] Collapsed {} near-duplicate OCR captures", duplicates);
+        }
+        self.ingested_keys.extend(dropped_keys);
+        Ok((kept, duplicates))
     }
 
-    fn chunk_text(
-        &self,
-        text: &str,
+    /// Replace every chunk of a derived source type (summaries, time usage) with a fresh batch
+    pub fn replace_source_chunks(
+        &mut self,
         source_type: &str,
-        file_index: usize,
-        metadata: &serde_json::Value,
+        chunks: Vec<DataChunk>,
+    ) -> Result<usize> {
+        if let Some(store) = &self.store {
+            store.delete_source_type(source_type)?;
+        }
+        let index = Arc::make_mut(&mut self.index);
+        index
+            .chunks
+            .retain(|c| c.metadata.source_type != source_type);
+        // Removing chunks shifts positions, so the lexical index is rebuilt
+        index.rebuild_lexical_index();
+        Ok(self.add_chunks(chunks)?.added)
+    }
+
+    pub fn ingest_data(&mut self, data_files: &[serde_json::Value]) -> Result<usize> {
+        Ok(self.ingest(&ExportIngestor(data_files))?.added)
+    }
+
+    /// Chunk and index everything an ingestor yields
+    pub fn ingest(&mut self, ingestor: &dyn Ingestor) -> Result<IngestStats> {
+        let mut stats = IngestStats::default();
+        let pending = self.prepare_captures(&ingestor.captures(), &mut stats)?;
+        let added = self.add_chunks(pending)?;
+        stats.added = added.added;
+        stats.duplicates_dropped = added.duplicates_dropped;
+        stats.log(ingestor.source_name());
+        Ok(stats)
+    }
+
+    /// Turn captures into chunks, skipping records the privacy rules exclude or whose source key
+    /// is already indexed. Every ingestion source goes through here, so the same capture always
+    /// yields the same chunks.
+    fn prepare_captures(
+        &self,
+        captures: &[Capture],
+        stats: &mut IngestStats,
     ) -> Result<Vec<DataChunk>> {
-        // Simple text chunking by splitting on spaces and taking chunks of CHUNK_SIZE characters
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let mut chunks = Vec::new();
-        let mut current_chunk = String::new();
+        let group_transcripts = self.groups_transcripts();
+        let exclusions = &self.index.exclusions;
+        let mut pending = Vec::new();
+        let mut transcripts = Vec::new();
+
+        for (index, capture) in captures.iter().enumerate() {
+            for record in capture.records() {
+                let source_type = record.source_type;
+                if exclusions.excludes(
+                    source_type,
+                    capture.app_name.as_deref(),
+                    capture.window_name.as_deref(),
+                    capture.browser_url.as_deref(),
+                ) {
+                    stats.excluded += 1;
+                    continue;
+                }
+                if self.is_frame_ingested(capture.frame_id, source_type, &record.text) {
+                    stats.already_indexed += 1;
+                    continue;
+                }
+
+                match record.transcription {
+                    // Grouped into speaker turns once every capture has been seen
+                    Some(transcription) if group_transcripts => {
+                        transcripts.push(PendingTranscript {
+                            text: record.text,
+                            transcription,
+                            metadata: record.metadata,
+                            row_index: index,
+                        })
+                    }
+                    _ => pending.extend(self.chunk_text(
+                        &record.text,
+                        source_type,
+                        index,
+                        &record.metadata,
+                    )?),
+                }
 
-        for word in words {
-            if current_chunk.len() + word.len() + 1 > CHUNK_SIZE {
-                if !current_chunk.is_empty() {
-                    chunks.push(current_chunk);
-                    current_chunk = String::new();
+                match source_type {
+                    "ocr" => stats.ocr_chunks += 1,
+                    "audio" => stats.audio_chunks += 1,
+                    _ => stats.app_chunks += 1,
                 }
             }
-            if !current_chunk.is_empty() {
-                current_chunk.push(' ');
+
+            // Progress logging every 1000 rows
+            if (index + 1) % 1000 == 0 {
+                println!("[RAG] Processed {} rows...", index + 1);
             }
-            current_chunk.push_str(word);
         }
 
-        if !current_chunk.is_empty() {
-            chunks.push(current_chunk);
-        }
+        pending.extend(self.transcript_chunks(transcripts));
+        stats.captures += captures.len();
+        Ok(pending)
+    }
+
+    fn chunk_text(
+        &self,
+        text: &str,
+        source_type: &str,
+        file_index: usize,
+        metadata: &serde_json::Value,
+    ) -> Result<Vec<DataChunk>> {
+        let model = chunking_model();
+        let chunks = self
+            .chunking
+            .for_source(source_type)
+            .chunker(&model)
+            .chunk(text);
+
+        let frame_id = metadata["frame_id"].as_i64();
+        let source_key = frame_source_key(frame_id, source_type, text);
+        Ok(self.make_chunks(chunks, source_type, file_index, metadata, source_key))
+    }
 
+    fn make_chunks(
+        &self,
+        chunks: Vec<String>,
+        source_type: &str,
+        file_index: usize,
+        metadata: &serde_json::Value,
+        source_key: Option<String>,
+    ) -> Vec<DataChunk> {
         let mut data_chunks = Vec::new();
+        let frame_id = metadata["frame_id"].as_i64();
 
-        for (_chunk_index, chunk_text) in chunks.iter().enumerate() {
+        for (chunk_index, chunk_text) in chunks.iter().enumerate() {
             let chunk_metadata = ChunkMetadata {
                 source_type: source_type.to_string(),
                 app_name: metadata["app_name"].as_str().map(|s| s.to_string()),
@@ -140,189 +860,425 @@ impl RAGSystem {
                 window_name: metadata["window_name"].as_str().map(|s| s.to_string()),
                 speaker_id: metadata["speaker_id"].as_str().map(|s| s.to_string()),
                 file_index: Some(file_index),
+                frame_id,
+                browser_url: non_empty_str(&metadata["browser_url"]),
+                video_file: non_empty_str(&metadata["video_file"]),
+                audio_file: non_empty_str(&metadata["audio_file"]),
+                audio_start_time: metadata["start_time"].as_f64(),
+                audio_end_time: metadata["end_time"].as_f64(),
+                transcription_engine: non_empty_str(&metadata["transcription_engine"]),
+                ocr_length: metadata["ocr_length"].as_u64(),
+                merged_keys: Vec::new(),
+                repeat_count: None,
+                first_seen: None,
+                last_seen: None,
+            };
+
+            // Frame-backed chunks get a stable id so re-ingesting the same frame is a no-op
+            let id = match &source_key {
+                Some(key) => format!("{}:{}", key, chunk_index),
+                None => Uuid::new_v4().to_string(),
             };
 
             let data_chunk = DataChunk {
-                id: Uuid::new_v4().to_string(),
+                id,
                 content: chunk_text.to_string(),
                 metadata: chunk_metadata,
+                embedding: None,
             };
 
             data_chunks.push(data_chunk);
         }
 
-        Ok(data_chunks)
+        data_chunks
     }
 
-    pub async fn query_rag(&self, query: &RAGQuery) -> Result<RAGResponse> {
-        use chrono::Utc;
-        use std::fs::OpenOptions;
-        use std::io::Write;
+    /// Whether audio rows are grouped into speaker turns instead of chunked one by one
+    /// Applies to chunks ingested from now on
+    pub fn set_chunking_policy(&mut self, policy: ChunkingPolicy) {
+        self.chunking = policy;
+    }
 
-        println!("[RAG] Starting search for query: '{}'", query.query);
-        println!("[RAG] Total chunks available: {}", self.chunks.len());
+    pub fn groups_transcripts(&self) -> bool {
+        self.chunking.for_source("audio").strategy == ChunkingStrategy::Transcript
+    }
 
-        // Create log file with timestamp
-        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        let log_filename = format!("rag_results_{}.log", timestamp);
-        let mut log_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(false)
-            .open(&log_filename)
-            .map_err(|e| anyhow::anyhow!("Failed to create log file: {}", e))?;
-
-        writeln!(log_file, "=== RAG QUERY LOG ===")?;
-        writeln!(log_file, "Timestamp: {}", Utc::now())?;
-        writeln!(log_file, "Query: {}", query.query)?;
-        writeln!(log_file, "Total chunks available: {}", self.chunks.len())?;
-        writeln!(log_file, "Top K: {}", query.top_k)?;
-        writeln!(
-            log_file,
-            "Similarity threshold: {}",
-            query.similarity_threshold
-        )?;
-        writeln!(log_file, "")?;
-
-        // Improved keyword-based search with better filtering
-        let mut context_chunks = Vec::new();
-        let mut similarity_scores = Vec::new();
-
-        let query_lower = query.query.to_lowercase();
-
-        // Filter out SQL-specific terms and common words that don't help with matching
-        let stop_words = [
-            "f.timestamp",
-            "datetime",
-            "now",
-            "hour",
-            "day",
-            "month",
-            "year",
-            "the",
-            "for",
-            "and",
-            "or",
-            "in",
-            "on",
-            "at",
-            "to",
-            "from",
-            "with",
-            "by",
-            "of",
-            "a",
-            "an",
-            "is",
-            "are",
-            "was",
-            "were",
-            "be",
-            "been",
-            "have",
-            "has",
-            "had",
-            "do",
-            "does",
-            "did",
-            "will",
-            "would",
-            "could",
-            "should",
-            "may",
-            "might",
-            "can",
-            "must",
-            "shall",
-            "period:",
-            "look",
-            "used,",
-            "visited,",
-            "activities,",
-            "tasks,",
-            "made.",
-            "analyze",
-            "all",
-            "digital",
-            "activity",
-            "applications",
-            "websites",
-            "communication",
-            "work",
-            "important",
-            "actions",
-            "decisions",
-        ];
+    /// Group transcription rows into speaker turns, one chunk (or more, for long turns) each.
+    /// Every merged row's source key is kept on the chunk so re-ingestion skips it.
+    fn transcript_chunks(&self, mut transcripts: Vec<PendingTranscript>) -> Vec<DataChunk> {
+        if transcripts.is_empty() {
+            return Vec::new();
+        }
 
-        let query_words: Vec<&str> = query_lower
-            .split_whitespace()
-            .filter(|word| !stop_words.contains(word))
+        transcripts.sort_by(|a, b| {
+            let a_key = (
+                a.metadata["audio_file"].as_str(),
+                a.metadata["start_time"].as_f64(),
+            );
+            let b_key = (
+                b.metadata["audio_file"].as_str(),
+                b.metadata["start_time"].as_f64(),
+            );
+            a_key
+                .partial_cmp(&b_key)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        // Frames from the same video chunk repeat the same transcription; keep one copy
+        let mut unique: Vec<PendingTranscript> = Vec::new();
+        let mut duplicate_keys: Vec<Vec<String>> = Vec::new();
+        for transcript in transcripts {
+            if let Some(last) = unique.last() {
+                if last.transcription == transcript.transcription
+                    && last.metadata["audio_file"] == transcript.metadata["audio_file"]
+                    && last.metadata["start_time"] == transcript.metadata["start_time"]
+                {
+                    if let Some(keys) = duplicate_keys.last_mut() {
+                        keys.extend(transcript.source_key());
+                    }
+                    continue;
+                }
+            }
+            unique.push(transcript);
+            duplicate_keys.push(Vec::new());
+        }
+
+        let segments: Vec<TranscriptSegment> = unique
+            .iter()
+            .map(|t| TranscriptSegment {
+                audio_file: t.metadata["audio_file"].as_str(),
+                speaker: t.metadata["speaker_id"].as_str(),
+                start_time: t.metadata["start_time"].as_f64(),
+                end_time: t.metadata["end_time"].as_f64(),
+                text: &t.transcription,
+            })
             .collect();
 
-        println!("[RAG] Filtered search keywords: {:?}", query_words);
-        writeln!(log_file, "Filtered search keywords: {:?}", query_words)?;
+        let config = self.chunking.for_source("audio");
+        let model = chunking_model();
+        let max_tokens = config.resolved_max_tokens(&model);
+        let chunker = config.chunker(&model);
 
-        // If no meaningful keywords, use a broader search
-        let search_terms = if query_words.is_empty() {
-            vec![
-                "app",
-                "activity",
-                "time",
-                "work",
-                "communication",
-                "development",
-                "meeting",
-                "coding",
-            ]
-        } else {
-            query_words
-        };
+        let mut chunks = Vec::new();
+        for group in group_transcript(&segments, max_tokens) {
+            let first = &unique[group.start];
+            let last = &unique[group.end - 1];
+
+            let time_span = match (
+                first.metadata["start_time"].as_f64(),
+                last.metadata["end_time"].as_f64(),
+            ) {
+                (Some(start), Some(end)) => format!(" [{:.0}s-{:.0}s]", start, end),
+                _ => String::new(),
+            };
+            let turn_text = unique[group.clone()]
+                .iter()
+                .map(|t| t.transcription.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let content = format!(
+                "Audio from {} ({}){}: {}",
+                first.metadata["app_name"].as_str().unwrap_or("Unknown"),
+                first.metadata["speaker_id"].as_str().unwrap_or("Unknown"),
+                time_span,
+                turn_text
+            );
 
-        for chunk in &self.chunks {
-            let chunk_lower = chunk.content.to_lowercase();
-            let mut score = 0.0;
+            let mut metadata = first.metadata.clone();
+            metadata["end_time"] = last.metadata["end_time"].clone();
 
-            for word in &search_terms {
-                if chunk_lower.contains(word) {
-                    score += 1.0;
-                }
+            let mut merged_keys: Vec<String> = duplicate_keys[group.start].clone();
+            for i in group.start + 1..group.end {
+                merged_keys.extend(unique[i].source_key());
+                merged_keys.extend(duplicate_keys[i].iter().cloned());
             }
 
-            if score > 0.0 {
-                score = score / search_terms.len() as f32;
-                // Lower the threshold for better matching
-                if score >= 0.1 {
-                    // Much lower threshold
-                    context_chunks.push(chunk.clone());
-                    similarity_scores.push(score);
-                }
+            let mut turn_chunks = self.make_chunks(
+                chunker.chunk(&content),
+                "audio",
+                first.row_index,
+                &metadata,
+                first.source_key(),
+            );
+            for chunk in turn_chunks.iter_mut() {
+                chunk.metadata.merged_keys = merged_keys.clone();
             }
+            chunks.extend(turn_chunks);
         }
 
         println!(
-            "[RAG] Found {} chunks with score >= {}",
-            context_chunks.len(),
-            query.similarity_threshold
+            "[RAG] Grouped {} transcription rows into {} transcript chunks",
+            unique.len(),
+            chunks.len()
         );
-        writeln!(
-            log_file,
-            "Found {} chunks with score >= {}",
-            context_chunks.len(),
-            query.similarity_threshold
-        )?;
+        chunks
+    }
 
-        // Sort by similarity score and take top_k
-        let mut indexed_chunks: Vec<(usize, f32)> = similarity_scores
+    /// Apply these privacy rules from now on, e.g. after the user saved new ones
+    pub fn set_exclusion_rules(&mut self, rules: ExclusionRules) {
+        Arc::make_mut(&mut self.index).exclusions = rules;
+    }
+
+    /// Cheap handle to the current index for querying without holding the lock
+    pub fn snapshot(&self) -> Arc<RAGIndex> {
+        self.index.clone()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.index.chunks.len()
+    }
+
+    pub fn store(&self) -> Option<&ChunkStore> {
+        self.store.as_ref()
+    }
+
+    /// Chunks that still need an embedding, as (chunk id, content)
+    fn chunks_missing_embeddings(&self) -> Vec<(String, String)> {
+        self.index
+            .chunks
+            .iter()
+            .filter(|c| c.embedding.is_none())
+            .map(|c| (c.id.clone(), c.content.clone()))
+            .collect()
+    }
+
+    fn set_embeddings(&mut self, embeddings: Vec<(String, Vec<f32>)>) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.update_embeddings(&embeddings)?;
+        }
+
+        let mut by_id: HashMap<String, Vec<f32>> = embeddings.into_iter().collect();
+        for chunk in Arc::make_mut(&mut self.index).chunks.iter_mut() {
+            if let Some(embedding) = by_id.remove(&chunk.id) {
+                chunk.embedding = Some(embedding);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear_collection(&mut self) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.clear()?;
+        }
+        // Only the chunks go; the model and privacy rules stay with the collection
+        let index = Arc::make_mut(&mut self.index);
+        index.chunks.clear();
+        index.lexical_index.clear();
+        self.ingested_keys.clear();
+        self.ocr_fingerprints.clear();
+        println!("[RAG] Cleared all chunks");
+        Ok(())
+    }
+
+    pub fn get_collection_stats(&self) -> Result<HashMap<String, usize>> {
+        if let Some(store) = &self.store {
+            return store.stats();
+        }
+
+        let mut stats = HashMap::new();
+        stats.insert("total_points".to_string(), self.index.chunks.len());
+        let frames: HashSet<i64> = self
+            .index
+            .chunks
+            .iter()
+            .filter_map(|c| c.metadata.frame_id)
+            .collect();
+        stats.insert("frames_indexed".to_string(), frames.len());
+        let duplicates: u32 = self
+            .index
+            .chunks
+            .iter()
+            .filter_map(|c| c.metadata.repeat_count)
+            .map(|count| count.saturating_sub(1))
+            .sum();
+        stats.insert("duplicates_dropped".to_string(), duplicates as usize);
+
+        Ok(stats)
+    }
+}
+
+impl RAGIndex {
+    fn with_saved_rules() -> Self {
+        Self {
+            exclusions: ExclusionRules::load(),
+            ..Self::default()
+        }
+    }
+
+    /// Retrieve context for the query and answer it. With `on_delta`, the answer is
+    /// streamed piece by piece as the model produces it.
+    pub async fn query_rag(
+        &self,
+        query: &RAGQuery,
+        on_delta: Option<DeltaSink<'_>>,
+    ) -> Result<RAGResponse> {
+        let started = Instant::now();
+        let RAGRetrieval {
+            chunks: final_chunks,
+            scores: final_scores,
+            mut trace,
+        } = self.retrieve(query).await?;
+
+        // Keep the best-ranked chunks that fit the context window of the model answering with
+        // these options
+        let options = CompletionOptions::default();
+        let budget = ContextBudget::for_options(&options).await;
+        let entries: Vec<(String, String)> = final_chunks
             .iter()
             .enumerate()
-            .map(|(idx, &score)| (idx, score))
+            .map(|(i, chunk)| (chunk.id.clone(), format_context_entry(i + 1, chunk)))
             .collect();
+        let template = prompts::template(prompts::RAG_ANSWER);
+        trace.templates.push(template.usage());
+        let (kept, budget_report) =
+            budget.pack(&rag_answer_prompt(&template, "", &query.query), &entries);
+        let final_chunks: Vec<DataChunk> = kept.iter().map(|&i| final_chunks[i].clone()).collect();
+        let final_scores: Vec<f32> = kept.iter().map(|&i| final_scores[i]).collect();
+        trace.set_budget(budget_report.clone());
+        trace.retrieval_ms = started.elapsed().as_millis() as u64;
+
+        // Generate answer using RAG context
+        println!("[RAG] Generating AI response...");
+        let generation_started = Instant::now();
+        let ProviderAnswer {
+            text: answer,
+            provider,
+            model,
+        } = self
+            .generate_rag_answer(&template, &query.query, &final_chunks, &options, on_delta)
+            .await?;
+        trace.provider = Some(provider.clone());
+        trace.model = Some(model.clone());
+        trace.generation_ms = generation_started.elapsed().as_millis() as u64;
+        trace.total_ms = started.elapsed().as_millis() as u64;
+
+        println!("[RAG] AI Response: {}", answer);
+        println!(
+            "[RAG] Search completed in {} ms ({} ms retrieval, {} ms generation)",
+            trace.total_ms, trace.retrieval_ms, trace.generation_ms
+        );
+
+        let citations = extract_citations(&answer, &final_chunks);
+        println!("[RAG] Extracted {} citations from answer", citations.len());
+
+        crate::trace::record(&trace);
+
+        Ok(RAGResponse {
+            answer,
+            context_chunks: final_chunks,
+            similarity_scores: final_scores,
+            citations,
+            budget: Some(budget_report),
+            provider: Some(provider),
+            model: Some(model),
+            trace: if query.include_trace {
+                Some(trace)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Rank chunks for the query without generating an answer
+    pub async fn retrieve(&self, query: &RAGQuery) -> Result<RAGRetrieval> {
+        let started = Instant::now();
+        let mut trace = QueryTrace::new(&query.query);
+        trace.templates = query.templates.clone();
+        trace.collection = query.collection.clone();
+        trace.retrieval_mode = query.retrieval_mode;
+        trace.rerank = query.rerank;
+        trace.top_k = query.top_k;
+        trace.similarity_threshold = query.similarity_threshold;
+        trace.filters = query.filters.clone();
+        trace.total_chunks = self.chunks.len();
+
+        println!("[RAG] Starting search for query: '{}'", query.query);
+        println!("[RAG] Total chunks available: {}", self.chunks.len());
+        println!(
+            "[RAG] Retrieval mode: {:?}, rerank: {}",
+            query.retrieval_mode, query.rerank
+        );
+
+        // Metadata filters narrow the candidate set before either signal is scored
+        let mask = self.filter_mask(&query.filters)?;
+        if let Some(ref mask) = mask {
+            let candidates = mask.iter().filter(|&&allowed| allowed).count();
+            println!(
+                "[RAG] Filters {:?} left {} candidate chunks",
+                query.filters, candidates
+            );
+            trace.candidates = Some(candidates);
+        }
+
+        let above_threshold = |scores: Vec<(usize, f32)>| -> Vec<(usize, f32)> {
+            scores
+                .into_iter()
+                .filter(|&(_, score)| score >= query.similarity_threshold)
+                .collect()
+        };
+
+        // Semantic signal: cosine similarity against the query embedding
+        let query_embedding =
+            if query.retrieval_mode != RetrievalMode::Lexical && self.has_embeddings() {
+                self.embed_query(&query.query).await
+            } else {
+                None
+            };
+        let semantic = query_embedding.map(|embedding| {
+            println!(
+                "[RAG] Ranking by embedding similarity ({})",
+                EMBEDDING_MODEL
+            );
+            above_threshold(self.semantic_scores(&embedding, mask.as_deref()))
+        });
+
+        // Lexical signal: BM25, also used when semantic search was requested but is unavailable
+        let lexical = if query.retrieval_mode != RetrievalMode::Semantic || semantic.is_none() {
+            let (keywords, scores) = self.keyword_scores(&query.query, mask.as_deref());
+            println!("[RAG] Filtered search keywords: {:?}", keywords);
+            trace.keywords = keywords;
+            Some(above_threshold(scores))
+        } else {
+            None
+        };
+
+        // Per-signal scores are kept for the trace before fusion replaces them
+        trace.semantic_matches = semantic.as_ref().map(|scores| scores.len());
+        trace.lexical_matches = lexical.as_ref().map(|scores| scores.len());
+        let semantic_by_chunk: HashMap<usize, f32> = semantic.iter().flatten().copied().collect();
+        let lexical_by_chunk: HashMap<usize, f32> = lexical.iter().flatten().copied().collect();
+
+        let mut indexed_chunks = match (semantic, lexical) {
+            (Some(semantic), Some(lexical)) => {
+                println!(
+                    "[RAG] Fusing {} semantic and {} lexical matches",
+                    semantic.len(),
+                    lexical.len()
+                );
+                reciprocal_rank_fusion(&[semantic, lexical])
+            }
+            (Some(scores), None) | (None, Some(scores)) => scores,
+            (None, None) => Vec::new(),
+        };
+
+        println!(
+            "[RAG] Found {} chunks with score >= {}",
+            indexed_chunks.len(),
+            query.similarity_threshold
+        );
+
+        // Sort by similarity score and take top_k
         indexed_chunks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
 
+        if query.rerank && !indexed_chunks.is_empty() {
+            indexed_chunks.truncate(query.top_k.max(RERANK_CANDIDATES));
+            indexed_chunks = self.rerank_with_model(&query.query, indexed_chunks).await;
+        }
+
         let top_k = std::cmp::min(query.top_k, indexed_chunks.len());
         let final_chunks: Vec<DataChunk> = indexed_chunks[..top_k]
             .iter()
-            .map(|&(idx, _)| context_chunks[idx].clone())
+            .map(|&(idx, _)| self.chunks[idx].clone())
             .collect();
         let final_scores: Vec<f32> = indexed_chunks[..top_k]
             .iter()
@@ -330,8 +1286,6 @@ impl RAGSystem {
             .collect();
 
         println!("[RAG] Selected top {} chunks:", final_chunks.len());
-        writeln!(log_file, "Selected top {} chunks:", final_chunks.len())?;
-
         for (i, (chunk, score)) in final_chunks.iter().zip(final_scores.iter()).enumerate() {
             println!("[RAG] Chunk {} (Score: {:.3}):", i + 1, score);
             println!("[RAG]   Type: {}", chunk.metadata.source_type);
@@ -339,307 +1293,824 @@ impl RAGSystem {
                 "[RAG]   App: {}",
                 chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
             );
-            println!("[RAG]   Content: {}", chunk.content);
             println!("[RAG]   ---");
+        }
+        trace.chunks = indexed_chunks[..top_k]
+            .iter()
+            .map(|&(idx, score)| {
+                TraceChunk::new(
+                    &self.chunks[idx],
+                    score,
+                    semantic_by_chunk.get(&idx).copied(),
+                    lexical_by_chunk.get(&idx).copied(),
+                )
+            })
+            .collect();
+        trace.retrieval_ms = started.elapsed().as_millis() as u64;
 
-            writeln!(log_file, "Chunk {} (Score: {:.3}):", i + 1, score)?;
-            writeln!(log_file, "  Type: {}", chunk.metadata.source_type)?;
-            writeln!(
-                log_file,
-                "  App: {}",
-                chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
-            )?;
-            writeln!(
-                log_file,
-                "  Timestamp: {}",
-                chunk.metadata.timestamp.as_deref().unwrap_or("Unknown")
-            )?;
-            writeln!(log_file, "  Content: {}", chunk.content)?;
-            writeln!(log_file, "  ---")?;
+        Ok(RAGRetrieval {
+            chunks: final_chunks,
+            scores: final_scores,
+            trace,
+        })
+    }
+
+    fn has_embeddings(&self) -> bool {
+        self.chunks.iter().any(|c| c.embedding.is_some())
+    }
+
+    /// Cosine similarity between the query and every embedded chunk, as (chunk index, score)
+    fn semantic_scores(&self, query_embedding: &[f32], mask: Option<&[bool]>) -> Vec<(usize, f32)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| mask.map_or(true, |mask| mask[*idx]))
+            .filter_map(|(idx, chunk)| {
+                let embedding = chunk.embedding.as_ref()?;
+                Some((idx, cosine_similarity(query_embedding, embedding)?))
+            })
+            .collect()
+    }
+
+    /// BM25 scores from the lexical index, as (chunk index, score).
+    /// Scores are divided by the best match so they fall in 0..=1 like cosine similarity.
+    fn keyword_scores(
+        &self,
+        query: &str,
+        mask: Option<&[bool]>,
+    ) -> (Vec<String>, Vec<(usize, f32)>) {
+        let query_lower = query.to_lowercase();
+
+        let query_words: Vec<String> = query_lower
+            .split_whitespace()
+            .filter(|word| !STOP_WORDS.contains(word))
+            .map(|word| word.to_string())
+            .collect();
+
+        // If no meaningful keywords, use a broader search
+        let search_terms = if query_words.is_empty() {
+            FALLBACK_SEARCH_TERMS
+                .iter()
+                .map(|word| word.to_string())
+                .collect()
+        } else {
+            query_words
+        };
+
+        let mut scores = self.lexical_index.search(&search_terms.join(" "));
+        if let Some(mask) = mask {
+            scores.retain(|&(idx, _)| mask[idx]);
+        }
+        let max_score = scores.iter().map(|&(_, score)| score).fold(0.0, f32::max);
+        if max_score > 0.0 {
+            for (_, score) in scores.iter_mut() {
+                *score /= max_score;
+            }
         }
 
-        // Generate answer using RAG context
-        println!("[RAG] Generating AI response...");
-        println!("[RAG] DEBUG: About to call generate_rag_answer");
-        writeln!(log_file, "Generating AI response...")?;
+        (search_terms, scores)
+    }
+
+    /// Lexical-only search over the index; never calls a model
+    pub fn keyword_search(
+        &self,
+        query: &str,
+        top_k: usize,
+        filters: &RAGFilters,
+    ) -> Result<KeywordSearchResponse> {
+        let mask = self.filter_mask(filters)?;
+        let (_, mut scores) = self.keyword_scores(query, mask.as_deref());
+        let total_matches = scores.len();
+
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scores.truncate(top_k);
+
+        Ok(KeywordSearchResponse {
+            chunks: scores
+                .iter()
+                .map(|&(idx, _)| self.chunks[idx].clone())
+                .collect(),
+            scores: scores.iter().map(|&(_, score)| score).collect(),
+            total_matches,
+        })
+    }
+
+    /// Every chunk matching the filters, in index order
+    pub fn filtered_chunks(&self, filters: &RAGFilters) -> Result<Vec<DataChunk>> {
+        let chunks = match self.filter_mask(filters)? {
+            Some(mask) => self
+                .chunks
+                .iter()
+                .zip(mask)
+                .filter(|(_, keep)| *keep)
+                .map(|(chunk, _)| chunk.clone())
+                .collect(),
+            None => self.chunks.clone(),
+        };
+        Ok(chunks)
+    }
+
+    /// Which chunks pass the metadata filters and the privacy rules, by position; None when
+    /// nothing is filtered. Rules saved after a chunk was indexed still keep it out.
+    fn filter_mask(&self, filters: &RAGFilters) -> Result<Option<Vec<bool>>> {
+        let exclusions = &self.exclusions;
+        if filters.is_empty() && exclusions.is_empty() {
+            return Ok(None);
+        }
+        let (start, end) = filters.time_bounds()?;
+        Ok(Some(
+            self.chunks
+                .iter()
+                .map(|chunk| {
+                    filters.matches(&chunk.metadata, start, end)
+                        && !exclusions.excludes_chunk(&chunk.metadata)
+                })
+                .collect(),
+        ))
+    }
+
+    fn rebuild_lexical_index(&mut self) {
+        self.lexical_index.clear();
+        for chunk in &self.chunks {
+            self.lexical_index.add_document(&chunk.content);
+        }
+    }
+
+    /// Ask the selected Ollama model to grade each candidate's relevance from 0 to 10.
+    /// Keeps the fused order if the model is unavailable or its reply cannot be parsed.
+    async fn rerank_with_model(
+        &self,
+        query: &str,
+        candidates: Vec<(usize, f32)>,
+    ) -> Vec<(usize, f32)> {
+        let passages = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, &(idx, _))| {
+                let content: String = self.chunks[idx]
+                    .content
+                    .chars()
+                    .take(RERANK_PASSAGE_CHARS)
+                    .collect();
+                format!("[{}] {}", i + 1, content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let prompt = format!(
+            "Rate how relevant each passage is to the question on a scale from 0 (unrelated) to 10 (answers it directly).\n\
+             Reply with only a JSON array of {} numbers, one per passage, in order.\n\n\
+             Question: {}\n\nPassages:\n{}",
+            candidates.len(),
+            query,
+            passages
+        );
+
+        println!("[RAG] Reranking {} candidates", candidates.len());
+        let ratings = match self
+            .complete(None, &prompt, &CompletionOptions::default(), None)
+            .await
+        {
+            Ok(reply) => {
+                println!(
+                    "[RAG] Rerank ratings from {} ({})",
+                    reply.provider, reply.model
+                );
+                parse_rerank_ratings(&reply.text, candidates.len())
+            }
+            Err(e) => {
+                println!("[RAG] Rerank failed, keeping fused order: {}", e);
+                return candidates;
+            }
+        };
+        let Some(ratings) = ratings else {
+            println!("[RAG] Could not parse rerank ratings, keeping fused order");
+            return candidates;
+        };
+
+        let mut reranked: Vec<(usize, f32)> = candidates
+            .iter()
+            .zip(ratings)
+            .map(|(&(idx, _), rating)| (idx, (rating / 10.0).clamp(0.0, 1.0)))
+            .collect();
+        // Stable sort keeps the fused order between equally rated passages
+        reranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        reranked
+    }
+
+    async fn generate_rag_answer(
+        &self,
+        template: &PromptTemplate,
+        query: &str,
+        context_chunks: &[DataChunk],
+        options: &CompletionOptions,
+        on_delta: Option<DeltaSink<'_>>,
+    ) -> Result<ProviderAnswer> {
+        // Prepare context from chunks, numbered so the answer can cite them
+        let context_text = context_chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| format_context_entry(i + 1, chunk))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        // Create RAG prompt
+        let prompt = rag_answer_prompt(template, &context_text, query);
+
+        // Tries the configured providers in order; the provider that answered goes in the trace
+        self.complete(
+            Some(crate::ai::ASSISTANT_SYSTEM_PROMPT),
+            &prompt,
+            options,
+            on_delta,
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to generate RAG answer: {}", e))
+    }
+
+    // The collection's own model if it has one, otherwise the configured provider chain
+    async fn complete(
+        &self,
+        system: Option<&str>,
+        prompt: &str,
+        options: &CompletionOptions,
+        on_delta: Option<DeltaSink<'_>>,
+    ) -> Result<ProviderAnswer, String> {
+        let Some(model) = &self.model else {
+            return complete_with_fallback(system, prompt, options, on_delta).await;
+        };
+        let text = match on_delta {
+            Some(on_delta) => model.complete_stream(system, prompt, on_delta).await?,
+            None => model.complete(system, prompt).await?,
+        };
+        Ok(ProviderAnswer {
+            text,
+            provider: model.config().name.clone(),
+            model: model.model(),
+        })
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        match &self.model {
+            Some(model) => Ok(model.embed(texts).await?),
+            None => crate::ai::get_ollama_embeddings(texts, EMBEDDING_MODEL).await,
+        }
+    }
+
+    async fn embed_query(&self, query: &str) -> Option<Vec<f32>> {
+        match self.embed(&[query.to_string()]).await {
+            Ok(mut embeddings) => embeddings.pop(),
+            Err(e) => {
+                println!("[RAG] Failed to embed query: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn format_context_entry(number: usize, chunk: &DataChunk) -> String {
+    format!(
+        "[{}] ({} - {} - {}): {}",
+        number,
+        chunk.metadata.source_type,
+        chunk.metadata.app_name.as_deref().unwrap_or("Unknown"),
+        chunk
+            .metadata
+            .timestamp
+            .as_deref()
+            .unwrap_or("Unknown time"),
+        chunk.content
+    )
+}
+
+fn rag_answer_prompt(template: &PromptTemplate, context_text: &str, query: &str) -> String {
+    template.render(&[("context", context_text), ("question", query)])
+}
+
+/// Split the answer into sentences and collect the `[n]` markers each one carries
+fn extract_citations(answer: &str, chunks: &[DataChunk]) -> Vec<Citation> {
+    let mut citations = Vec::new();
+    let mut sentence = String::new();
+
+    let mut flush = |sentence: &mut String| {
+        let mut indices = Vec::new();
+        let mut rest = sentence.as_str();
+        while let Some(open) = rest.find('[') {
+            rest = &rest[open + 1..];
+            let Some(close) = rest.find(']') else {
+                break;
+            };
+            for marker in rest[..close].split(',') {
+                if let Ok(n) = marker.trim().parse::<usize>() {
+                    if n >= 1 && n <= chunks.len() && !indices.contains(&(n - 1)) {
+                        indices.push(n - 1);
+                    }
+                }
+            }
+            rest = &rest[close + 1..];
+        }
+
+        if !indices.is_empty() {
+            citations.push(Citation {
+                claim: sentence.trim().to_string(),
+                chunk_ids: indices.iter().map(|&i| chunks[i].id.clone()).collect(),
+                chunk_indices: indices,
+            });
+        }
+        sentence.clear();
+    };
+
+    let mut chars = answer.chars().peekable();
+    while let Some(c) = chars.next() {
+        sentence.push(c);
+        let ends_sentence = match c {
+            '\n' => true,
+            // Markers usually follow the period, so keep them with their sentence
+            '.' | '!' | '?' => chars.peek().map_or(true, |next| next.is_whitespace()),
+            _ => false,
+        };
+        if ends_sentence {
+            while let Some(&next) = chars.peek() {
+                if next == ' ' {
+                    chars.next();
+                } else if next == '[' {
+                    for c in chars.by_ref() {
+                        sentence.push(c);
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                } else {
+                    break;
+                }
+            }
+            flush(&mut sentence);
+        }
+    }
+    flush(&mut sentence);
+
+    citations
+}
+
+fn chunking_model() -> String {
+    crate::ai::get_selected_model().unwrap_or_else(|| DEFAULT_CHUNK_MODEL.to_string())
+}
+
+// Screenpipe rows use "" for missing text columns
+fn non_empty_str(value: &serde_json::Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|s| !s.is_empty() && *s != "Unknown")
+        .map(|s| s.to_string())
+}
+
+/// Stable key for one piece of text captured in a screenpipe frame.
+/// A frame can join to several transcriptions, so the text hash is part of the key.
+fn frame_source_key(frame_id: Option<i64>, source_type: &str, text: &str) -> Option<String> {
+    frame_id.map(|id| format!("{}:{}:{:016x}", id, source_type, fnv1a_hash(text)))
+}
+
+// Frame-backed ids are "<source key>:<chunk index>"; other chunks stand alone
+fn capture_key(chunk: &DataChunk) -> String {
+    match (chunk.metadata.frame_id, chunk.id.rsplit_once(':')) {
+        (Some(_), Some((key, _))) => key.to_string(),
+        _ => chunk.id.clone(),
+    }
+}
+
+/// Split a batch into captures: runs of consecutive chunks cut from the same text
+fn group_by_capture(chunks: Vec<DataChunk>) -> Vec<Vec<DataChunk>> {
+    let mut captures: Vec<Vec<DataChunk>> = Vec::new();
+    for chunk in chunks {
+        match captures.last_mut() {
+            Some(capture) if capture_key(&capture[0]) == capture_key(&chunk) => capture.push(chunk),
+            _ => captures.push(vec![chunk]),
+        }
+    }
+    captures
+}
+
+fn capture_text(capture: &[DataChunk]) -> String {
+    capture
+        .iter()
+        .map(|c| c.content.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Near-duplicates are only looked for within the same app and window
+fn dedup_scope(metadata: &ChunkMetadata) -> String {
+    format!(
+        "{}|{}",
+        metadata.app_name.as_deref().unwrap_or(""),
+        metadata.window_name.as_deref().unwrap_or("")
+    )
+}
 
-        let answer = self
-            .generate_rag_answer(&query.query, &final_chunks)
-            .await?;
-        println!("[RAG] DEBUG: generate_rag_answer completed successfully");
+fn is_earlier(a: &str, b: &str) -> bool {
+    match (parse_chunk_time(a), parse_chunk_time(b)) {
+        (Some(a), Some(b)) => a < b,
+        _ => a < b,
+    }
+}
 
-        println!("[RAG] AI Response: {}", answer);
-        writeln!(log_file, "AI Response: {}", answer)?;
-
-        // Build context that was sent to AI
-        let mut context_parts = Vec::new();
-        for (i, chunk) in final_chunks.iter().enumerate() {
-            context_parts.push(format!(
-                "Context {} (Score: {:.3}):\nType: {}\nApp: {}\nContent: {}",
-                i + 1,
-                final_scores[i],
-                chunk.metadata.source_type,
-                chunk.metadata.app_name.as_deref().unwrap_or("Unknown"),
-                chunk.content
-            ));
-        }
+pub const DEFAULT_COLLECTION: &str = "default";
 
-        let full_context = context_parts.join("\n\n");
-        writeln!(log_file, "")?;
-        writeln!(log_file, "=== FULL CONTEXT SENT TO AI ===")?;
-        writeln!(log_file, "{}", full_context)?;
-        writeln!(log_file, "")?;
-        writeln!(log_file, "=== END RAG LOG ===")?;
+// Queries only take the read lock long enough to grab an index snapshot; ingestion
+// prepares a batch under the read lock and commits it under one write lock.
+pub type SharedRAGSystem = Arc<RwLock<RAGSystem>>;
 
-        println!(
-            "[RAG] Full context sent to AI ({} characters):",
-            full_context.len()
-        );
-        println!(
-            "[RAG] First 500 chars: {}",
-            &full_context[..full_context.len().min(500)]
-        );
-        if full_context.len() > 500 {
-            println!("[RAG] ... (truncated)");
-        }
-        println!("[RAG] Log saved to: {}", log_filename);
-        println!("[RAG] Search completed successfully");
+// Loaded RAG collections by name. Each collection has its own lock, so analyses
+// working on different collections never block or clobber each other.
+static RAG_COLLECTIONS: once_cell::sync::Lazy<Mutex<HashMap<String, SharedRAGSystem>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
 
-        Ok(RAGResponse {
-            answer,
-            context_chunks: final_chunks,
-            similarity_scores: final_scores,
-        })
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() || a.is_empty() {
+        return None;
     }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some(dot / (norm_a * norm_b))
+}
 
-    async fn generate_rag_answer(
-        &self,
-        query: &str,
-        context_chunks: &[DataChunk],
-    ) -> Result<String> {
-        println!("[RAG] DEBUG: generate_rag_answer started");
-        println!(
-            "[RAG] DEBUG: Number of context chunks: {}",
-            context_chunks.len()
-        );
+/// Merge ranked lists with reciprocal rank fusion, normalized so a chunk ranked first
+/// in every list scores 1.0
+fn reciprocal_rank_fusion(rankings: &[Vec<(usize, f32)>]) -> Vec<(usize, f32)> {
+    let mut fused: HashMap<usize, f32> = HashMap::new();
+    for ranking in rankings {
+        let mut ranking = ranking.clone();
+        ranking.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        for (rank, (idx, _)) in ranking.into_iter().enumerate() {
+            *fused.entry(idx).or_insert(0.0) += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
 
-        // Prepare context from chunks
-        let context_text = context_chunks
-            .iter()
-            .map(|chunk| {
-                format!(
-                    "[{}] {}: {}",
-                    chunk.metadata.source_type,
-                    chunk.metadata.app_name.as_deref().unwrap_or("Unknown"),
-                    chunk.content
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n\n");
+    let best_possible = rankings.len() as f32 / (RRF_K + 1.0);
+    let mut fused: Vec<(usize, f32)> = fused
+        .into_iter()
+        .map(|(idx, score)| (idx, score / best_possible))
+        .collect();
+    // Callers sort by score with a stable sort; index order keeps ties from depending on hash order
+    fused.sort_by_key(|&(idx, _)| idx);
+    fused
+}
 
-        println!(
-            "[RAG] DEBUG: Context text length: {} characters",
-            context_text.len()
-        );
+// The judge replies with free text; pull out the first JSON array of numbers
+fn parse_rerank_ratings(reply: &str, expected: usize) -> Option<Vec<f32>> {
+    let start = reply.find('[')?;
+    let end = reply[start..].find(']')? + start;
+    let ratings: Vec<f32> = serde_json::from_str(&reply[start..=end]).ok()?;
+    if ratings.len() == expected {
+        Some(ratings)
+    } else {
+        None
+    }
+}
 
-        // Create RAG prompt
-        let prompt = format!(
-            "You are a helpful AI assistant analyzing digital activity data. Use the following context to answer the user's question accurately and insightfully.\n\nCONTEXT DATA:\n{}\n\nUSER QUESTION: {}\n\nPlease provide a comprehensive answer based on the context data above. Be specific and reference the actual content when possible. If the context doesn't contain enough information to answer the question, say so clearly.\n\nAnswer:",
-            context_text, query
-        );
+/// Embed every chunk of a collection that does not have an embedding yet.
+/// The model is called without holding the RAG lock; results are written back in one pass.
+pub async fn embed_pending_chunks(rag: &SharedRAGSystem) -> Result<usize> {
+    let (pending, index) = {
+        let system = rag.read().unwrap();
+        (system.chunks_missing_embeddings(), system.snapshot())
+    };
 
-        println!("[RAG] DEBUG: About to call call_ai_async (OpenAI fallback to Ollama)");
-        println!("[RAG] DEBUG: Prompt length: {} characters", prompt.len());
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    println!(
+        "[RAG] Embedding {} chunks with {}",
+        pending.len(),
+        EMBEDDING_MODEL
+    );
 
-        // Use call_ai_async for OpenAI first, fallback to Ollama
-        let result = crate::ai::call_ai_async(&prompt).await;
+    let mut embedded = Vec::with_capacity(pending.len());
+    for batch in pending.chunks(EMBEDDING_BATCH_SIZE) {
+        let texts: Vec<String> = batch.iter().map(|(_, content)| content.clone()).collect();
+        let embeddings = index.embed(&texts).await.map_err(|e| anyhow::anyhow!(e))?;
+        embedded.extend(batch.iter().map(|(id, _)| id.clone()).zip(embeddings));
+    }
 
-        match &result {
-            Ok(response) => {
-                println!(
-                    "[RAG] DEBUG: call_ai_async succeeded, response length: {}",
-                    response.len()
-                );
-            }
-            Err(e) => {
-                println!("[RAG] DEBUG: call_ai_async failed: {}", e);
-            }
-        }
+    let count = embedded.len();
+    rag.write().unwrap().set_embeddings(embedded)?;
+    println!("[RAG] Embedded {} chunks", count);
+    Ok(count)
+}
 
-        result.map_err(|e| anyhow::anyhow!("Failed to generate RAG answer: {}", e))
+// Embedding is best-effort: without it queries fall back to keyword matching
+async fn embed_pending_chunks_logged(rag: &SharedRAGSystem) {
+    if let Err(e) = embed_pending_chunks(rag).await {
+        println!("[RAG] Skipping embeddings: {}", e);
     }
+}
 
-    pub fn clear_collection(&mut self) -> Result<()> {
-        self.chunks.clear();
-        println!("[RAG] Cleared all chunks");
+fn validate_collection_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
         Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid collection name '{}': use up to 64 letters, digits, '-' or '_'",
+            name
+        ))
     }
+}
 
-    pub fn get_collection_stats(&self) -> Result<HashMap<String, usize>> {
-        let mut stats = HashMap::new();
-        stats.insert("total_points".to_string(), self.chunks.len());
+/// Handle to a collection, loading it from disk on first use.
+/// The default collection always exists; other collections are created on demand only
+/// when `create` is set.
+pub fn open_collection(name: Option<&str>, create: bool) -> Result<SharedRAGSystem> {
+    let name = name.unwrap_or(DEFAULT_COLLECTION);
+    validate_collection_name(name)?;
 
-        Ok(stats)
+    let mut collections = RAG_COLLECTIONS.lock().unwrap();
+    if let Some(collection) = collections.get(name) {
+        return Ok(collection.clone());
     }
+
+    let exists = name == DEFAULT_COLLECTION || ChunkStore::collection_exists(name);
+    if !exists && !create {
+        return Err(anyhow::anyhow!("RAG collection '{}' does not exist", name));
+    }
+
+    let collection = Arc::new(RwLock::new(RAGSystem::load_collection(name)));
+    collections.insert(name.to_string(), collection.clone());
+    println!("[RAG] Opened collection '{}'", name);
+    Ok(collection)
 }
 
-// Global RAG system instance
-static RAG_SYSTEM: once_cell::sync::Lazy<Arc<Mutex<Option<RAGSystem>>>> =
-    once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(None)));
+/// Apply newly saved privacy rules to every open collection
+pub fn set_exclusion_rules(rules: &ExclusionRules) {
+    for collection in RAG_COLLECTIONS.lock().unwrap().values() {
+        collection
+            .write()
+            .unwrap()
+            .set_exclusion_rules(rules.clone());
+    }
+}
 
 // Public API functions for Tauri commands
-pub async fn initialize_rag() -> Result<String> {
-    {
-        let mut rag_system = RAG_SYSTEM.lock().unwrap();
-        if rag_system.is_none() {
-            *rag_system = Some(RAGSystem::new());
-        }
+pub async fn initialize_rag(collection: Option<String>) -> Result<String> {
+    let rag = open_collection(collection.as_deref(), true)?;
+    let chunk_count = rag.read().unwrap().chunk_count();
+    Ok(format!(
+        "RAG system initialized successfully ({} chunks loaded)",
+        chunk_count
+    ))
+}
+
+pub async fn create_collection(name: String) -> Result<String> {
+    validate_collection_name(&name)?;
+    if RAG_COLLECTIONS.lock().unwrap().contains_key(&name) || ChunkStore::collection_exists(&name) {
+        return Err(anyhow::anyhow!("RAG collection '{}' already exists", name));
     }
-    Ok("RAG system initialized successfully".to_string())
+    open_collection(Some(&name), true)?;
+    Ok(format!("Created RAG collection '{}'", name))
 }
 
-pub async fn ingest_data_rag(data_files: Vec<serde_json::Value>) -> Result<String> {
-    let chunk_count = {
-        let mut rag_system = RAG_SYSTEM.lock().unwrap();
-        if let Some(ref mut system) = *rag_system {
-            system.ingest_data(&data_files)?
-        } else {
-            return Err(anyhow::anyhow!("RAG system not initialized"));
-        }
-    };
+/// Every collection on disk or in memory, with its chunk count
+pub async fn list_collections() -> Result<Vec<CollectionInfo>> {
+    let mut names: HashSet<String> = ChunkStore::list_collections()?.into_iter().collect();
+    names.insert(DEFAULT_COLLECTION.to_string());
+    let loaded: HashMap<String, SharedRAGSystem> = RAG_COLLECTIONS.lock().unwrap().clone();
+    names.extend(loaded.keys().cloned());
+
+    let mut infos = Vec::new();
+    for name in names {
+        let (chunk_count, is_loaded) = match loaded.get(&name) {
+            Some(rag) => (rag.read().unwrap().chunk_count(), true),
+            None => {
+                let count = ChunkStore::open_collection(&name)?
+                    .stats()?
+                    .get("total_points")
+                    .copied()
+                    .unwrap_or(0);
+                (count, false)
+            }
+        };
+        infos.push(CollectionInfo {
+            name,
+            chunk_count,
+            loaded: is_loaded,
+        });
+    }
+    infos.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(infos)
+}
+
+/// Unload a collection and delete its on-disk index. The default collection can only be cleared.
+pub async fn drop_collection(name: String) -> Result<String> {
+    validate_collection_name(&name)?;
+    if name == DEFAULT_COLLECTION {
+        return Err(anyhow::anyhow!(
+            "The default collection cannot be dropped; clear it instead"
+        ));
+    }
+
+    let was_loaded = RAG_COLLECTIONS.lock().unwrap().remove(&name).is_some();
+    let was_on_disk = ChunkStore::collection_exists(&name);
+    if !was_loaded && !was_on_disk {
+        return Err(anyhow::anyhow!("RAG collection '{}' does not exist", name));
+    }
+    if was_on_disk {
+        ChunkStore::delete_collection(&name)?;
+    }
+    Ok(format!("Dropped RAG collection '{}'", name))
+}
+
+pub async fn ingest_data_rag(
+    data_files: Vec<serde_json::Value>,
+    collection: Option<String>,
+) -> Result<String> {
+    let rag = open_collection(collection.as_deref(), true)?;
+    let chunk_count = rag.write().unwrap().ingest_data(&data_files)?;
+    embed_pending_chunks_logged(&rag).await;
     Ok(format!("Successfully ingested {} chunks", chunk_count))
 }
 
+/// Answer a query. With `custom_query`, a SELECT over screenpipe's database, its rows are
+/// ingested first and the answer is drawn from them only.
 pub async fn query_rag_system(
-    query: String,
-    top_k: Option<usize>,
-    similarity_threshold: Option<f32>,
+    mut rag_query: RAGQuery,
     custom_query: Option<String>,
-    time_range: Option<String>,
 ) -> Result<RAGResponse> {
-    // If custom query is provided, first ingest the data
     if let Some(custom_sql) = custom_query {
         println!("[RAG] Query with custom SQL provided, ingesting data first");
-
-        // Initialize RAG system if not already initialized
-        {
-            let mut rag_system = RAG_SYSTEM.lock().unwrap();
-            if rag_system.is_none() {
-                *rag_system = Some(RAGSystem::new());
-                println!("[RAG] Initialized new RAG system for custom query");
-            }
-        }
-
-        // Ingest data using custom query
-        let ingest_result = ingest_sql_data_rag(time_range, Some(custom_sql)).await?;
+        let rows = fetch_sql_rows(&custom_sql).await?;
+        // Frames indexed earlier are skipped by ingestion but still belong to this answer
+        rag_query.filters.frame_ids = Some(
+            rows.iter()
+                .filter_map(|row| row["frame_id"].as_i64())
+                .collect(),
+        );
+        let rag = open_collection(rag_query.collection.as_deref(), true)?;
+        let ingest_result = process_sql_data_for_rag(rows, &rag).await?;
         println!("[RAG] Custom data ingestion result: {}", ingest_result);
     }
 
-    // Use default values if not provided
-    let top_k_value = top_k.unwrap_or(5);
-    let similarity_threshold_value = similarity_threshold.unwrap_or(0.1);
+    let rag = open_collection(rag_query.collection.as_deref(), false)?;
+    // The snapshot stays valid while ingestion writes a new version of the index
+    let index = rag.read().unwrap().snapshot();
+    let response = index.query_rag(&rag_query, None).await?;
+    Ok(response)
+}
 
-    let rag_query = RAGQuery {
-        query,
-        top_k: top_k_value,
-        similarity_threshold: similarity_threshold_value,
-    };
+/// Retrieve context for the query without generating an answer, for callers that
+/// build their own prompt around it
+pub async fn retrieve_rag_context(rag_query: &RAGQuery) -> Result<RAGRetrieval> {
+    let rag = open_collection(rag_query.collection.as_deref(), false)?;
+    let index = rag.read().unwrap().snapshot();
+    index.retrieve(rag_query).await
+}
 
-    let system_clone = {
-        let rag_system = RAG_SYSTEM.lock().unwrap();
-        if let Some(ref system) = *rag_system {
-            Some(system.clone())
-        } else {
-            None
-        }
-    };
+/// Like `query_rag_system`, but streams the answer to `on_delta` while it is generated
+pub async fn stream_rag_query(rag_query: RAGQuery, on_delta: DeltaSink<'_>) -> Result<RAGResponse> {
+    let rag = open_collection(rag_query.collection.as_deref(), false)?;
+    let index = rag.read().unwrap().snapshot();
+    index.query_rag(&rag_query, Some(on_delta)).await
+}
 
-    let system = system_clone.ok_or_else(|| anyhow::anyhow!("RAG system not initialized"))?;
-    let response = system.query_rag(&rag_query).await?;
+/// Fast lexical search over the RAG index without calling an LLM
+pub async fn keyword_search_rag(
+    query: String,
+    top_k: Option<usize>,
+    filters: Option<RAGFilters>,
+    collection: Option<String>,
+) -> Result<KeywordSearchResponse> {
+    let rag = open_collection(collection.as_deref(), false)?;
+    let index = rag.read().unwrap().snapshot();
+
+    let response =
+        index.keyword_search(&query, top_k.unwrap_or(20), &filters.unwrap_or_default())?;
+    println!(
+        "[RAG] Keyword search '{}' matched {} chunks",
+        query, response.total_matches
+    );
     Ok(response)
 }
 
-pub async fn clear_rag_data() -> Result<String> {
-    {
-        let mut rag_system = RAG_SYSTEM.lock().unwrap();
-        if let Some(ref mut system) = *rag_system {
-            system.clear_collection()?;
-        }
+pub async fn get_chunking_policy() -> Result<ChunkingPolicy> {
+    Ok(ChunkingPolicy::load())
+}
+
+/// Set the chunking strategy for a source type ("default" for everything else).
+/// Applies to chunks ingested from now on, in every collection.
+pub async fn set_chunking_config(source_type: String, config: ChunkingConfig) -> Result<String> {
+    let mut policy = ChunkingPolicy::load();
+    policy.set(&source_type, config);
+    policy.save().map_err(|e| anyhow::anyhow!(e))?;
+
+    let collections: Vec<SharedRAGSystem> =
+        RAG_COLLECTIONS.lock().unwrap().values().cloned().collect();
+    for rag in collections {
+        rag.write().unwrap().set_chunking_policy(policy.clone());
     }
+    Ok(format!("Chunking strategy for '{}' updated", source_type))
+}
+
+pub async fn clear_rag_data(collection: Option<String>) -> Result<String> {
+    let rag = open_collection(collection.as_deref(), false)?;
+    rag.write().unwrap().clear_collection()?;
     Ok("RAG data cleared successfully".to_string())
 }
 
-pub async fn get_rag_stats() -> Result<HashMap<String, usize>> {
-    let stats = {
-        let rag_system = RAG_SYSTEM.lock().unwrap();
-        if let Some(ref system) = *rag_system {
-            system.get_collection_stats()?
-        } else {
-            return Err(anyhow::anyhow!("RAG system not initialized"));
-        }
-    };
+pub async fn get_rag_stats(collection: Option<String>) -> Result<HashMap<String, usize>> {
+    let rag = open_collection(collection.as_deref(), false)?;
+    let stats = rag.read().unwrap().get_collection_stats()?;
     Ok(stats)
 }
 
+/// What `ingest_sql_data_rag` indexes for a time window
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SqlIngestMode {
+    /// Every capture: OCR text, transcriptions and app usage, plus summaries of the window
+    Detailed,
+    /// Per-app time totals for the window, for questions about where the time went
+    AppUsage,
+}
+
+impl Default for SqlIngestMode {
+    fn default() -> Self {
+        SqlIngestMode::Detailed
+    }
+}
+
+impl SqlIngestMode {
+    /// Time questions are answered from per-app totals, everything else from the captures
+    pub fn for_quick_action(action_type: &str) -> Self {
+        match action_type {
+            "time_usage" | "app_usage" => SqlIngestMode::AppUsage,
+            _ => SqlIngestMode::Detailed,
+        }
+    }
+}
+
 // Function to ingest data from SQL queries into RAG system
 pub async fn ingest_sql_data_rag(
-    time_range: Option<String>,
+    window: Option<TimeWindow>,
+    mode: SqlIngestMode,
     custom_query: Option<String>,
+    collection: Option<String>,
 ) -> Result<String> {
-    use reqwest::Client;
+    let rag = open_collection(collection.as_deref(), true)?;
 
-    let client = Client::new();
     println!(
-        "[RAG] Ingesting data with time_range: {:?}, custom_query: {:?}",
-        time_range, custom_query
+        "[RAG] Ingesting data with window: {:?}, mode: {:?}, custom_query: {:?}",
+        window, mode, custom_query
     );
 
     // If custom query is provided, use it directly
     if let Some(custom_sql) = custom_query {
         println!("[RAG] Using custom SQL query provided by user");
         println!("[RAG] Custom SQL Query: {}", custom_sql);
-        let query = custom_sql;
 
         println!("[RAG] Sending custom QL query to localhost:3030");
-        let response = client
-            .post("http://localhost:3030/raw_sql")
-            .header("Content-Type", "application/json")
-            .timeout(std::time::Duration::from_secs(60))
-            .json(&serde_json::json!({ "query": query }))
-            .send()
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to send SQL query: {}", e))?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(anyhow::anyhow!("SQL API error: {}", error_text));
-        }
-
-        let sql_data: Vec<serde_json::Value> = response
-            .json()
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to parse SQL response: {}", e))?;
+        let sql_data = fetch_sql_rows(&custom_sql).await?;
 
         // Process custom query results using existing logic
-        return process_sql_data_for_rag(sql_data).await;
+        return process_sql_data_for_rag(sql_data, &rag).await;
     }
 
     // Build SQL query to get comprehensive data
-    let time_filter =
-        time_range.unwrap_or_else(|| "f.timestamp > datetime('now', '-30 days')".to_string());
+    let window = window.unwrap_or_else(|| TimeWindow {
+        start: Utc::now() - chrono::Duration::days(30),
+        end: Utc::now(),
+    });
+    let time_filter = window.to_sql_filter();
+    let exclusions = ExclusionRules::load();
+    let privacy_filter = exclusions.sql_condition();
 
-    // Check if this is a time usage query
-    let is_time_usage_query = time_filter.to_lowercase().contains("month")
-        || time_filter.to_lowercase().contains("time")
-        || time_filter.to_lowercase().contains("usage");
+    println!("[RAG] Sending SQL query to localhost:3030");
+    match mode {
+        SqlIngestMode::AppUsage => {
+            let sql_data = fetch_sql_rows(&app_usage_query(&time_filter, &privacy_filter)).await?;
+            ingest_app_usage_rows(sql_data, &exclusions, &rag).await
+        }
+        SqlIngestMode::Detailed => {
+            let fetch_page = |offset| {
+                let query =
+                    capture_page_query(&time_filter, &privacy_filter, SQL_PAGE_SIZE, offset);
+                async move { fetch_sql_rows(&query).await }
+            };
+            ingest_capture_pages(fetch_page, SQL_PAGE_SIZE, &exclusions, &rag).await
+        }
+    }
+}
 
-    let query = if is_time_usage_query {
-        // For time usage queries, get aggregated data with better time calculation
-        format!(
-            r#"
+// Apps used in the window, aggregated per app with better time calculation
+fn app_usage_query(time_filter: &str, privacy_filter: &str) -> String {
+    format!(
+        r#"
             SELECT 
                 f.app_name,
                 COUNT(*) as frame_count,
@@ -648,17 +2119,25 @@ pub async fn ingest_sql_data_rag(
                 ROUND((JULIANDAY(MAX(f.timestamp)) - JULIANDAY(MIN(f.timestamp))) * 24 * 60, 2) as total_span_minutes,
                 GROUP_CONCAT(DISTINCT f.window_name) as window_names
             FROM frames f 
-            WHERE {} 
+            WHERE {} AND {} 
             GROUP BY f.app_name
             ORDER BY frame_count DESC
             LIMIT 50;
             "#,
-            time_filter
-        )
-    } else {
-        // For regular queries, get detailed data
-        format!(
-            r#"
+        time_filter, privacy_filter
+    )
+}
+
+// One page of the captures in the window. Oldest first with a stable order, so consecutive
+// offsets neither skip nor repeat rows.
+fn capture_page_query(
+    time_filter: &str,
+    privacy_filter: &str,
+    limit: usize,
+    offset: usize,
+) -> String {
+    format!(
+        r#"
             SELECT 
                 f.id AS frame_id, 
                 f.timestamp, 
@@ -679,16 +2158,16 @@ pub async fn ingest_sql_data_rag(
             LEFT JOIN audio_chunks ac ON f.video_chunk_id = ac.id 
             LEFT JOIN audio_transcriptions at ON at.audio_chunk_id = ac.id 
             LEFT JOIN ocr_text o ON o.frame_id = f.id 
-            WHERE {} 
-            ORDER BY f.timestamp DESC 
-            LIMIT 1000;
+            WHERE {} AND {} 
+            ORDER BY f.timestamp ASC, f.id ASC, at.id ASC 
+            LIMIT {} OFFSET {};
             "#,
-            time_filter
-        )
-    };
+        time_filter, privacy_filter, limit, offset
+    )
+}
 
-    println!("[RAG] Sending SQL query to localhost:3030");
-    let response = client
+async fn fetch_sql_rows(query: &str) -> Result<Vec<serde_json::Value>> {
+    let response = reqwest::Client::new()
         .post("http://localhost:3030/raw_sql")
         .header("Content-Type", "application/json")
         .timeout(std::time::Duration::from_secs(60))
@@ -705,10 +2184,24 @@ pub async fn ingest_sql_data_rag(
         return Err(anyhow::anyhow!("SQL API error: {}", error_text));
     }
 
-    let sql_data: Vec<serde_json::Value> = response
+    response
         .json()
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to parse SQL response: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Failed to parse SQL response: {}", e))
+}
+
+// Index the per-app rows of an app usage `sql_ingest_query`
+async fn ingest_app_usage_rows(
+    mut sql_data: Vec<serde_json::Value>,
+    exclusions: &ExclusionRules,
+    rag: &SharedRAGSystem,
+) -> Result<String> {
+    if exclusions.excludes_source("app_usage") {
+        println!("[RAG] App usage is excluded by the privacy rules, skipping time usage chunks");
+        return Ok("App usage is excluded by the privacy rules".to_string());
+    }
+    // The time usage chunks below read the rows directly
+    sql_data.retain(|row| !exclusions.excludes_row(row));
 
     // Print SQL query results for debugging
     println!("=== SQL QUERY RESULTS ===");
@@ -736,94 +2229,25 @@ pub async fn ingest_sql_data_rag(
     }
     println!("=== END SQL RESULTS ===");
 
-    // Initialize RAG system if not already initialized
-    {
-        let mut rag_system = RAG_SYSTEM.lock().unwrap();
-        if rag_system.is_none() {
-            *rag_system = Some(RAGSystem::new());
-            println!("[RAG] Initialized new RAG system");
-        }
-    }
-
     // Convert SQL data to RAG chunks
     let mut total_chunks = 0;
-    let mut ocr_chunks = 0;
-    let mut audio_chunks = 0;
     let mut app_chunks = 0;
 
     println!("[RAG] Processing {} SQL rows for ingestion", sql_data.len());
 
-    // Pre-calculate statistics for better context
-    let mut app_stats = std::collections::HashMap::new();
-    let mut website_stats = std::collections::HashMap::new();
-    let mut audio_stats = std::collections::HashMap::new();
-    let mut time_periods = Vec::new();
-
-    // First pass: collect statistics
-    for row in &sql_data {
-        let app_name = row["app_name"].as_str().unwrap_or("Unknown");
-        let timestamp = row["timestamp"].as_str().unwrap_or("");
-        let window_name = row["window_name"].as_str().unwrap_or("");
-        let browser_url = row["browser_url"].as_str().unwrap_or("");
-        let transcription = row["transcription"].as_str().unwrap_or("");
-        let _ocr_text = row["ocr_text"].as_str().unwrap_or(""); // Prefix with underscore to suppress warning
-
-        // App usage statistics
-        let app_entry = app_stats
-            .entry(app_name.to_string())
-            .or_insert_with(|| std::collections::HashMap::new());
-        *app_entry.entry("count".to_string()).or_insert(0) += 1;
-
-        // Store unique windows count
-        let windows_count = app_entry.entry("windows_count".to_string()).or_insert(0);
-        if !window_name.is_empty() {
-            *windows_count += 1;
-        }
-
-        // Website statistics
-        if !browser_url.is_empty() {
-            if let Some(domain) = extract_domain(browser_url) {
-                let website_entry = website_stats.entry(domain).or_insert(0);
-                *website_entry += 1;
-            }
-        }
-
-        // Audio statistics
-        if !transcription.is_empty() {
-            let audio_entry = audio_stats.entry(app_name.to_string()).or_insert(0);
-            *audio_entry += 1;
-        }
-
-        // Time periods
-        if !timestamp.is_empty() {
-            time_periods.push(timestamp.to_string());
-        }
-    }
-
-    // Calculate time range
-    let time_range_text = if time_periods.len() >= 2 {
-        let mut sorted_times = time_periods.clone();
-        sorted_times.sort();
-        format!(
-            "{} to {}",
-            sorted_times.first().unwrap(),
-            sorted_times.last().unwrap()
-        )
-    } else {
-        "Unknown time period".to_string()
-    };
-
-    // Check if we have aggregated data (time usage query)
-    if !sql_data.is_empty() && sql_data[0].get("frame_count").is_some() {
-        // Process aggregated time usage data
-        println!("[RAG] Processing aggregated time usage data");
+    // Process aggregated time usage data
+    println!("[RAG] Processing aggregated time usage data");
 
-        // Calculate total screen time from individual app data
-        let mut total_frames = 0;
-        let mut total_span_minutes = 0.0;
-        let mut all_first_seen = Vec::new();
-        let mut all_last_seen = Vec::new();
+    // Calculate total screen time from individual app data
+    let mut total_frames = 0;
+    let mut total_span_minutes = 0.0;
+    let mut all_first_seen = Vec::new();
+    let mut all_last_seen = Vec::new();
+    let mut pending = Vec::new();
 
+    // The whole batch is chunked and swapped in under one write lock
+    {
+        let mut system = rag.write().unwrap();
         for (index, row) in sql_data.iter().enumerate() {
             if let Some(app_name) = row["app_name"].as_str() {
                 // Process individual app data
@@ -847,12 +2271,14 @@ pub async fn ingest_sql_data_rag(
                 let estimated_active_minutes = total_span_minutes_app * 0.25; // Assume 25% active usage
 
                 let usage_text = format!(
-                    "App: {} - Estimated Active Time: {:.1} minutes ({:.1} hours) - Total Span: {:.1} minutes - Frames: {} - Period: {} to {} - Windows: {}",
-                    app_name, estimated_active_minutes, estimated_active_minutes / 60.0, total_span_minutes_app, frame_count, first_seen, last_seen, window_names
-                );
+                "App: {} - Estimated Active Time: {:.1} minutes ({:.1} hours) - Total Span: {:.1} minutes - Frames: {} - Period: {} to {} - Windows: {}",
+                app_name, estimated_active_minutes, estimated_active_minutes / 60.0, total_span_minutes_app, frame_count, first_seen, last_seen, window_names
+            );
 
+                // Dated by the last capture so time-bounded queries over the window find it
                 let metadata = serde_json::json!({
                     "app_name": app_name,
+                    "timestamp": row["last_seen"].as_str(),
                     "estimated_active_minutes": estimated_active_minutes,
                     "total_span_minutes": total_span_minutes_app,
                     "frame_count": frame_count,
@@ -861,26 +2287,17 @@ pub async fn ingest_sql_data_rag(
                     "source_type": "time_usage"
                 });
 
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&usage_text, "time_usage", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
+                let chunks = system.chunk_text(&usage_text, "time_usage", index, &metadata)?;
 
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
+                pending.extend(chunks);
                 app_chunks += 1;
                 total_chunks += 1;
             }
         }
 
+        // Time usage chunks describe the latest query window, so they replace older ones
+        system.replace_source_chunks("time_usage", std::mem::take(&mut pending))?;
+
         // Create total screen time summary
         if !sql_data.is_empty() {
             let unknown_str = "Unknown".to_string();
@@ -889,85 +2306,173 @@ pub async fn ingest_sql_data_rag(
             let unique_apps_used = sql_data.len() as u64;
             let total_active_minutes = total_span_minutes * 0.25; // Assume 25% active usage
 
-            let total_screen_text = format!(
-                "TOTAL SCREEN TIME SUMMARY - Total Active Time: {:.1} minutes ({:.1} hours) - Total Span: {:.1} minutes - Total Frames: {} - Period: {} to {} - Unique Apps Used: {}",
-                total_active_minutes, total_active_minutes / 60.0, total_span_minutes, total_frames, overall_first_seen, overall_last_seen, unique_apps_used
-            );
+            let total_screen_text = format!(
+            "TOTAL SCREEN TIME SUMMARY - Total Active Time: {:.1} minutes ({:.1} hours) - Total Span: {:.1} minutes - Total Frames: {} - Period: {} to {} - Unique Apps Used: {}",
+            total_active_minutes, total_active_minutes / 60.0, total_span_minutes, total_frames, overall_first_seen, overall_last_seen, unique_apps_used
+        );
+
+            let metadata = serde_json::json!({
+                "app_name": "TOTAL_SCREEN_TIME",
+                "timestamp": all_last_seen.iter().max(),
+                "total_active_minutes": total_active_minutes,
+                "total_span_minutes": total_span_minutes,
+                "total_frames": total_frames,
+                "overall_first_seen": overall_first_seen,
+                "overall_last_seen": overall_last_seen,
+                "unique_apps_used": unique_apps_used,
+                "source_type": "total_screen_time"
+            });
+
+            let chunks =
+                system.chunk_text(&total_screen_text, "total_screen_time", 0, &metadata)?;
+
+            system.replace_source_chunks("total_screen_time", chunks)?;
+            total_chunks += 1;
+        }
+    }
+
+    println!("[RAG] Ingestion Summary:");
+    println!("[RAG]   Total apps processed: {}", sql_data.len());
+    println!("[RAG]   Time usage chunks created: {}", app_chunks);
+    println!("[RAG]   Total chunks: {}", total_chunks);
+
+    embed_pending_chunks_logged(rag).await;
+
+    Ok(format!(
+        "Successfully ingested {} time usage chunks into RAG system",
+        total_chunks
+    ))
+}
+
+/// Index every capture of a window page by page, oldest first, then summarize the whole window.
+/// `fetch_page` gets the offset of the next page; a page shorter than `page_size` is the last.
+async fn ingest_capture_pages<F, Fut>(
+    mut fetch_page: F,
+    page_size: usize,
+    exclusions: &ExclusionRules,
+    rag: &SharedRAGSystem,
+) -> Result<String>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<serde_json::Value>>>,
+{
+    let mut total_chunks = 0;
+    let mut summary = SummaryStats::default();
+    let mut offset = 0;
+    loop {
+        let mut page = fetch_page(offset).await?;
+        let fetched = page.len();
+        println!("[RAG] Fetched {} SQL rows at offset {}", fetched, offset);
+        offset += fetched;
+
+        page.retain(|row| !exclusions.excludes_row(row));
+        // Excluded sources stay out of the chunks and the summaries alike
+        for row in &mut page {
+            exclusions.strip_excluded_sources(row);
+        }
+        total_chunks += ingest_from(&SqlRowIngestor(&page), rag).await?.total();
+        for row in &page {
+            summary.add(row);
+        }
+
+        if fetched < page_size {
+            break;
+        }
+    }
+
+    // Summaries are app usage statistics
+    if !exclusions.excludes_source("app_usage") {
+        total_chunks += add_summary_chunks(&summary, rag)?;
+    }
+    Ok(format!(
+        "Successfully ingested {} data chunks into RAG system",
+        total_chunks
+    ))
+}
+
+/// App, website and audio counts over the captures of a window, gathered page by page so the
+/// rows themselves need not be kept
+#[derive(Default)]
+struct SummaryStats {
+    // Frames and frames with a window title, per app
+    apps: HashMap<String, (usize, usize)>,
+    // Frames per browser domain
+    websites: HashMap<String, usize>,
+    // Clips per app
+    audio: HashMap<String, usize>,
+    first_seen: Option<String>,
+    last_seen: Option<String>,
+}
 
-            let metadata = serde_json::json!({
-                "app_name": "TOTAL_SCREEN_TIME",
-                "total_active_minutes": total_active_minutes,
-                "total_span_minutes": total_span_minutes,
-                "total_frames": total_frames,
-                "overall_first_seen": overall_first_seen,
-                "overall_last_seen": overall_last_seen,
-                "unique_apps_used": unique_apps_used,
-                "source_type": "total_screen_time"
-            });
+impl SummaryStats {
+    fn add(&mut self, row: &serde_json::Value) {
+        let app_name = row["app_name"].as_str().unwrap_or("Unknown");
+        let timestamp = row["timestamp"].as_str().unwrap_or("");
+        let window_name = row["window_name"].as_str().unwrap_or("");
+        let browser_url = row["browser_url"].as_str().unwrap_or("");
+        let transcription = row["transcription"].as_str().unwrap_or("");
 
-            let chunks = {
-                let rag_system = RAG_SYSTEM.lock().unwrap();
-                if let Some(ref system) = *rag_system {
-                    system.chunk_text(&total_screen_text, "total_screen_time", 0, &metadata)?
-                } else {
-                    return Err(anyhow::anyhow!("RAG system not initialized"));
-                }
-            };
+        let app = self.apps.entry(app_name.to_string()).or_insert((0, 0));
+        app.0 += 1;
+        if !window_name.is_empty() {
+            app.1 += 1;
+        }
 
-            {
-                let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                if let Some(ref mut system) = *rag_system {
-                    system.chunks.extend(chunks);
-                }
-            }
-            total_chunks += 1;
+        if let Some(domain) = extract_domain(browser_url) {
+            *self.websites.entry(domain).or_insert(0) += 1;
         }
 
-        println!("[RAG] Ingestion Summary:");
-        println!("[RAG]   Total apps processed: {}", sql_data.len());
-        println!("[RAG]   Time usage chunks created: {}", app_chunks);
-        println!("[RAG]   Total chunks: {}", total_chunks);
+        if !transcription.is_empty() {
+            *self.audio.entry(app_name.to_string()).or_insert(0) += 1;
+        }
 
-        return Ok(format!(
-            "Successfully ingested {} time usage chunks into RAG system",
-            total_chunks
-        ));
+        if !timestamp.is_empty() {
+            if self
+                .first_seen
+                .as_deref()
+                .map_or(true, |first| timestamp < first)
+            {
+                self.first_seen = Some(timestamp.to_string());
+            }
+            if self
+                .last_seen
+                .as_deref()
+                .map_or(true, |last| timestamp > last)
+            {
+                self.last_seen = Some(timestamp.to_string());
+            }
+        }
     }
+}
+
+// App, website and audio summaries of a window, replacing those of the previous window
+fn add_summary_chunks(stats: &SummaryStats, rag: &SharedRAGSystem) -> Result<usize> {
+    let mut total_chunks = 0;
+    let SummaryStats {
+        apps: app_stats,
+        websites: website_stats,
+        audio: audio_stats,
+        first_seen,
+        last_seen,
+    } = stats;
+
+    let time_range_text = match (first_seen, last_seen) {
+        (Some(first), Some(last)) => format!("{} to {}", first, last),
+        _ => "Unknown time period".to_string(),
+    };
 
     // Create summary chunks
     let mut summary_chunks = Vec::new();
 
-    // App usage summary
-    let mut app_summary_parts = Vec::new();
-    for (app_name, stats) in &app_stats {
-        let count = stats.get("count").unwrap_or(&0);
-        let windows_count = stats.get("windows_count").unwrap_or(&0);
-        app_summary_parts.push(format!(
-            "{} ({} frames, {} windows)",
-            app_name, count, windows_count
-        ));
-    }
-    app_summary_parts.sort_by(|a, b| {
-        let count_a = a
-            .split('(')
-            .nth(1)
-            .unwrap_or("0")
-            .split(' ')
-            .next()
-            .unwrap_or("0")
-            .parse::<i32>()
-            .unwrap_or(0);
-        let count_b = b
-            .split('(')
-            .nth(1)
-            .unwrap_or("0")
-            .split(' ')
-            .next()
-            .unwrap_or("0")
-            .parse::<i32>()
-            .unwrap_or(0);
-        count_b.cmp(&count_a)
-    });
+    // App usage summary, most used first
+    let mut app_list: Vec<_> = app_stats.iter().collect();
+    app_list.sort_by_key(|(_, (count, _))| std::cmp::Reverse(*count));
+    let app_summary_parts: Vec<String> = app_list
+        .iter()
+        .map(|(app_name, (count, windows_count))| {
+            format!("{} ({} frames, {} windows)", app_name, count, windows_count)
+        })
+        .collect();
 
     let app_summary = format!(
         "App Usage Summary ({} apps): {}",
@@ -982,6 +2487,7 @@ pub async fn ingest_sql_data_rag(
 
     let app_summary_metadata = serde_json::json!({
         "source_type": "app_summary",
+        "timestamp": last_seen,
         "total_apps": app_stats.len(),
         "time_range": time_range_text
     });
@@ -1006,6 +2512,7 @@ pub async fn ingest_sql_data_rag(
 
         let website_summary_metadata = serde_json::json!({
             "source_type": "website_summary",
+        "timestamp": last_seen,
             "total_domains": website_stats.len(),
             "time_range": time_range_text
         });
@@ -1031,6 +2538,7 @@ pub async fn ingest_sql_data_rag(
 
         let audio_summary_metadata = serde_json::json!({
             "source_type": "audio_summary",
+        "timestamp": last_seen,
             "total_audio_apps": audio_stats.len(),
             "time_range": time_range_text
         });
@@ -1039,174 +2547,34 @@ pub async fn ingest_sql_data_rag(
     }
 
     // Add summary chunks to RAG system
-    for (summary_text, metadata) in summary_chunks {
-        let chunks = {
-            let rag_system = RAG_SYSTEM.lock().unwrap();
-            if let Some(ref system) = *rag_system {
-                system.chunk_text(&summary_text, "summary", 0, &metadata)?
-            } else {
-                return Err(anyhow::anyhow!("RAG system not initialized"));
-            }
-        };
-
-        {
-            let mut rag_system = RAG_SYSTEM.lock().unwrap();
-            if let Some(ref mut system) = *rag_system {
-                system.chunks.extend(chunks);
-            }
-        }
-        total_chunks += 1;
-    }
-
-    // Process individual rows for detailed context
-    for (index, row) in sql_data.iter().enumerate() {
-        // Process OCR text with enhanced context
-        if let Some(ocr_text) = row["ocr_text"].as_str() {
-            if !ocr_text.is_empty() {
-                let enhanced_text = format!(
-                    "OCR from {}: {}",
-                    row["app_name"].as_str().unwrap_or("Unknown"),
-                    ocr_text
-                );
-
-                let metadata = serde_json::json!({
-                    "app_name": row["app_name"].as_str().unwrap_or("Unknown"),
-                    "timestamp": row["timestamp"].as_str(),
-                    "window_name": row["window_name"].as_str(),
-                    "browser_url": row["browser_url"].as_str().unwrap_or(""),
-                    "source_type": "ocr",
-                    "ocr_length": row["ocr_text_length"].as_u64().unwrap_or(0)
-                });
-
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&enhanced_text, "ocr", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
-
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
-                ocr_chunks += 1;
-                total_chunks += 1;
-            }
-        }
-
-        // Process audio transcriptions with enhanced context
-        if let Some(transcription) = row["transcription"].as_str() {
-            if !transcription.is_empty() {
-                let enhanced_text = format!(
-                    "Audio from {} ({}): {}",
-                    row["app_name"].as_str().unwrap_or("Unknown"),
-                    row["device"].as_str().unwrap_or("Unknown"),
-                    transcription
-                );
-
-                let metadata = serde_json::json!({
-                    "app_name": row["app_name"].as_str().unwrap_or("Unknown"),
-                    "timestamp": row["timestamp"].as_str(),
-                    "speaker_id": row["device"].as_str(),
-                    "transcription_engine": row["transcription_engine"].as_str().unwrap_or("Unknown"),
-                    "start_time": row["start_time"].as_f64(),
-                    "end_time": row["end_time"].as_f64(),
-                    "source_type": "audio"
-                });
-
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&enhanced_text, "audio", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
-
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
-                audio_chunks += 1;
-                total_chunks += 1;
-            }
-        }
-
-        // Process app usage patterns with enhanced context
-        if let Some(app_name) = row["app_name"].as_str() {
-            if !app_name.is_empty() {
-                let browser_url = row["browser_url"].as_str().unwrap_or("");
-                let url_info = if !browser_url.is_empty() {
-                    format!(" - URL: {}", browser_url)
-                } else {
-                    "".to_string()
-                };
-
-                let enhanced_text = format!(
-                    "App Activity: {} - Window: {} - Timestamp: {}{}",
-                    app_name,
-                    row["window_name"].as_str().unwrap_or("Unknown"),
-                    row["timestamp"].as_str().unwrap_or("Unknown"),
-                    url_info
-                );
-
-                let metadata = serde_json::json!({
-                    "app_name": app_name,
-                    "timestamp": row["timestamp"].as_str(),
-                    "window_name": row["window_name"].as_str(),
-                    "browser_url": browser_url,
-                    "frame_id": row["frame_id"].as_u64(),
-                    "source_type": "app_usage"
-                });
-
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&enhanced_text, "app_usage", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
+    {
+        let mut system = rag.write().unwrap();
+        let mut pending = Vec::new();
+        for (summary_text, metadata) in summary_chunks {
+            let chunks = system.chunk_text(&summary_text, "summary", 0, &metadata)?;
 
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
-                app_chunks += 1;
-                total_chunks += 1;
-            }
+            pending.extend(chunks);
+            total_chunks += 1;
         }
 
-        // Progress logging every 1000 rows
-        if (index + 1) % 1000 == 0 {
-            println!("[RAG] Processed {} rows...", index + 1);
-        }
+        // Summaries describe the latest query window, so they replace older ones
+        system.replace_source_chunks("summary", pending)?;
     }
 
-    println!("[RAG] Ingestion Summary:");
-    println!("[RAG]   Total rows processed: {}", sql_data.len());
-    println!("[RAG]   OCR chunks created: {}", ocr_chunks);
-    println!("[RAG]   Audio chunks created: {}", audio_chunks);
-    println!("[RAG]   App usage chunks created: {}", app_chunks);
-    println!("[RAG]   Total chunks: {}", total_chunks);
-
-    println!(
-        "[RAG] Successfully ingested {} chunks into global RAG system",
-        total_chunks
-    );
+    Ok(total_chunks)
+}
 
-    Ok(format!(
-        "Successfully ingested {} data chunks into RAG system",
-        total_chunks
-    ))
+// Screenpipe stores timestamps either as RFC 3339 or as "YYYY-MM-DD HH:MM:SS[.fff]" in UTC
+pub fn parse_chunk_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = timestamp.parse::<DateTime<Utc>>() {
+        return Some(dt);
+    }
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|dt| dt.and_utc())
 }
 
 // Helper function to extract domain from URL
@@ -1222,173 +2590,106 @@ fn extract_domain(url: &str) -> Option<String> {
     }
 }
 
-// Helper function to process SQL data for RAG ingestion
-async fn process_sql_data_for_rag(sql_data: Vec<serde_json::Value>) -> Result<String> {
-    // Initialize RAG system if not already initialized
-    {
-        let mut rag_system = RAG_SYSTEM.lock().unwrap();
-        if rag_system.is_none() {
-            *rag_system = Some(RAGSystem::new());
-            println!("[RAG] Initialized new RAG system");
-        }
-    }
-
-    // Convert SQL data to RAG chunks
-    let mut total_chunks = 0;
-    let mut ocr_chunks = 0;
-    let mut audio_chunks = 0;
-    let mut app_chunks = 0;
-
-    println!("[RAG] Processing {} SQL rows for ingestion", sql_data.len());
-
-    // Process individual rows for detailed context
-    for (index, row) in sql_data.iter().enumerate() {
-        // Process OCR text with enhanced context
-        if let Some(ocr_text) = row["ocr_text"].as_str() {
-            if !ocr_text.is_empty() {
-                let enhanced_text = format!(
-                    "OCR from {}: {}",
-                    row["app_name"].as_str().unwrap_or("Unknown"),
-                    ocr_text
-                );
-
-                let metadata = serde_json::json!({
-                    "app_name": row["app_name"].as_str().unwrap_or("Unknown"),
-                    "timestamp": row["timestamp"].as_str(),
-                    "window_name": row["window_name"].as_str(),
-                    "browser_url": row["browser_url"].as_str().unwrap_or(""),
-                    "source_type": "ocr",
-                    "ocr_length": row["ocr_text_length"].as_u64().unwrap_or(0)
-                });
-
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&enhanced_text, "ocr", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
-
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
-                ocr_chunks += 1;
-                total_chunks += 1;
-            }
-        }
-
-        // Process audio transcriptions with enhanced context
-        if let Some(transcription) = row["transcription"].as_str() {
-            if !transcription.is_empty() {
-                let enhanced_text = format!(
-                    "Audio from {} ({}): {}",
-                    row["app_name"].as_str().unwrap_or("Unknown"),
-                    row["device"].as_str().unwrap_or("Unknown"),
-                    transcription
-                );
-
-                let metadata = serde_json::json!({
-                    "app_name": row["app_name"].as_str().unwrap_or("Unknown"),
-                    "timestamp": row["timestamp"].as_str(),
-                    "speaker_id": row["device"].as_str(),
-                    "transcription_engine": row["transcription_engine"].as_str().unwrap_or("Unknown"),
-                    "start_time": row["start_time"].as_f64(),
-                    "end_time": row["end_time"].as_f64(),
-                    "source_type": "audio"
-                });
-
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&enhanced_text, "audio", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
+/// Index rows returned by screenpipe's `/raw_sql` into a collection
+pub async fn process_sql_data_for_rag(
+    sql_data: Vec<serde_json::Value>,
+    rag: &SharedRAGSystem,
+) -> Result<String> {
+    let stats = ingest_from(&SqlRowIngestor(&sql_data), rag).await?;
+    Ok(format!(
+        "Successfully ingested {} data chunks into RAG system",
+        stats.total()
+    ))
+}
 
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
-                audio_chunks += 1;
-                total_chunks += 1;
-            }
-        }
+/// Index what an ingestor yields into a collection and embed the new chunks.
+/// Captures are chunked under the read lock and committed under one write lock.
+pub async fn ingest_from(ingestor: &dyn Ingestor, rag: &SharedRAGSystem) -> Result<IngestStats> {
+    let captures = ingestor.captures();
+    println!(
+        "[RAG] Processing {} {} captures for ingestion",
+        captures.len(),
+        ingestor.source_name()
+    );
 
-        // Process app usage patterns with enhanced context
-        if let Some(app_name) = row["app_name"].as_str() {
-            if !app_name.is_empty() {
-                let browser_url = row["browser_url"].as_str().unwrap_or("");
-                let url_info = if !browser_url.is_empty() {
-                    format!(" - URL: {}", browser_url)
-                } else {
-                    "".to_string()
-                };
-
-                let enhanced_text = format!(
-                    "App Activity: {} - Window: {} - Timestamp: {}{}",
-                    app_name,
-                    row["window_name"].as_str().unwrap_or("Unknown"),
-                    row["timestamp"].as_str().unwrap_or("Unknown"),
-                    url_info
-                );
+    let mut stats = IngestStats::default();
+    let pending = rag
+        .read()
+        .unwrap()
+        .prepare_captures(&captures, &mut stats)?;
+    let added = rag.write().unwrap().add_chunks(pending)?;
+    stats.added = added.added;
+    stats.duplicates_dropped = added.duplicates_dropped;
+    stats.log(ingestor.source_name());
+
+    embed_pending_chunks_logged(rag).await;
+    Ok(stats)
+}
 
-                let metadata = serde_json::json!({
-                    "app_name": app_name,
-                    "timestamp": row["timestamp"].as_str(),
-                    "window_name": row["window_name"].as_str(),
-                    "browser_url": browser_url,
-                    "frame_id": row["frame_id"].as_u64(),
-                    "source_type": "app_usage"
-                });
+/// Fetch results of a screenpipe `/search` and index them like any other capture.
+/// `time_range` accepts anything `time_expr` understands; without it every match is fetched.
+pub async fn ingest_search_rag(
+    query: Option<String>,
+    content_type: Option<String>,
+    time_range: Option<String>,
+    limit: Option<usize>,
+    collection: Option<String>,
+) -> Result<String> {
+    let rag = open_collection(collection.as_deref(), true)?;
+    let limit = limit.unwrap_or(SEARCH_INGEST_LIMIT);
+
+    let mut params: Vec<(&str, String)> = vec![(
+        "content_type",
+        content_type.unwrap_or_else(|| "all".to_string()),
+    )];
+    if let Some(query) = query.filter(|q| !q.trim().is_empty()) {
+        params.push(("q", query));
+    }
+    if let Some(time_range) = time_range {
+        let window =
+            crate::time_expr::parse_time_expression(&time_range).map_err(|e| anyhow::anyhow!(e))?;
+        params.push(("start_time", window.start.to_rfc3339()));
+        params.push(("end_time", window.end.to_rfc3339()));
+    }
 
-                let chunks = {
-                    let rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref system) = *rag_system {
-                        system.chunk_text(&enhanced_text, "app_usage", index, &metadata)?
-                    } else {
-                        return Err(anyhow::anyhow!("RAG system not initialized"));
-                    }
-                };
+    let client = reqwest::Client::new();
+    let mut items: Vec<serde_json::Value> = Vec::new();
+    while items.len() < limit {
+        let page_size = (limit - items.len()).min(SEARCH_PAGE_SIZE);
+        let response = client
+            .get("http://localhost:3030/search")
+            .query(&params)
+            .query(&[("limit", page_size), ("offset", items.len())])
+            .timeout(std::time::Duration::from_secs(60))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to send search request: {}", e))?;
 
-                {
-                    let mut rag_system = RAG_SYSTEM.lock().unwrap();
-                    if let Some(ref mut system) = *rag_system {
-                        system.chunks.extend(chunks);
-                    }
-                }
-                app_chunks += 1;
-                total_chunks += 1;
-            }
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(anyhow::anyhow!("Search API error: {}", error_text));
         }
 
-        // Progress logging every 1000 rows
-        if (index + 1) % 1000 == 0 {
-            println!("[RAG] Processed {} rows...", index + 1);
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse search response: {}", e))?;
+        let page = body["data"].as_array().cloned().unwrap_or_default();
+        let exhausted = page.len() < page_size;
+        items.extend(page);
+        if exhausted {
+            break;
         }
     }
 
-    println!("[RAG] Ingestion Summary:");
-    println!("[RAG]   Total rows processed: {}", sql_data.len());
-    println!("[RAG]   OCR chunks created: {}", ocr_chunks);
-    println!("[RAG]   Audio chunks created: {}", audio_chunks);
-    println!("[RAG]   App usage chunks created: {}", app_chunks);
-    println!("[RAG]   Total chunks: {}", total_chunks);
-
-    println!(
-        "[RAG] Successfully ingested {} chunks into global RAG system",
-        total_chunks
-    );
-
+    let stats = ingest_from(&SearchIngestor(&items), &rag).await?;
     Ok(format!(
-        "Successfully ingested {} data chunks into RAG system",
-        total_chunks
+        "Successfully ingested {} data chunks from {} search results",
+        stats.total(),
+        items.len()
     ))
 }
 
@@ -1403,6 +2704,8 @@ pub async fn perform_pure_rust_analysis(time_range: Option<(String, String)>) ->
     } else {
         "f.timestamp > datetime('now', '-1 day')".to_string()
     };
+    let exclusions = ExclusionRules::load();
+    let where_condition = format!("{} AND {}", where_condition, exclusions.sql_condition());
 
     let sql_query = format!(
         "SELECT f.id AS frame_id, f.timestamp, f.name AS video_file, f.window_name, f.app_name, f.browser_url, ac.file_path AS audio_file, at.transcription, at.device, at.is_input_device, at.transcription_engine, at.start_time, at.end_time, o.text AS ocr_text, o.text_length AS ocr_text_length, COUNT(*) OVER () AS total_count FROM frames f LEFT JOIN audio_chunks ac ON f.video_chunk_id = ac.id LEFT JOIN audio_transcriptions at ON at.audio_chunk_id = ac.id LEFT JOIN ocr_text o ON o.frame_id = f.id WHERE {} ORDER BY f.timestamp DESC LIMIT 10000;",
@@ -1426,10 +2729,14 @@ pub async fn perform_pure_rust_analysis(time_range: Option<(String, String)>) ->
         ));
     }
 
-    let sql_data: Vec<serde_json::Value> = response
+    let mut sql_data: Vec<serde_json::Value> = response
         .json()
         .await
         .map_err(|e| anyhow::anyhow!("Failed to parse SQL response: {}", e))?;
+    sql_data.retain(|row| !exclusions.excludes_row(row));
+    for row in sql_data.iter_mut() {
+        exclusions.strip_excluded_sources(row);
+    }
 
     println!(
         "[PURE_RUST] Retrieved {} rows from database",
@@ -2023,3 +3330,307 @@ impl ToTitleCase for str {
             .join(" ")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag_eval::offline_system;
+    use chrono::TimeZone;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn ocr_row(frame_id: i64, timestamp: &str, window_name: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({
+            "frame_id": frame_id,
+            "timestamp": timestamp,
+            "window_name": window_name,
+            "app_name": "Excel",
+            "browser_url": "",
+            "ocr_text": text,
+            "ocr_text_length": text.len()
+        })
+    }
+
+    /// Offline collection with the rows ingested the way a captured time window is
+    async fn ingest_rows(rows: &[serde_json::Value]) -> SharedRAGSystem {
+        let rag = offline_system();
+        let fetch_page = |offset: usize| {
+            let page = rows[offset.min(rows.len())..].to_vec();
+            async move { Ok(page) }
+        };
+        ingest_capture_pages(fetch_page, SQL_PAGE_SIZE, &ExclusionRules::default(), &rag)
+            .await
+            .unwrap();
+        rag
+    }
+
+    fn ocr_chunks(rag: &SharedRAGSystem) -> Vec<DataChunk> {
+        rag.read()
+            .unwrap()
+            .snapshot()
+            .filtered_chunks(&RAGFilters {
+                source_types: vec!["ocr".to_string()],
+                ..RAGFilters::default()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn quick_actions_pick_their_ingest_mode() {
+        // Whatever the time range phrase, only time questions are answered from app totals
+        for action in ["time_usage", "app_usage"] {
+            assert_eq!(
+                SqlIngestMode::for_quick_action(action),
+                SqlIngestMode::AppUsage,
+                "{}",
+                action
+            );
+        }
+        for action in [
+            "past_hour",
+            "meeting_analysis",
+            "coding_activity",
+            "slack_activity",
+            "github_activity",
+            "audio_files",
+            "screenshots_with_text",
+            "custom",
+        ] {
+            assert_eq!(
+                SqlIngestMode::for_quick_action(action),
+                SqlIngestMode::Detailed,
+                "{}",
+                action
+            );
+        }
+        assert_eq!(SqlIngestMode::default(), SqlIngestMode::Detailed);
+    }
+
+    #[tokio::test]
+    async fn app_usage_quick_actions_retrieve_what_they_ingested() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 18, 0, 0).unwrap();
+        let window = crate::time_expr::parse_time_expression_at("past 12 hours", now).unwrap();
+        let rows = vec![
+            serde_json::json!({
+                "app_name": "Excel",
+                "frame_count": 120,
+                "total_span_minutes": 45.0,
+                "first_seen": "2024-05-15 09:00:00",
+                "last_seen": "2024-05-15 09:45:00",
+                "window_names": "budget.xlsx"
+            }),
+            serde_json::json!({
+                "app_name": "Slack",
+                "frame_count": 60,
+                "total_span_minutes": 30.0,
+                "first_seen": "2024-05-15 10:00:00",
+                "last_seen": "2024-05-15 10:30:00",
+                "window_names": "#design"
+            }),
+        ];
+
+        // Same steps as `quick_action_rag` once the rows are fetched
+        for action in ["app_usage", "time_usage"] {
+            assert_eq!(
+                SqlIngestMode::for_quick_action(action),
+                SqlIngestMode::AppUsage
+            );
+            let rag = offline_system();
+            ingest_app_usage_rows(rows.clone(), &ExclusionRules::default(), &rag)
+                .await
+                .unwrap();
+
+            let mut query = RAGQuery::new("How much time did I spend in each app?");
+            query.top_k = 10;
+            query.similarity_threshold = 0.1;
+            query.filters = RAGFilters::for_quick_action(action);
+            query.filters.start_time = Some(window.start.to_rfc3339());
+            query.filters.end_time = Some(window.end.to_rfc3339());
+            let index = rag.read().unwrap().snapshot();
+            let response = index.query_rag(&query, None).await.unwrap();
+
+            let apps: HashSet<&str> = response
+                .context_chunks
+                .iter()
+                .filter(|chunk| chunk.metadata.source_type == "time_usage")
+                .filter_map(|chunk| chunk.metadata.app_name.as_deref())
+                .collect();
+            assert_eq!(apps, HashSet::from(["Excel", "Slack"]), "{}", action);
+        }
+    }
+
+    #[tokio::test]
+    async fn frame_id_filter_keeps_only_the_listed_frames() {
+        let rows: Vec<serde_json::Value> = (1..=3)
+            .map(|frame_id| {
+                ocr_row(
+                    frame_id,
+                    "2024-05-14 09:00:00",
+                    &format!("notes-{}.md", frame_id),
+                    &format!("Design review notes, part {}", frame_id),
+                )
+            })
+            .collect();
+        let rag = offline_system();
+        process_sql_data_for_rag(rows, &rag).await.unwrap();
+        let index = rag.read().unwrap().snapshot();
+
+        let only = |frame_ids: Vec<i64>| {
+            let filters = RAGFilters {
+                frame_ids: Some(frame_ids),
+                ..RAGFilters::default()
+            };
+            let mut kept: Vec<i64> = index
+                .filtered_chunks(&filters)
+                .unwrap()
+                .iter()
+                .filter_map(|chunk| chunk.metadata.frame_id)
+                .collect();
+            kept.sort_unstable();
+            kept.dedup();
+            kept
+        };
+        assert_eq!(only(vec![1, 3]), vec![1, 3]);
+        // A custom query that returned no rows has nothing to answer from
+        assert!(only(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn retrieval_stops_before_generating_an_answer() {
+        let rows = [ocr_row(
+            3,
+            "2024-05-14 09:15:00",
+            "standup.md",
+            "Standup notes: migrate billing service to the new queue",
+        )];
+        let rag = ingest_rows(&rows).await;
+
+        let query = RAGQuery {
+            similarity_threshold: 0.0,
+            ..RAGQuery::new("billing queue migration")
+        };
+        let index = rag.read().unwrap().snapshot();
+        let retrieval = index.retrieve(&query).await.unwrap();
+
+        assert_eq!(retrieval.chunks.len(), retrieval.scores.len());
+        assert!(retrieval
+            .chunks
+            .iter()
+            .any(|chunk| chunk.metadata.frame_id == Some(3)));
+        assert!(retrieval.trace.provider.is_none());
+        assert!(retrieval.trace.model.is_none());
+        assert_eq!(retrieval.trace.generation_ms, 0);
+    }
+
+    #[tokio::test]
+    async fn privacy_rules_saved_later_keep_indexed_chunks_out() {
+        let rows = [
+            ocr_row(
+                11,
+                "2024-05-14 10:00:00",
+                "budget.xlsx",
+                "Marketing budget for the autumn campaign",
+            ),
+            serde_json::json!({
+                "frame_id": 12,
+                "timestamp": "2024-05-14 10:05:00",
+                "window_name": "Personal vault",
+                "app_name": "1Password",
+                "browser_url": "",
+                "ocr_text": "Vault item: home router admin login",
+                "ocr_text_length": 35
+            }),
+        ];
+        let usage = vec![serde_json::json!({
+            "app_name": "Excel",
+            "frame_count": 120,
+            "total_span_minutes": 45.0,
+            "first_seen": "2024-05-14 10:00:00",
+            "last_seen": "2024-05-14 10:45:00",
+            "window_names": "budget.xlsx"
+        })];
+        let rag = ingest_rows(&rows).await;
+        ingest_app_usage_rows(usage, &ExclusionRules::default(), &rag)
+            .await
+            .unwrap();
+
+        let rules = ExclusionRules {
+            apps: vec!["1password".to_string()],
+            source_types: vec!["app_usage".to_string()],
+            ..ExclusionRules::default()
+        };
+        rag.write().unwrap().set_exclusion_rules(rules);
+        let index = rag.read().unwrap().snapshot();
+        let kept = index.filtered_chunks(&RAGFilters::default()).unwrap();
+
+        let source_types: HashSet<&str> = index
+            .chunks
+            .iter()
+            .map(|chunk| chunk.metadata.source_type.as_str())
+            .collect();
+        for aggregate in ["time_usage", "total_screen_time", "summary"] {
+            assert!(
+                source_types.contains(aggregate),
+                "{} was not indexed",
+                aggregate
+            );
+        }
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].metadata.frame_id, Some(11));
+    }
+
+    #[tokio::test]
+    async fn every_hour_of_a_multi_page_window_is_covered() {
+        const HOURS: i64 = 30;
+        const PAGE_SIZE: usize = 40;
+        // Three captures an hour, oldest first like `capture_page_query` returns them
+        let start = Utc.with_ymd_and_hms(2024, 5, 13, 0, 0, 0).unwrap();
+        let rows: Vec<serde_json::Value> = (0..HOURS * 3)
+            .map(|i| {
+                let time = start + chrono::Duration::minutes(20 * i);
+                ocr_row(
+                    i + 1,
+                    &time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    &format!("ledger-{}.xlsx", i),
+                    &format!("Reconciled ledger entry {} for account {}", i, i * 7),
+                )
+            })
+            .collect();
+        assert!(rows.len() > 2 * PAGE_SIZE);
+
+        let requests = AtomicUsize::new(0);
+        let fetch_page = |offset: usize| {
+            requests.fetch_add(1, Ordering::SeqCst);
+            let end = (offset + PAGE_SIZE).min(rows.len());
+            let page = rows[offset.min(end)..end].to_vec();
+            async move { Ok(page) }
+        };
+        let rag = offline_system();
+        ingest_capture_pages(fetch_page, PAGE_SIZE, &ExclusionRules::default(), &rag)
+            .await
+            .unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        let ocr = ocr_chunks(&rag);
+        assert_eq!(ocr.len(), rows.len());
+        assert_eq!(
+            crate::summarize::group_by_hour(ocr).len(),
+            HOURS as usize,
+            "every hour of the window should have captures to summarize"
+        );
+
+        // Summary statistics are gathered across pages, not from the last one
+        let index = rag.read().unwrap().snapshot();
+        let app_summary = index
+            .chunks
+            .iter()
+            .find(|chunk| chunk.content.starts_with("App Usage Summary"))
+            .expect("an app usage summary");
+        assert!(
+            app_summary
+                .content
+                .contains(&format!("Excel ({} frames", rows.len())),
+            "{}",
+            app_summary.content
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// Offline retrieval evaluation. Fixture screenpipe rows are ingested into an in-memory
+// index with a mocked model, then each golden question is asked and the retrieved chunks
+// are scored against the ones that answer it.
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::ai::DeltaSink;
+use crate::bm25::tokenize;
+use crate::chunking::ChunkingPolicy;
+use crate::dedup::fnv1a_hash;
+use crate::llm::{EmbedFuture, LlmFuture, LlmProvider, ProviderConfig};
+use crate::privacy::ExclusionRules;
+use crate::rag::{
+    process_sql_data_for_rag, DataChunk, RAGQuery, RAGSystem, RetrievalMode, SharedRAGSystem,
+};
+
+const FIXTURE: &str = include_str!("../tests/fixtures/rag_eval.json");
+const K: usize = 5;
+const EMBEDDING_DIMENSIONS: usize = 256;
+// Baseline for hybrid retrieval; raise it when a retrieval change improves the numbers
+const MIN_RECALL_AT_K: f64 = 0.9;
+const MIN_MRR: f64 = 0.8;
+
+#[derive(Deserialize)]
+struct Fixture {
+    // Rows in the shape `/raw_sql` returns them
+    rows: Vec<serde_json::Value>,
+    cases: Vec<GoldenCase>,
+}
+
+#[derive(Deserialize)]
+struct GoldenCase {
+    question: String,
+    expected: Vec<ExpectedChunk>,
+}
+
+// Chunk ids are random, so expected chunks are identified by the row and source they came from
+#[derive(Deserialize)]
+struct ExpectedChunk {
+    frame_id: i64,
+    source_type: String,
+}
+
+impl ExpectedChunk {
+    fn matches(&self, chunk: &DataChunk) -> bool {
+        chunk.metadata.frame_id == Some(self.frame_id)
+            && chunk.metadata.source_type == self.source_type
+    }
+}
+
+const OFFLINE_ANSWER: &str = "The activity data covers this [1].";
+
+/// Hashed bag-of-words embeddings and a canned answer, so no model server is needed
+pub struct OfflineModel {
+    config: ProviderConfig,
+}
+
+impl OfflineModel {
+    pub fn new() -> Self {
+        Self {
+            config: ProviderConfig {
+                name: "offline".to_string(),
+                model: Some("offline".to_string()),
+                ..ProviderConfig::ollama()
+            },
+        }
+    }
+}
+
+impl LlmProvider for OfflineModel {
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    fn complete<'a>(&'a self, _system: Option<&'a str>, _prompt: &'a str) -> LlmFuture<'a> {
+        Box::pin(async { Ok(OFFLINE_ANSWER.to_string()) })
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        _system: Option<&'a str>,
+        _prompt: &'a str,
+        on_delta: DeltaSink<'a>,
+    ) -> LlmFuture<'a> {
+        Box::pin(async move {
+            on_delta(OFFLINE_ANSWER);
+            Ok(OFFLINE_ANSWER.to_string())
+        })
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> EmbedFuture<'a> {
+        Box::pin(async move { Ok(texts.iter().map(|text| embed_offline(text)).collect()) })
+    }
+}
+
+fn embed_offline(text: &str) -> Vec<f32> {
+    let mut embedding = vec![0.0f32; EMBEDDING_DIMENSIONS];
+    for token in tokenize(text) {
+        embedding[(fnv1a_hash(&token) % EMBEDDING_DIMENSIONS as u64) as usize] += 1.0;
+    }
+    let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|v| *v /= norm);
+    }
+    embedding
+}
+
+/// In-memory collection with default chunking and no privacy rules that embeds and answers offline
+pub fn offline_system() -> SharedRAGSystem {
+    // Default chunking and no exclusions, whatever is saved on this machine
+    let mut system = RAGSystem::with_model(Some(Arc::new(OfflineModel::new())));
+    system.set_chunking_policy(ChunkingPolicy::default());
+    system.set_exclusion_rules(ExclusionRules::default());
+    Arc::new(RwLock::new(system))
+}
+
+struct EvalReport {
+    mode: RetrievalMode,
+    recall_at_k: f64,
+    mrr: f64,
+    mean_latency: Duration,
+    p95_latency: Duration,
+    // Questions whose expected chunks were not all in the top k
+    misses: Vec<String>,
+}
+
+impl EvalReport {
+    fn print(&self, cases: usize) {
+        println!(
+            "[RAG_EVAL] {:?}: recall@{} {:.3}, MRR {:.3}, latency mean {:.1} ms / p95 {:.1} ms over {} questions",
+            self.mode,
+            K,
+            self.recall_at_k,
+            self.mrr,
+            self.mean_latency.as_secs_f64() * 1000.0,
+            self.p95_latency.as_secs_f64() * 1000.0,
+            cases
+        );
+        for question in &self.misses {
+            println!("[RAG_EVAL]   missed: {}", question);
+        }
+    }
+}
+
+async fn build_index(fixture: &Fixture) -> SharedRAGSystem {
+    let rag = offline_system();
+    process_sql_data_for_rag(fixture.rows.clone(), &rag)
+        .await
+        .expect("fixture rows should ingest");
+    rag
+}
+
+async fn evaluate(rag: &SharedRAGSystem, cases: &[GoldenCase], mode: RetrievalMode) -> EvalReport {
+    let index = rag.read().unwrap().snapshot();
+    let mut recall_sum = 0.0;
+    let mut reciprocal_rank_sum = 0.0;
+    let mut latencies = Vec::new();
+    let mut misses = Vec::new();
+
+    for case in cases {
+        let mut query = RAGQuery::new(case.question.clone());
+        query.top_k = K;
+        query.retrieval_mode = mode;
+
+        let started = Instant::now();
+        let response = index
+            .query_rag(&query, None)
+            .await
+            .expect("query should succeed offline");
+        latencies.push(started.elapsed());
+
+        let retrieved = &response.context_chunks;
+        let found = case
+            .expected
+            .iter()
+            .filter(|expected| retrieved.iter().any(|chunk| expected.matches(chunk)))
+            .count();
+        recall_sum += found as f64 / case.expected.len() as f64;
+        if found < case.expected.len() {
+            misses.push(case.question.clone());
+        }
+
+        if let Some(rank) = retrieved
+            .iter()
+            .position(|chunk| case.expected.iter().any(|expected| expected.matches(chunk)))
+        {
+            reciprocal_rank_sum += 1.0 / (rank + 1) as f64;
+        }
+    }
+
+    latencies.sort();
+    let count = cases.len().max(1);
+    EvalReport {
+        mode,
+        recall_at_k: recall_sum / count as f64,
+        mrr: reciprocal_rank_sum / count as f64,
+        mean_latency: latencies.iter().sum::<Duration>() / count as u32,
+        p95_latency: latencies
+            .get((latencies.len() * 95 / 100).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default(),
+        misses,
+    }
+}
+
+#[tokio::test]
+async fn retrieval_meets_baseline() {
+    let fixture: Fixture = serde_json::from_str(FIXTURE).expect("fixture should parse");
+    let rag = build_index(&fixture).await;
+
+    let mut hybrid = None;
+    for mode in [
+        RetrievalMode::Lexical,
+        RetrievalMode::Semantic,
+        RetrievalMode::Hybrid,
+    ] {
+        let report = evaluate(&rag, &fixture.cases, mode).await;
+        report.print(fixture.cases.len());
+        if mode == RetrievalMode::Hybrid {
+            hybrid = Some(report);
+        }
+    }
+
+    let hybrid = hybrid.unwrap();
+    assert!(
+        hybrid.recall_at_k >= MIN_RECALL_AT_K,
+        "hybrid recall@{} {:.3} is below the baseline {:.3}",
+        K,
+        hybrid.recall_at_k,
+        MIN_RECALL_AT_K
+    );
+    assert!(
+        hybrid.mrr >= MIN_MRR,
+        "hybrid MRR {:.3} is below the baseline {:.3}",
+        hybrid.mrr,
+        MIN_MRR
+    );
+}
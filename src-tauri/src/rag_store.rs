@@ -0,0 +1,350 @@
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::rag::{ChunkMetadata, DataChunk};
+
+const STORE_FILE_NAME: &str = "rag_index.sqlite";
+// Named collections other than the default live here, one database per collection
+const COLLECTIONS_DIR_NAME: &str = "rag_collections";
+const COLLECTION_EXTENSION: &str = "sqlite";
+
+/// SQLite-backed chunk store that keeps the RAG index across restarts.
+///
+/// Chunks coming from screenpipe rows are keyed by `frame_id` so re-running an
+/// ingestion over an overlapping time range only adds frames we have not seen yet.
+#[derive(Clone)]
+pub struct ChunkStore {
+    path: PathBuf,
+}
+
+impl ChunkStore {
+    /// Open the store in the app data directory
+    pub fn open_default() -> Result<Self> {
+        let data_dir = crate::paths::get_app_data_dir().map_err(|e| anyhow::anyhow!(e))?;
+        Self::open(data_dir.join(STORE_FILE_NAME))
+    }
+
+    /// Open the store backing a named collection; the default collection keeps the original file
+    pub fn open_collection(name: &str) -> Result<Self> {
+        if name == crate::rag::DEFAULT_COLLECTION {
+            return Self::open_default();
+        }
+        let dir = Self::collections_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        Self::open(Self::collection_path(&dir, name))
+    }
+
+    pub fn collection_exists(name: &str) -> bool {
+        Self::collections_dir()
+            .map(|dir| Self::collection_path(&dir, name).exists())
+            .unwrap_or(false)
+    }
+
+    /// Names of the named collections on disk (the default collection is not included)
+    pub fn list_collections() -> Result<Vec<String>> {
+        let dir = Self::collections_dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(COLLECTION_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    /// Delete a named collection's database, including its WAL files
+    pub fn delete_collection(name: &str) -> Result<()> {
+        let path = Self::collection_path(&Self::collections_dir()?, name);
+        std::fs::remove_file(&path)?;
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(suffix);
+            let _ = std::fs::remove_file(sidecar);
+        }
+        Ok(())
+    }
+
+    fn collections_dir() -> Result<PathBuf> {
+        let data_dir = crate::paths::get_app_data_dir().map_err(|e| anyhow::anyhow!(e))?;
+        Ok(data_dir.join(COLLECTIONS_DIR_NAME))
+    }
+
+    fn collection_path(dir: &Path, name: &str) -> PathBuf {
+        dir.join(format!("{}.{}", name, COLLECTION_EXTENSION))
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let store = Self {
+            path: path.as_ref().to_path_buf(),
+        };
+        let conn = store.connect()?;
+        conn.execute_batch(
+            r#"
+            PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS chunks (
+                id TEXT PRIMARY KEY,
+                frame_id INTEGER,
+                source_type TEXT NOT NULL,
+                timestamp TEXT,
+                content TEXT NOT NULL,
+                metadata TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX IF NOT EXISTS idx_chunks_frame ON chunks(frame_id, source_type);
+            CREATE INDEX IF NOT EXISTS idx_chunks_source ON chunks(source_type);
+            CREATE TABLE IF NOT EXISTS source_keys (
+                key TEXT PRIMARY KEY
+            );
+            CREATE TABLE IF NOT EXISTS summaries (
+                level TEXT NOT NULL,
+                period_start TEXT NOT NULL,
+                period_end TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                content TEXT NOT NULL,
+                source_count INTEGER NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (level, period_start)
+            );
+            "#,
+        )?;
+        Self::migrate(&conn)?;
+        println!("[RAG_STORE] Opened chunk store at {}", store.path.display());
+        Ok(store)
+    }
+
+    // Add columns introduced after the first schema version
+    fn migrate(conn: &Connection) -> Result<()> {
+        let mut stmt = conn.prepare("PRAGMA table_info(chunks)")?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        if !columns.iter().any(|c| c == "embedding") {
+            conn.execute("ALTER TABLE chunks ADD COLUMN embedding BLOB", [])?;
+        }
+        Ok(())
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        Connection::open(&self.path)
+            .map_err(|e| anyhow::anyhow!("Failed to open RAG store {}: {}", self.path.display(), e))
+    }
+
+    pub fn load_chunks(&self) -> Result<Vec<DataChunk>> {
+        let conn = self.connect()?;
+        let mut stmt =
+            conn.prepare("SELECT id, content, metadata, embedding FROM chunks ORDER BY rowid")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<Vec<u8>>>(3)?,
+            ))
+        })?;
+
+        let mut chunks = Vec::new();
+        for row in rows {
+            let (id, content, metadata_json, embedding) = row?;
+            let metadata: ChunkMetadata = match serde_json::from_str(&metadata_json) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    println!("[RAG_STORE] Skipping chunk {} with bad metadata: {}", id, e);
+                    continue;
+                }
+            };
+            chunks.push(DataChunk {
+                id,
+                content,
+                metadata,
+                embedding: embedding.map(|bytes| decode_embedding(&bytes)),
+            });
+        }
+
+        Ok(chunks)
+    }
+
+    /// Insert chunks in a single transaction, ignoring ids that are already stored.
+    /// Returns the number of chunks actually written.
+    pub fn insert_chunks(&self, chunks: &[DataChunk]) -> Result<usize> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO chunks (id, frame_id, source_type, timestamp, content, metadata, embedding) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for chunk in chunks {
+                inserted += stmt.execute(params![
+                    chunk.id,
+                    chunk.metadata.frame_id,
+                    chunk.metadata.source_type,
+                    chunk.metadata.timestamp,
+                    chunk.content,
+                    serde_json::to_string(&chunk.metadata)?,
+                    chunk.embedding.as_deref().map(encode_embedding),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    pub fn update_embeddings(&self, embeddings: &[(String, Vec<f32>)]) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("UPDATE chunks SET embedding = ?1 WHERE id = ?2")?;
+            for (id, embedding) in embeddings {
+                stmt.execute(params![encode_embedding(embedding), id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rewrite the metadata of chunks that were updated in place (e.g. repeat counts)
+    pub fn update_metadata(&self, chunks: &[&DataChunk]) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("UPDATE chunks SET metadata = ?1 WHERE id = ?2")?;
+            for chunk in chunks {
+                stmt.execute(params![serde_json::to_string(&chunk.metadata)?, chunk.id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Source keys of captures that were ingested without a chunk of their own
+    /// (collapsed near-duplicates)
+    pub fn load_source_keys(&self) -> Result<Vec<String>> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare("SELECT key FROM source_keys")?;
+        let keys = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(keys)
+    }
+
+    pub fn insert_source_keys(&self, keys: &[String]) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("INSERT OR IGNORE INTO source_keys (key) VALUES (?1)")?;
+            for key in keys {
+                stmt.execute(params![key])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cached summary of one period, if one was saved for exactly these inputs
+    pub fn load_summary(
+        &self,
+        level: &str,
+        period_start: &str,
+        fingerprint: &str,
+    ) -> Result<Option<String>> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(
+            "SELECT content FROM summaries WHERE level = ?1 AND period_start = ?2 AND fingerprint = ?3",
+        )?;
+        let mut rows = stmt.query(params![level, period_start, fingerprint])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save a period summary, replacing one built from older inputs
+    pub fn save_summary(
+        &self,
+        level: &str,
+        period: (&str, &str),
+        fingerprint: &str,
+        content: &str,
+        source_count: usize,
+    ) -> Result<()> {
+        let conn = self.connect()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO summaries (level, period_start, period_end, fingerprint, content, source_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![level, period.0, period.1, fingerprint, content, source_count as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_source_type(&self, source_type: &str) -> Result<usize> {
+        let conn = self.connect()?;
+        Ok(conn.execute(
+            "DELETE FROM chunks WHERE source_type = ?1",
+            params![source_type],
+        )?)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        let conn = self.connect()?;
+        conn.execute("DELETE FROM chunks", [])?;
+        conn.execute("DELETE FROM source_keys", [])?;
+        conn.execute("DELETE FROM summaries", [])?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<HashMap<String, usize>> {
+        let conn = self.connect()?;
+        let mut stats = HashMap::new();
+
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM chunks", [], |row| row.get(0))?;
+        stats.insert("total_points".to_string(), total as usize);
+
+        let frames: i64 = conn.query_row(
+            "SELECT COUNT(DISTINCT frame_id) FROM chunks WHERE frame_id IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        stats.insert("frames_indexed".to_string(), frames as usize);
+
+        let duplicates: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(json_extract(metadata, '$.repeat_count') - 1), 0) FROM chunks WHERE json_extract(metadata, '$.repeat_count') IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        stats.insert("duplicates_dropped".to_string(), duplicates as usize);
+
+        let mut stmt =
+            conn.prepare("SELECT source_type, COUNT(*) FROM chunks GROUP BY source_type")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (source_type, count) = row?;
+            stats.insert(format!("{}_chunks", source_type), count as usize);
+        }
+
+        Ok(stats)
+    }
+}
+
+// Embeddings are stored as little-endian f32 blobs
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
@@ -0,0 +1,606 @@
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::llm::ProviderConfig;
+
+const CONFIG_FILE_NAME: &str = "redaction.json";
+const MAX_RECENT_REPORTS: usize = 50;
+
+// Saved configuration with its custom patterns compiled, loaded on first use
+static ACTIVE: Lazy<RwLock<Option<Arc<ActiveConfig>>>> = Lazy::new(|| RwLock::new(None));
+// Latest reports with something masked, newest last
+static RECENT_REPORTS: Lazy<Mutex<VecDeque<RedactionReport>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionLevel {
+    /// Send text as is
+    Off,
+    /// Mask secrets and financial data: API keys and tokens, passwords, card numbers,
+    /// IBANs and the custom patterns
+    Standard,
+    /// Also mask contact details: email addresses and phone numbers
+    Strict,
+}
+
+impl Default for RedactionLevel {
+    fn default() -> Self {
+        RedactionLevel::Strict
+    }
+}
+
+/// A user-defined pattern; matches are reported under `name`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomPattern {
+    pub name: String,
+    pub pattern: String,
+}
+
+/// Redaction levels and user-defined patterns, persisted in the app data dir
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedactionConfig {
+    /// Explicit levels by provider name, ahead of the local and remote defaults
+    #[serde(default)]
+    pub providers: HashMap<String, RedactionLevel>,
+    /// For providers on this machine (a loopback `base_url`), whatever their kind
+    #[serde(default = "default_local_level")]
+    pub local_level: RedactionLevel,
+    /// For every other provider
+    #[serde(default)]
+    pub default_level: RedactionLevel,
+    #[serde(default)]
+    pub custom_patterns: Vec<CustomPattern>,
+}
+
+/// What was masked in one prompt sent to a provider. Only counts are kept, never the values.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedactionReport {
+    pub provider: String,
+    pub level: RedactionLevel,
+    pub created_at: String,
+    /// Matches per category ("email", "api_key", ... or a custom pattern's name)
+    pub masked: BTreeMap<String, usize>,
+}
+
+struct ActiveConfig {
+    config: RedactionConfig,
+    custom: Vec<(String, Regex)>,
+}
+
+struct Rule {
+    category: &'static str,
+    regex: Regex,
+    // Capture group holding the sensitive part; the rest of the match (a label) is kept
+    group: usize,
+    validate: Option<fn(&str) -> bool>,
+    strict_only: bool,
+}
+
+impl Rule {
+    fn new(category: &'static str, pattern: &str) -> Self {
+        Self {
+            category,
+            regex: Regex::new(pattern).unwrap(),
+            group: 0,
+            validate: None,
+            strict_only: false,
+        }
+    }
+
+    fn group(mut self, group: usize) -> Self {
+        self.group = group;
+        self
+    }
+
+    fn validate(mut self, validate: fn(&str) -> bool) -> Self {
+        self.validate = Some(validate);
+        self
+    }
+
+    fn strict_only(mut self) -> Self {
+        self.strict_only = true;
+        self
+    }
+}
+
+// Tokens go first so their digits are never taken for card or phone numbers
+static BUILTIN_RULES: Lazy<Vec<Rule>> = Lazy::new(|| {
+    vec![
+        Rule::new(
+            "api_key",
+            r"\b(?:sk-(?:proj-|ant-)?[A-Za-z0-9_-]{20,}|gh[pousr]_[A-Za-z0-9]{30,}|github_pat_[A-Za-z0-9_]{30,}|xox[abposr]-[A-Za-z0-9-]{10,}|AKIA[0-9A-Z]{16}|AIza[0-9A-Za-z_-]{35}|glpat-[A-Za-z0-9_-]{20,})",
+        ),
+        Rule::new(
+            "token",
+            r"\beyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+        ),
+        Rule::new("token", r"(?i)\bbearer\s+([A-Za-z0-9._~+/-]{20,}=*)").group(1),
+        Rule::new(
+            "api_key",
+            r#"(?i)\b(?:api[_-]?key|secret(?:[_-]?key)?|access[_-]?token|auth[_-]?token|client[_-]?secret|private[_-]?key)\b\s*[:=]\s*["']?([A-Za-z0-9_\-./+=]{12,})"#,
+        )
+        .group(1),
+        Rule::new(
+            "password",
+            r"(?i)\b(?:password|passwd|passcode|pwd|pin)\b\s*[:=]\s*(\S+)",
+        )
+        .group(1),
+        Rule::new("credit_card", r"\b(?:\d[ -]?){12,18}\d\b").validate(is_card_number),
+        Rule::new("iban", r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b").validate(is_iban),
+        Rule::new(
+            "email",
+            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+        )
+        .strict_only(),
+        // International numbers need the "+"; local ones one of the usual layouts, so dates
+        // and times never match
+        Rule::new(
+            "phone",
+            r"(?:\+\d{1,3}[ .-]?(?:\(\d{1,4}\)[ .-]?)?\d{1,4}(?:[ .-]?\d{2,4}){2,4}|\(\d{3}\)[ .-]?\d{3}[ .-]\d{4}|\b\d{3}[.-]\d{3}[.-]\d{4})\b",
+        )
+        .validate(is_phone_number)
+        .strict_only(),
+    ]
+});
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            providers: HashMap::new(),
+            local_level: default_local_level(),
+            default_level: RedactionLevel::default(),
+            custom_patterns: Vec::new(),
+        }
+    }
+}
+
+// Local models never send anything off the machine
+fn default_local_level() -> RedactionLevel {
+    RedactionLevel::Off
+}
+
+impl RedactionConfig {
+    pub fn level_for(&self, provider: &ProviderConfig) -> RedactionLevel {
+        if let Some(&level) = self.providers.get(&provider.name) {
+            return level;
+        }
+        if is_local(provider) {
+            self.local_level
+        } else {
+            self.default_level
+        }
+    }
+
+    fn file_path() -> Result<PathBuf, String> {
+        Ok(crate::paths::get_app_data_dir()?.join(CONFIG_FILE_NAME))
+    }
+
+    /// Load the saved configuration, falling back to the defaults
+    pub fn load() -> Self {
+        let path = match Self::file_path() {
+            Ok(path) => path,
+            Err(_) => return Self::default(),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                println!("[REDACT] Ignoring invalid {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Save the configuration and apply it to every call from now on.
+    /// Fails without saving if a custom pattern is not a valid regex.
+    pub fn save(&self) -> Result<(), String> {
+        let active = compile(self.clone())?;
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize redaction config: {}", e))?;
+        fs::write(Self::file_path()?, content)
+            .map_err(|e| format!("Failed to save redaction config: {}", e))?;
+        *ACTIVE.write().unwrap() = Some(Arc::new(active));
+        Ok(())
+    }
+}
+
+impl RedactionReport {
+    pub fn total(&self) -> usize {
+        self.masked.values().sum()
+    }
+}
+
+fn compile(config: RedactionConfig) -> Result<ActiveConfig, String> {
+    let custom = config
+        .custom_patterns
+        .iter()
+        .map(|custom| {
+            Regex::new(&custom.pattern)
+                .map(|regex| (custom.name.clone(), regex))
+                .map_err(|e| format!("Invalid pattern '{}': {}", custom.name, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(ActiveConfig { config, custom })
+}
+
+fn active_config() -> Arc<ActiveConfig> {
+    if let Some(active) = ACTIVE.read().unwrap().as_ref() {
+        return active.clone();
+    }
+
+    let config = RedactionConfig::load();
+    let active = match compile(config.clone()) {
+        Ok(active) => active,
+        Err(e) => {
+            println!("[REDACT] Ignoring custom patterns: {}", e);
+            ActiveConfig {
+                config,
+                custom: Vec::new(),
+            }
+        }
+    };
+    let active = Arc::new(active);
+    *ACTIVE.write().unwrap() = Some(active.clone());
+    active
+}
+
+/// Mask what `provider`'s policy keeps from leaving the machine
+pub fn redact_for_provider(provider: &ProviderConfig, text: &str) -> (String, RedactionReport) {
+    let active = active_config();
+    let level = active.config.level_for(provider);
+    let (redacted, masked) = redact(text, level, &active.custom);
+    let report = RedactionReport {
+        provider: provider.name.clone(),
+        level,
+        created_at: Utc::now().to_rfc3339(),
+        masked,
+    };
+    (redacted, report)
+}
+
+/// Redact a prompt right before it is sent to `provider`, keeping the report of what was masked
+pub fn prepare_for_provider(provider: &ProviderConfig, prompt: &str) -> String {
+    let (redacted, report) = redact_for_provider(provider, prompt);
+    keep_report(report);
+    redacted
+}
+
+/// `prepare_for_provider` for a batch of texts such as embedding inputs, with one report for
+/// the whole batch
+pub fn prepare_batch_for_provider(provider: &ProviderConfig, texts: &[String]) -> Vec<String> {
+    let active = active_config();
+    let level = active.config.level_for(provider);
+    let mut report = RedactionReport {
+        provider: provider.name.clone(),
+        level,
+        created_at: Utc::now().to_rfc3339(),
+        masked: BTreeMap::new(),
+    };
+    let redacted = texts
+        .iter()
+        .map(|text| {
+            let (redacted, masked) = redact(text, level, &active.custom);
+            for (category, count) in masked {
+                *report.masked.entry(category).or_insert(0) += count;
+            }
+            redacted
+        })
+        .collect();
+    keep_report(report);
+    redacted
+}
+
+fn keep_report(report: RedactionReport) {
+    if report.total() == 0 {
+        return;
+    }
+    println!(
+        "[REDACT] Masked {} items before calling {}: {:?}",
+        report.total(),
+        report.provider,
+        report.masked
+    );
+    let mut reports = RECENT_REPORTS.lock().unwrap();
+    reports.push_back(report);
+    while reports.len() > MAX_RECENT_REPORTS {
+        reports.pop_front();
+    }
+}
+
+/// Reports of prompts that had something masked, newest first
+pub fn recent_reports() -> Vec<RedactionReport> {
+    RECENT_REPORTS
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+// Whether requests stay on this machine, judged by the host of the provider's URL
+fn is_local(provider: &ProviderConfig) -> bool {
+    let Ok(url) = reqwest::Url::parse(&provider.base_url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .map_or(false, |ip| ip.is_loopback())
+}
+
+fn redact(
+    text: &str,
+    level: RedactionLevel,
+    custom: &[(String, Regex)],
+) -> (String, BTreeMap<String, usize>) {
+    let mut masked = BTreeMap::new();
+    if level == RedactionLevel::Off {
+        return (text.to_string(), masked);
+    }
+
+    let mut text = text.to_string();
+    for rule in BUILTIN_RULES.iter() {
+        if rule.strict_only && level != RedactionLevel::Strict {
+            continue;
+        }
+        text = mask_matches(
+            &text,
+            &rule.regex,
+            rule.group,
+            rule.validate,
+            rule.category,
+            &mut masked,
+        );
+    }
+    for (name, regex) in custom {
+        text = mask_matches(&text, regex, 0, None, name, &mut masked);
+    }
+    (text, masked)
+}
+
+fn mask_matches(
+    text: &str,
+    regex: &Regex,
+    group: usize,
+    validate: Option<fn(&str) -> bool>,
+    category: &str,
+    masked: &mut BTreeMap<String, usize>,
+) -> String {
+    let placeholder = format!("[REDACTED_{}]", category.to_uppercase());
+    regex
+        .replace_all(text, |caps: &regex::Captures| {
+            let whole = caps.get(0).unwrap();
+            let Some(secret) = caps.get(group) else {
+                return whole.as_str().to_string();
+            };
+            if validate.map_or(false, |validate| !validate(secret.as_str())) {
+                return whole.as_str().to_string();
+            }
+            *masked.entry(category.to_string()).or_insert(0) += 1;
+
+            // Keep the label around the secret ("Password: ")
+            let start = secret.start() - whole.start();
+            let end = secret.end() - whole.start();
+            format!(
+                "{}{}{}",
+                &whole.as_str()[..start],
+                placeholder,
+                &whole.as_str()[end..]
+            )
+        })
+        .into_owned()
+}
+
+// Luhn checksum, so order numbers and other long digit runs are left alone
+fn is_card_number(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+// ISO 13616 mod-97 check
+fn is_iban(candidate: &str) -> bool {
+    let compact: String = candidate.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let rearranged = format!("{}{}", &compact[4..], &compact[..4]);
+    let mut remainder: u32 = 0;
+    for c in rearranged.chars() {
+        let value = match c.to_digit(36) {
+            Some(value) => value,
+            None => return false,
+        };
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+fn is_phone_number(candidate: &str) -> bool {
+    let digits = candidate.chars().filter(|c| c.is_ascii_digit()).count();
+    (9..=15).contains(&digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ProviderKind;
+
+    fn redact_with(text: &str, level: RedactionLevel) -> (String, BTreeMap<String, usize>) {
+        redact(text, level, &[])
+    }
+
+    fn provider(name: &str, kind: ProviderKind, base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            name: name.to_string(),
+            kind,
+            base_url: base_url.to_string(),
+            ..ProviderConfig::ollama()
+        }
+    }
+
+    #[test]
+    fn contact_details_are_masked_only_when_strict() {
+        let text = "Mail jane.doe@example.com or call +44 20 7946 0958";
+
+        let (standard, masked) = redact_with(text, RedactionLevel::Standard);
+        assert_eq!(standard, text);
+        assert!(masked.is_empty());
+
+        let (strict, masked) = redact_with(text, RedactionLevel::Strict);
+        assert_eq!(strict, "Mail [REDACTED_EMAIL] or call [REDACTED_PHONE]");
+        assert_eq!(masked.get("email"), Some(&1));
+        assert_eq!(masked.get("phone"), Some(&1));
+    }
+
+    #[test]
+    fn dates_and_times_are_not_phone_numbers() {
+        let text = "Meeting on 2024-05-14 at 13:30:00, room 4.12";
+        let (redacted, masked) = redact_with(text, RedactionLevel::Strict);
+        assert_eq!(redacted, text);
+        assert!(masked.is_empty());
+    }
+
+    #[test]
+    fn card_numbers_must_pass_luhn() {
+        let (redacted, masked) = redact_with(
+            "Paid with 4111 1111 1111 1111 and 5500-0055-5555-5559",
+            RedactionLevel::Standard,
+        );
+        assert_eq!(
+            redacted,
+            "Paid with [REDACTED_CREDIT_CARD] and [REDACTED_CREDIT_CARD]"
+        );
+        assert_eq!(masked.get("credit_card"), Some(&2));
+
+        // Order and tracking numbers of card length that fail the checksum
+        for text in [
+            "Order 1234 5678 9012 3456 shipped",
+            "Tracking 100200300400500",
+        ] {
+            let (redacted, masked) = redact_with(text, RedactionLevel::Standard);
+            assert_eq!(redacted, text);
+            assert!(masked.is_empty(), "{} should not be masked", text);
+        }
+    }
+
+    #[test]
+    fn ibans_must_pass_mod_97() {
+        let (redacted, masked) =
+            redact_with("IBAN GB82 WEST 1234 5698 7654 32", RedactionLevel::Standard);
+        assert_eq!(redacted, "IBAN [REDACTED_IBAN]");
+        assert_eq!(masked.get("iban"), Some(&1));
+
+        let text = "IBAN GB82 WEST 1234 5698 7654 33";
+        let (redacted, masked) = redact_with(text, RedactionLevel::Standard);
+        assert_eq!(redacted, text);
+        assert!(masked.is_empty());
+    }
+
+    #[test]
+    fn custom_patterns_apply_at_standard_and_above() {
+        let custom = vec![(
+            "employee_id".to_string(),
+            Regex::new(r"\bEMP-\d{6}\b").unwrap(),
+        )];
+        let text = "Badge EMP-004217 and EMP-42";
+
+        let (redacted, masked) = redact(text, RedactionLevel::Standard, &custom);
+        assert_eq!(redacted, "Badge [REDACTED_EMPLOYEE_ID] and EMP-42");
+        assert_eq!(masked.get("employee_id"), Some(&1));
+
+        let (redacted, _) = redact(text, RedactionLevel::Off, &custom);
+        assert_eq!(redacted, text);
+    }
+
+    #[test]
+    fn level_follows_the_provider_host_not_its_name() {
+        let config = RedactionConfig::default();
+        let cases = [
+            (
+                ProviderKind::Ollama,
+                "http://localhost:11434",
+                RedactionLevel::Off,
+            ),
+            (
+                ProviderKind::Ollama,
+                "http://10.0.0.5:11434",
+                RedactionLevel::Strict,
+            ),
+            (
+                ProviderKind::OpenAICompatible,
+                "http://127.0.0.1:1234/v1",
+                RedactionLevel::Off,
+            ),
+            (
+                ProviderKind::OpenAICompatible,
+                "http://[::1]:8080/v1",
+                RedactionLevel::Off,
+            ),
+            (
+                ProviderKind::OpenAICompatible,
+                "https://api.openai.com/v1",
+                RedactionLevel::Strict,
+            ),
+        ];
+        for (kind, base_url, expected) in cases {
+            // A local-sounding name does not make a hosted provider local
+            let provider = provider("ollama", kind, base_url);
+            assert_eq!(config.level_for(&provider), expected, "{}", base_url);
+        }
+
+        let mut config = RedactionConfig::default();
+        config
+            .providers
+            .insert("work-proxy".to_string(), RedactionLevel::Standard);
+        let proxy = provider(
+            "work-proxy",
+            ProviderKind::OpenAICompatible,
+            "https://llm.example.com/v1",
+        );
+        assert_eq!(config.level_for(&proxy), RedactionLevel::Standard);
+    }
+
+    #[test]
+    fn missing_levels_fall_back_to_defaults() {
+        let config: RedactionConfig = serde_json::from_str(r#"{"custom_patterns": []}"#).unwrap();
+        assert_eq!(config.local_level, RedactionLevel::Off);
+        assert_eq!(config.default_level, RedactionLevel::Strict);
+    }
+}
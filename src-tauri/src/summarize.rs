@@ -0,0 +1,362 @@
+use anyhow::Result;
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::budget::ContextBudget;
+use crate::chunking::estimate_tokens;
+use crate::dedup::fnv1a_hash;
+use crate::rag::{open_collection, parse_chunk_time, DataChunk, RAGFilters};
+use crate::rag_store::ChunkStore;
+use crate::time_expr::TimeWindow;
+
+// Raw captures only; the aggregate chunks written at ingestion cover the whole ingested range
+const SUMMARY_SOURCE_TYPES: [&str; 3] = ["ocr", "audio", "app_usage"];
+// Instructions and period header around the data in a summarization prompt
+const PROMPT_OVERHEAD_TOKENS: usize = 128;
+
+/// Granularity of a summary. Hours summarize captures, days summarize hours, weeks summarize days.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryLevel {
+    Hour,
+    Day,
+    Week,
+}
+
+impl SummaryLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            SummaryLevel::Hour => "hour",
+            SummaryLevel::Day => "day",
+            SummaryLevel::Week => "week",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PeriodSummary {
+    pub level: SummaryLevel,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub content: String,
+    /// Captures (for hours) or lower-level summaries (for days and weeks) it was built from
+    pub source_count: usize,
+    // Hash of the inputs; the cached summary is reused while it matches
+    #[serde(skip)]
+    fingerprint: String,
+}
+
+/// Answer built from rolled-up summaries, with the summaries it used
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SummaryAnswer {
+    pub answer: String,
+    pub summaries: Vec<PeriodSummary>,
+}
+
+struct Summarizer {
+    store: Option<ChunkStore>,
+    // Tokens of input per summarization prompt
+    input_budget: usize,
+}
+
+/// Answer a question about a long time range by map-reduce summarization.
+/// Captures are summarized per hour, hours are rolled up per day and days per week, until
+/// the summaries fit in one prompt. Intermediate summaries are cached in the collection's
+/// store and reused as long as the captures under them have not changed.
+pub async fn answer_from_summaries(
+    question: &str,
+    window: TimeWindow,
+    collection: Option<&str>,
+) -> Result<SummaryAnswer> {
+    let rag = open_collection(collection, false)?;
+    let (index, store) = {
+        let system = rag.read().unwrap();
+        (system.snapshot(), system.store().cloned())
+    };
+
+    let filters = RAGFilters {
+        source_types: SUMMARY_SOURCE_TYPES.iter().map(|s| s.to_string()).collect(),
+        start_time: Some(window.start.to_rfc3339()),
+        end_time: Some(window.end.to_rfc3339()),
+        ..RAGFilters::default()
+    };
+    let chunks = index.filtered_chunks(&filters)?;
+    println!(
+        "[SUMMARY] {} captures between {}",
+        chunks.len(),
+        window.describe()
+    );
+
+    if chunks.is_empty() {
+        return Ok(SummaryAnswer {
+            answer: format!(
+                "No digital activity was recorded for {}.",
+                window.describe()
+            ),
+            summaries: Vec::new(),
+        });
+    }
+
+    let budget = ContextBudget::for_answer_model().await;
+    let summarizer = Summarizer {
+        store,
+        input_budget: budget.available("").saturating_sub(PROMPT_OVERHEAD_TOKENS),
+    };
+
+    // Map: one summary per hour
+    let mut summaries = summarizer.summarize_hours(chunks).await?;
+
+    // Reduce: roll up until everything fits in the answer prompt
+    for level in [SummaryLevel::Day, SummaryLevel::Week] {
+        if total_tokens(&summaries) <= summarizer.input_budget {
+            break;
+        }
+        summaries = summarizer.roll_up(&summaries, level).await?;
+    }
+
+    let context = summaries
+        .iter()
+        .map(format_summary)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let prompt = format!(
+        "Below are summaries of the user's digital activity for {}, in chronological order.\n\n\
+         {}\n\n\
+         Using only these summaries, respond to the request below. Mention specific days and times where relevant.\n\n\
+         REQUEST: {}",
+        window.describe(),
+        context,
+        question
+    );
+
+    let answer = crate::ai::call_ai_async(&prompt)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to generate answer: {}", e))?
+        .text;
+
+    Ok(SummaryAnswer { answer, summaries })
+}
+
+impl Summarizer {
+    async fn summarize_hours(&self, chunks: Vec<DataChunk>) -> Result<Vec<PeriodSummary>> {
+        let mut summaries = Vec::new();
+        for (hour, mut captures) in group_by_hour(chunks) {
+            captures.sort_by_key(|(time, _)| *time);
+            let mut ids: Vec<&str> = captures.iter().map(|(_, c)| c.id.as_str()).collect();
+            ids.sort_unstable();
+            let fingerprint = format!("{:016x}", fnv1a_hash(&ids.join(",")));
+
+            let lines = captures
+                .iter()
+                .map(|(time, chunk)| format_capture(*time, chunk))
+                .collect();
+            let start = local_to_utc(hour);
+            let summary = self
+                .summarize(
+                    SummaryLevel::Hour,
+                    (start, start + Duration::hours(1)),
+                    fingerprint,
+                    lines,
+                )
+                .await?;
+            summaries.push(summary);
+        }
+        Ok(summaries)
+    }
+
+    // Group summaries by the day or week they fall in and summarize each group
+    async fn roll_up(
+        &self,
+        summaries: &[PeriodSummary],
+        level: SummaryLevel,
+    ) -> Result<Vec<PeriodSummary>> {
+        let mut groups: BTreeMap<NaiveDate, Vec<&PeriodSummary>> = BTreeMap::new();
+        for summary in summaries {
+            let day = summary.start.with_timezone(&Local).date_naive();
+            let key = match level {
+                SummaryLevel::Week => {
+                    day - Duration::days(day.weekday().num_days_from_monday() as i64)
+                }
+                _ => day,
+            };
+            groups.entry(key).or_default().push(summary);
+        }
+
+        let mut rolled_up = Vec::new();
+        for (period_start, children) in groups {
+            // Nothing to condense for a single child
+            if let [only] = children.as_slice() {
+                rolled_up.push(PeriodSummary {
+                    level,
+                    ..(*only).clone()
+                });
+                continue;
+            }
+
+            let fingerprints: Vec<&str> = children.iter().map(|c| c.fingerprint.as_str()).collect();
+            let fingerprint = format!("{:016x}", fnv1a_hash(&fingerprints.join(",")));
+            let length = match level {
+                SummaryLevel::Week => Duration::days(7),
+                _ => Duration::days(1),
+            };
+            let start = local_to_utc(period_start.and_hms_opt(0, 0, 0).unwrap());
+            let lines = children.iter().map(|c| format_summary(c)).collect();
+
+            rolled_up.push(
+                self.summarize(level, (start, start + length), fingerprint, lines)
+                    .await?,
+            );
+        }
+        Ok(rolled_up)
+    }
+
+    async fn summarize(
+        &self,
+        level: SummaryLevel,
+        period: (DateTime<Utc>, DateTime<Utc>),
+        fingerprint: String,
+        lines: Vec<String>,
+    ) -> Result<PeriodSummary> {
+        let period_start = period.0.to_rfc3339();
+        let source_count = lines.len();
+
+        let cached = match &self.store {
+            Some(store) => store.load_summary(level.as_str(), &period_start, &fingerprint)?,
+            None => None,
+        };
+        let content = match cached {
+            Some(content) => content,
+            None => {
+                let lines = fit_to_budget(lines, self.input_budget);
+                let prompt = format!(
+                    "Summarize the user's digital activity during this {} ({}) in a short paragraph.\n\
+                     Name the applications, websites, documents, people and topics involved and what was accomplished. \
+                     Skip repeated or irrelevant screen text. Do not invent anything that is not in the data.\n\n\
+                     DATA:\n{}\n\nSummary:",
+                    level.as_str(),
+                    format_period(period),
+                    lines.join("\n")
+                );
+                let content = crate::ai::call_ai_async(&prompt)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to summarize {}: {}", level.as_str(), e))?
+                    .text
+                    .trim()
+                    .to_string();
+
+                if let Some(store) = &self.store {
+                    let period_end = period.1.to_rfc3339();
+                    store.save_summary(
+                        level.as_str(),
+                        (&period_start, &period_end),
+                        &fingerprint,
+                        &content,
+                        source_count,
+                    )?;
+                }
+                content
+            }
+        };
+
+        Ok(PeriodSummary {
+            level,
+            start: period.0,
+            end: period.1,
+            content,
+            source_count,
+            fingerprint,
+        })
+    }
+}
+
+/// Captures by the local hour they were taken in, oldest first; undated ones are left out
+pub fn group_by_hour(
+    chunks: Vec<DataChunk>,
+) -> BTreeMap<NaiveDateTime, Vec<(DateTime<Utc>, DataChunk)>> {
+    let mut hours: BTreeMap<NaiveDateTime, Vec<(DateTime<Utc>, DataChunk)>> = BTreeMap::new();
+    for chunk in chunks {
+        let Some(time) = chunk
+            .metadata
+            .timestamp
+            .as_deref()
+            .and_then(parse_chunk_time)
+        else {
+            continue;
+        };
+        let local = time.with_timezone(&Local).naive_local();
+        let hour = local.date().and_hms_opt(local.hour(), 0, 0).unwrap();
+        hours.entry(hour).or_default().push((time, chunk));
+    }
+    hours
+}
+
+// Keep evenly spaced lines so a busy period is still covered from start to end
+fn fit_to_budget(lines: Vec<String>, budget: usize) -> Vec<String> {
+    let total: usize = lines.iter().map(|l| estimate_tokens(l)).sum();
+    if total <= budget {
+        return lines;
+    }
+    let keep = (lines.len() * budget / total).max(1);
+    let step = lines.len() as f64 / keep as f64;
+    (0..keep)
+        .map(|i| lines[(i as f64 * step) as usize].clone())
+        .collect()
+}
+
+fn total_tokens(summaries: &[PeriodSummary]) -> usize {
+    summaries
+        .iter()
+        .map(|s| estimate_tokens(&format_summary(s)))
+        .sum()
+}
+
+fn local_to_utc(local: NaiveDateTime) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+fn format_period(period: (DateTime<Utc>, DateTime<Utc>)) -> String {
+    TimeWindow {
+        start: period.0,
+        end: period.1,
+    }
+    .describe()
+}
+
+fn format_capture(time: DateTime<Utc>, chunk: &DataChunk) -> String {
+    let source = match chunk.metadata.source_type.as_str() {
+        "audio" => format!(
+            "Audio - {}",
+            chunk.metadata.speaker_id.as_deref().unwrap_or("Unknown")
+        ),
+        source_type => format!(
+            "{} - {}",
+            source_type.to_uppercase(),
+            chunk.metadata.app_name.as_deref().unwrap_or("Unknown")
+        ),
+    };
+    format!(
+        "[{} {}] {}",
+        time.with_timezone(&Local).format("%H:%M"),
+        source,
+        chunk
+            .content
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+fn format_summary(summary: &PeriodSummary) -> String {
+    format!(
+        "[{}] {}",
+        format_period((summary.start, summary.end)),
+        summary.content
+    )
+}
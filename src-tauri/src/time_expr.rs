@@ -0,0 +1,690 @@
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Words that carry no meaning for the range itself ("for the past hour", "on Tuesday")
+const FILLER_WORDS: [&str; 10] = [
+    "the", "on", "at", "from", "during", "in", "of", "around", "between", "for",
+];
+const RANGE_SEPARATORS: [&str; 5] = ["-", "to", "until", "till", "and"];
+
+// "f.timestamp > datetime('now', '-7 days')", the only SQL form older callers still send
+static LEGACY_FILTER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^f\.timestamp\s*>=?\s*datetime\(\s*'now'\s*,\s*'-(\d+)\s*([a-z]+)'\s*\)$")
+        .unwrap()
+});
+
+/// Concrete time window in UTC; `end` is exclusive
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeWindow {
+    /// SQL condition on screenpipe's `frames` table (aliased `f`).
+    /// `datetime()` normalizes both sides, whatever format the stored timestamp uses.
+    pub fn to_sql_filter(self) -> String {
+        format!(
+            "datetime(f.timestamp) >= datetime('{}') AND datetime(f.timestamp) < datetime('{}')",
+            self.start.format("%Y-%m-%d %H:%M:%S"),
+            self.end.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+
+    /// The window in the user's timezone, for prompts and logs
+    pub fn describe(self) -> String {
+        let start = self.start.with_timezone(&Local);
+        let end = self.end.with_timezone(&Local);
+        let end_format = if start.date_naive() == end.date_naive() {
+            "%H:%M"
+        } else {
+            "%a %Y-%m-%d %H:%M"
+        };
+        format!(
+            "{} to {}",
+            start.format("%a %Y-%m-%d %H:%M"),
+            end.format(end_format)
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Meridiem {
+    Am,
+    Pm,
+}
+
+#[derive(Clone, Copy)]
+struct Clock {
+    hour: u32,
+    minute: u32,
+    meridiem: Option<Meridiem>,
+}
+
+impl Clock {
+    fn hour_24(&self) -> u32 {
+        match self.meridiem {
+            Some(Meridiem::Am) => self.hour % 12,
+            Some(Meridiem::Pm) => self.hour % 12 + 12,
+            // "2-4" without am/pm almost always means the afternoon
+            None if (1..=6).contains(&self.hour) => self.hour + 12,
+            None => self.hour,
+        }
+    }
+
+    fn time(&self) -> NaiveTime {
+        NaiveTime::from_hms_opt(self.hour_24() % 24, self.minute, 0).unwrap_or(NaiveTime::MIN)
+    }
+}
+
+/// Parse a phrase like "yesterday afternoon", "last Tuesday 2-4pm", "since Monday" or
+/// "the past 90 minutes" relative to the current time in the user's timezone.
+/// Also accepts the preset names ("daily", "weekly", "monthly", "yearly", "all_time"),
+/// explicit `start/end` RFC 3339 intervals and `f.timestamp > datetime('now', '-N unit')`.
+/// Anything else is an error; the text never ends up in SQL as is.
+pub fn parse_time_expression(text: &str) -> Result<TimeWindow, String> {
+    parse_time_expression_at(text, Local::now())
+}
+
+/// Parse relative to `now`; calendar days and clock times are taken in `now`'s timezone
+pub fn parse_time_expression_at<Tz: TimeZone>(
+    text: &str,
+    now: DateTime<Tz>,
+) -> Result<TimeWindow, String> {
+    if let Some(window) = parse_interval(text.trim()) {
+        return Ok(window);
+    }
+    if let Some(captures) = LEGACY_FILTER.captures(text.trim()) {
+        let past = format!("past {} {}", &captures[1], &captures[2]);
+        return parse_time_expression_at(&past, now);
+    }
+
+    let parser = Parser {
+        tz: now.timezone(),
+        now: now.naive_local(),
+    };
+    let tokens = tokenize(text);
+    if tokens.is_empty() {
+        return Err("Empty time range".to_string());
+    }
+
+    parser
+        .parse(&tokens)
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| TimeWindow {
+            start: parser.to_utc(start),
+            end: parser.to_utc(end),
+        })
+        .ok_or_else(|| format!("Could not understand the time range '{}'", text.trim()))
+}
+
+// "2024-05-01T09:00:00Z/2024-05-01T17:00:00Z"
+fn parse_interval(text: &str) -> Option<TimeWindow> {
+    let (start, end) = text.split_once('/')?;
+    let start = DateTime::parse_from_rfc3339(start.trim()).ok()?;
+    let end = DateTime::parse_from_rfc3339(end.trim()).ok()?;
+    (start < end).then(|| TimeWindow {
+        start: start.with_timezone(&Utc),
+        end: end.with_timezone(&Utc),
+    })
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let text = text
+        .to_lowercase()
+        .replace(['–', '—'], "-")
+        .replace("a.m.", "am")
+        .replace("p.m.", "pm")
+        .replace(['?', '!', ','], " ");
+
+    let mut tokens: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        // Keep ISO dates whole, split ranges like "2-4pm" around the dash
+        let parts: Vec<&str> = if NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok() {
+            vec![word]
+        } else {
+            word.split_inclusive('-')
+                .flat_map(|part| match part.strip_suffix('-') {
+                    Some(before) => vec![before, "-"],
+                    None => vec![part],
+                })
+                .filter(|part| !part.is_empty())
+                .collect()
+        };
+
+        for part in parts {
+            let trimmed = part.trim_matches('.');
+            // "2 pm" -> "2pm"
+            if matches!(trimmed, "am" | "pm") {
+                if let Some(last) = tokens.last_mut() {
+                    if last.chars().next().map_or(false, |c| c.is_ascii_digit()) {
+                        last.push_str(trimmed);
+                        continue;
+                    }
+                }
+            }
+            if !trimmed.is_empty() && !FILLER_WORDS.contains(&trimmed) {
+                tokens.push(trimmed.to_string());
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser<Tz: TimeZone> {
+    tz: Tz,
+    now: NaiveDateTime,
+}
+
+type Span = (NaiveDateTime, NaiveDateTime);
+
+impl<Tz: TimeZone> Parser<Tz> {
+    fn today(&self) -> NaiveDate {
+        self.now.date()
+    }
+
+    fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        self.tz
+            .from_local_datetime(&local)
+            .earliest()
+            // Skipped by a daylight saving jump; the wall clock resumes an hour later
+            .or_else(|| {
+                self.tz
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    }
+
+    fn parse(&self, tokens: &[String]) -> Option<Span> {
+        let words: Vec<&str> = tokens.iter().map(|t| t.as_str()).collect();
+
+        if let Some(span) = self.parse_preset(&words) {
+            return Some(span);
+        }
+        if words[0] == "since" {
+            let start = self.parse_point(&words[1..])?;
+            return Some((start, self.now));
+        }
+        if let Some(span) = self.parse_relative(&words) {
+            return Some(span);
+        }
+        self.parse_day_span(&words)
+    }
+
+    fn parse_preset(&self, words: &[&str]) -> Option<Span> {
+        let rolling = |duration: Duration| Some((self.now - duration, self.now));
+        match words.join(" ").as_str() {
+            "daily" => rolling(Duration::days(1)),
+            "weekly" => rolling(Duration::days(7)),
+            "monthly" => rolling(Duration::days(30)),
+            "yearly" => rolling(Duration::days(365)),
+            "all_time" | "all time" | "alltime" | "all" => {
+                Some((DateTime::UNIX_EPOCH.naive_utc(), self.now))
+            }
+            _ => None,
+        }
+    }
+
+    // "past 90 minutes", "last hour", "last week", "this month"
+    fn parse_relative(&self, words: &[&str]) -> Option<Span> {
+        let (qualifier, rest) = words.split_first()?;
+        if !matches!(*qualifier, "past" | "last" | "previous" | "this") {
+            return None;
+        }
+
+        let (count, unit) = match rest {
+            [count, unit] => (count.parse::<i64>().ok().filter(|&count| count > 0)?, *unit),
+            [unit] => (1, *unit),
+            _ => return None,
+        };
+        let unit = unit.trim_end_matches('s');
+        let counted = rest.len() == 2;
+
+        // Calendar periods: "this week" so far, "last week" as the previous full week
+        if !counted && matches!(*qualifier, "this" | "last" | "previous") {
+            let this_start = match unit {
+                "week" => {
+                    let days_since_monday = self.today().weekday().num_days_from_monday() as i64;
+                    Some(self.today() - Duration::days(days_since_monday))
+                }
+                "month" => self.today().with_day(1),
+                "year" => NaiveDate::from_ymd_opt(self.today().year(), 1, 1),
+                _ => None,
+            };
+            if let Some(this_start) = this_start {
+                let this_start = this_start.and_time(NaiveTime::MIN);
+                if *qualifier == "this" {
+                    return Some((this_start, self.now));
+                }
+                let previous_start = match unit {
+                    "week" => this_start - Duration::days(7),
+                    "month" => {
+                        let last_day = this_start.date() - Duration::days(1);
+                        last_day.with_day(1)?.and_time(NaiveTime::MIN)
+                    }
+                    _ => NaiveDate::from_ymd_opt(this_start.year() - 1, 1, 1)?
+                        .and_time(NaiveTime::MIN),
+                };
+                return Some((previous_start, this_start));
+            }
+        }
+        if *qualifier == "this" {
+            return None;
+        }
+
+        // Counts come from the user, so anything out of chrono's range is not a window
+        let duration = match unit {
+            "minute" | "min" => Duration::try_minutes(count),
+            "hour" | "hr" => Duration::try_hours(count),
+            "day" => Duration::try_days(count),
+            "week" => Duration::try_weeks(count),
+            "month" => count.checked_mul(30).and_then(Duration::try_days),
+            "year" => count.checked_mul(365).and_then(Duration::try_days),
+            _ => return None,
+        }?;
+        Some((self.now.checked_sub_signed(duration)?, self.now))
+    }
+
+    // Start of a "since ..." range: a day ("Monday"), a time ("3pm") or both
+    fn parse_point(&self, words: &[&str]) -> Option<NaiveDateTime> {
+        let (day, rest) = self.parse_day(words);
+        match rest.as_slice() {
+            [] => day.map(|day| day.and_time(NaiveTime::MIN)),
+            [time] => {
+                let clock = parse_clock(time)?;
+                Some(day.unwrap_or_else(|| self.today()).and_time(clock.time()))
+            }
+            _ => None,
+        }
+    }
+
+    // A day, optionally narrowed by a part of the day or a clock range
+    fn parse_day_span(&self, words: &[&str]) -> Option<Span> {
+        let (day, rest) = self.parse_day(words);
+        let mut part = None;
+        let mut clock_range = None;
+
+        let mut i = 0;
+        while i < rest.len() {
+            if let Some(span) = part_of_day(rest[i]) {
+                part = Some(span);
+                i += 1;
+            } else if let Some((range, used)) = parse_clock_range(&rest[i..]) {
+                clock_range = Some(range);
+                i += used;
+            } else {
+                // Anything unrecognized means this is not a time expression we understand
+                return None;
+            }
+        }
+
+        if day.is_none() && part.is_none() && clock_range.is_none() {
+            return None;
+        }
+        let day = day.unwrap_or_else(|| self.today());
+
+        if let Some((from, to)) = clock_range {
+            let start = day.and_time(from);
+            let mut end = day.and_time(to);
+            if end <= start {
+                end += Duration::days(1);
+            }
+            return Some((start, end));
+        }
+        if let Some((from_hour, to_hour)) = part {
+            let start = day.and_time(NaiveTime::MIN) + Duration::hours(from_hour);
+            let end = day.and_time(NaiveTime::MIN) + Duration::hours(to_hour);
+            return Some((start, end));
+        }
+        let start = day.and_time(NaiveTime::MIN);
+        Some((start, start + Duration::days(1)))
+    }
+
+    /// Find the day the words refer to and return the words that are left
+    fn parse_day<'w>(&self, words: &[&'w str]) -> (Option<NaiveDate>, Vec<&'w str>) {
+        let today = self.today();
+        let mut day = None;
+        let mut rest = Vec::new();
+
+        let mut i = 0;
+        while i < words.len() {
+            let word = words[i];
+            let next = words.get(i + 1).copied();
+
+            if day.is_none() {
+                let found = match (word, next) {
+                    ("today", _) => Some((today, 1)),
+                    ("yesterday", _) => Some((today - Duration::days(1), 1)),
+                    ("tonight", _) => {
+                        rest.push("evening");
+                        Some((today, 1))
+                    }
+                    ("last", Some("night")) => {
+                        rest.push("overnight");
+                        Some((today - Duration::days(1), 2))
+                    }
+                    ("this", Some(part)) if part_of_day(part).is_some() => Some((today, 1)),
+                    ("last", Some(name)) => parse_weekday(name)
+                        .map(|weekday| (previous_weekday(today - Duration::days(1), weekday), 2)),
+                    ("this", Some(name)) => {
+                        parse_weekday(name).map(|weekday| (previous_weekday(today, weekday), 2))
+                    }
+                    (count, Some("days")) | (count, Some("day"))
+                        if words.get(i + 2) == Some(&"ago") =>
+                    {
+                        count
+                            .parse::<i64>()
+                            .ok()
+                            .and_then(Duration::try_days)
+                            .and_then(|ago| today.checked_sub_signed(ago))
+                            .map(|day| (day, 3))
+                    }
+                    (name, _) => parse_weekday(name)
+                        .map(|weekday| (previous_weekday(today, weekday), 1))
+                        .or_else(|| {
+                            NaiveDate::parse_from_str(name, "%Y-%m-%d")
+                                .ok()
+                                .map(|date| (date, 1))
+                        }),
+                };
+
+                if let Some((date, used)) = found {
+                    day = Some(date);
+                    i += used;
+                    continue;
+                }
+            }
+
+            rest.push(word);
+            i += 1;
+        }
+
+        (day, rest)
+    }
+}
+
+// Hours relative to the start of the day
+fn part_of_day(word: &str) -> Option<(i64, i64)> {
+    match word {
+        "morning" => Some((5, 12)),
+        "noon" | "midday" | "lunchtime" => Some((11, 14)),
+        "afternoon" => Some((12, 17)),
+        "evening" => Some((17, 22)),
+        "night" => Some((21, 29)),
+        // "last night": from the evening until the next morning
+        "overnight" => Some((18, 30)),
+        _ => None,
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thur" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// Most recent `weekday` on or before `from`
+fn previous_weekday(from: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days_back = (7 + from.weekday().num_days_from_monday() as i64
+        - weekday.num_days_from_monday() as i64)
+        % 7;
+    from - Duration::days(days_back)
+}
+
+fn parse_clock(word: &str) -> Option<Clock> {
+    match word {
+        "noon" | "midday" => {
+            return Some(Clock {
+                hour: 12,
+                minute: 0,
+                meridiem: Some(Meridiem::Pm),
+            })
+        }
+        "midnight" => {
+            return Some(Clock {
+                hour: 12,
+                minute: 0,
+                meridiem: Some(Meridiem::Am),
+            })
+        }
+        _ => {}
+    }
+
+    let (digits, meridiem) = if let Some(digits) = word.strip_suffix("am") {
+        (digits, Some(Meridiem::Am))
+    } else if let Some(digits) = word.strip_suffix("pm") {
+        (digits, Some(Meridiem::Pm))
+    } else {
+        (word, None)
+    };
+
+    let (hour, minute) = match digits.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (digits.parse::<u32>().ok()?, 0),
+    };
+    let max_hour = if meridiem.is_some() { 12 } else { 23 };
+    if hour > max_hour || minute > 59 || (meridiem.is_some() && hour == 0) {
+        return None;
+    }
+    Some(Clock {
+        hour,
+        minute,
+        meridiem,
+    })
+}
+
+// "2-4pm", "2pm to 4pm", "14:00 until 16:30", or a single "3pm" meaning that hour.
+// Returns the range and how many words it used.
+fn parse_clock_range(words: &[&str]) -> Option<((NaiveTime, NaiveTime), usize)> {
+    if let [first, separator, second, ..] = words {
+        if RANGE_SEPARATORS.contains(separator) {
+            if let (Some(mut from), Some(to)) = (parse_clock(first), parse_clock(second)) {
+                // "2-4pm": the first time shares the second one's am/pm unless that
+                // would put it after the end ("11-1pm" is 11am to 1pm)
+                if from.meridiem.is_none() && to.meridiem.is_some() && from.hour <= 12 {
+                    let shared = Clock {
+                        meridiem: to.meridiem,
+                        ..from
+                    };
+                    if shared.time() <= to.time() {
+                        from = shared;
+                    } else {
+                        from.meridiem = Some(Meridiem::Am);
+                    }
+                }
+                return Some(((from.time(), to.time()), 3));
+            }
+        }
+    }
+
+    // A lone number is too ambiguous; a single time needs am/pm or minutes
+    let first = words.first()?;
+    let explicit = first.ends_with("am")
+        || first.ends_with("pm")
+        || first.contains(':')
+        || matches!(*first, "noon" | "midnight" | "midday");
+    if !explicit {
+        return None;
+    }
+    let clock = parse_clock(first)?;
+    let start = clock.time();
+    Some(((start, start + Duration::hours(1)), 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    const NOW: &str = "2024-05-15 10:30"; // a Wednesday
+
+    // (now, expression, expected start, expected end), all in UTC
+    const CASES: &[(&str, &str, &str, &str)] = &[
+        (NOW, "last 3 days", "2024-05-12 10:30", "2024-05-15 10:30"),
+        (
+            NOW,
+            "the past 90 minutes",
+            "2024-05-15 09:00",
+            "2024-05-15 10:30",
+        ),
+        (NOW, "last hour", "2024-05-15 09:30", "2024-05-15 10:30"),
+        (NOW, "yesterday", "2024-05-14 00:00", "2024-05-15 00:00"),
+        (
+            NOW,
+            "yesterday afternoon",
+            "2024-05-14 12:00",
+            "2024-05-14 17:00",
+        ),
+        (NOW, "last night", "2024-05-14 18:00", "2024-05-15 06:00"),
+        (NOW, "3 days ago", "2024-05-12 00:00", "2024-05-13 00:00"),
+        (NOW, "Monday", "2024-05-13 00:00", "2024-05-14 00:00"),
+        (
+            NOW,
+            "on Friday morning",
+            "2024-05-10 05:00",
+            "2024-05-10 12:00",
+        ),
+        (
+            NOW,
+            "last Tuesday 2-4pm",
+            "2024-05-14 14:00",
+            "2024-05-14 16:00",
+        ),
+        (NOW, "since Monday", "2024-05-13 00:00", "2024-05-15 10:30"),
+        (
+            NOW,
+            "between 11 and 1pm",
+            "2024-05-15 11:00",
+            "2024-05-15 13:00",
+        ),
+        (NOW, "this week", "2024-05-13 00:00", "2024-05-15 10:30"),
+        (NOW, "last week", "2024-05-06 00:00", "2024-05-13 00:00"),
+        (NOW, "this month", "2024-05-01 00:00", "2024-05-15 10:30"),
+        (NOW, "weekly", "2024-05-08 10:30", "2024-05-15 10:30"),
+        (
+            NOW,
+            "2024-05-01 9am to 5pm",
+            "2024-05-01 09:00",
+            "2024-05-01 17:00",
+        ),
+        (
+            NOW,
+            "2024-05-01T09:00:00Z/2024-05-01T17:00:00Z",
+            "2024-05-01 09:00",
+            "2024-05-01 17:00",
+        ),
+        (
+            NOW,
+            "f.timestamp > datetime('now', '-7 days')",
+            "2024-05-08 10:30",
+            "2024-05-15 10:30",
+        ),
+        // Month and year rollover
+        (
+            "2024-01-10 12:00",
+            "last month",
+            "2023-12-01 00:00",
+            "2024-01-01 00:00",
+        ),
+        (
+            "2024-01-10 12:00",
+            "last year",
+            "2023-01-01 00:00",
+            "2024-01-01 00:00",
+        ),
+        (
+            "2024-01-03 12:00",
+            "last week",
+            "2023-12-25 00:00",
+            "2024-01-01 00:00",
+        ),
+        (
+            "2024-01-02 12:00",
+            "last Sunday",
+            "2023-12-31 00:00",
+            "2024-01-01 00:00",
+        ),
+        (
+            "2024-01-02 12:00",
+            "3 days ago",
+            "2023-12-30 00:00",
+            "2023-12-31 00:00",
+        ),
+        (
+            "2024-03-01 08:00",
+            "yesterday",
+            "2024-02-29 00:00",
+            "2024-03-01 00:00",
+        ),
+    ];
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    #[test]
+    fn parses_supported_forms() {
+        for (now, expression, start, end) in CASES {
+            let window = parse_time_expression_at(expression, utc(now))
+                .unwrap_or_else(|e| panic!("{}: {}", expression, e));
+            assert_eq!(
+                (window.start, window.end),
+                (utc(start), utc(end)),
+                "{} at {}",
+                expression,
+                now
+            );
+        }
+    }
+
+    #[test]
+    fn takes_days_and_times_in_the_callers_timezone() {
+        let now = utc(NOW).with_timezone(&FixedOffset::east_opt(2 * 3600).unwrap());
+        let window = parse_time_expression_at("yesterday afternoon", now).unwrap();
+        assert_eq!(window.start, utc("2024-05-14 10:00"));
+        assert_eq!(window.end, utc("2024-05-14 15:00"));
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for expression in [
+            "",
+            "banana",
+            "sometime soon",
+            "last 3 fortnights",
+            "tuesday 25pm",
+            "1=1",
+            "f.timestamp > 0",
+            "f.timestamp > datetime('now', '-1 day') OR 1=1",
+            "f.timestamp > datetime('now', '-1 day'); DROP TABLE frames",
+            "f.timestamp > datetime('now', '-1 fortnight')",
+            "past 0 days",
+            "past -5 days",
+            "past 1000000 years",
+            "last 100000000 days",
+            "past 9223372036854775807 minutes",
+            "99999999 days ago",
+            "f.timestamp > datetime('now', '-99999999999 days')",
+        ] {
+            assert!(
+                parse_time_expression_at(expression, utc(NOW)).is_err(),
+                "'{}' should not parse",
+                expression
+            );
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::budget::BudgetReport;
+use crate::prompts::TemplateUse;
+use crate::rag::{DataChunk, RAGFilters, RetrievalMode};
+
+const HISTORY_FILE_NAME: &str = "query_history.json";
+// Oldest traces are dropped beyond this
+const MAX_HISTORY_ENTRIES: usize = 200;
+const PREVIEW_CHARS: usize = 200;
+
+// Loaded from disk on first use, newest trace last
+static HISTORY: Lazy<Mutex<Option<VecDeque<QueryTrace>>>> = Lazy::new(|| Mutex::new(None));
+
+/// How one query was answered: what was searched, how every selected chunk scored,
+/// what fit in the prompt and how long each step took
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryTrace {
+    pub id: String,
+    pub created_at: String,
+    pub query: String,
+    pub collection: Option<String>,
+    pub retrieval_mode: RetrievalMode,
+    pub rerank: bool,
+    pub top_k: usize,
+    pub similarity_threshold: f32,
+    pub filters: RAGFilters,
+    pub total_chunks: usize,
+    /// Chunks left after the filters; None when no filter was set
+    pub candidates: Option<usize>,
+    /// Terms the lexical search ran with; empty when it did not run
+    pub keywords: Vec<String>,
+    pub semantic_matches: Option<usize>,
+    pub lexical_matches: Option<usize>,
+    /// Selected chunks in rank order
+    pub chunks: Vec<TraceChunk>,
+    /// Prompt size and model of the final prompt
+    pub budget: Option<BudgetReport>,
+    /// Provider and model that wrote the answer
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Prompt templates and versions the answer was built from
+    #[serde(default)]
+    pub templates: Vec<TemplateUse>,
+    pub retrieval_ms: u64,
+    pub generation_ms: u64,
+    pub total_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TraceChunk {
+    pub id: String,
+    pub source_type: String,
+    pub app_name: Option<String>,
+    pub timestamp: Option<String>,
+    /// Score the chunk was ranked by (fused, or from the reranker)
+    pub score: f32,
+    pub semantic_score: Option<f32>,
+    pub lexical_score: Option<f32>,
+    /// Whether it fit in the prompt
+    pub included: bool,
+    pub preview: String,
+}
+
+/// History listing entry without the per-chunk details
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryTraceSummary {
+    pub id: String,
+    pub created_at: String,
+    pub query: String,
+    pub collection: Option<String>,
+    pub model: Option<String>,
+    pub chunks: usize,
+    pub total_ms: u64,
+}
+
+impl QueryTrace {
+    pub fn new(query: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            query: query.to_string(),
+            collection: None,
+            retrieval_mode: RetrievalMode::default(),
+            rerank: false,
+            top_k: 0,
+            similarity_threshold: 0.0,
+            filters: RAGFilters::default(),
+            total_chunks: 0,
+            candidates: None,
+            keywords: Vec::new(),
+            semantic_matches: None,
+            lexical_matches: None,
+            chunks: Vec::new(),
+            budget: None,
+            provider: None,
+            model: None,
+            templates: Vec::new(),
+            retrieval_ms: 0,
+            generation_ms: 0,
+            total_ms: 0,
+        }
+    }
+
+    /// Mark which traced chunks made it into the prompt described by `budget`
+    pub fn set_budget(&mut self, budget: BudgetReport) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.included = chunk.included && !budget.dropped_ids.contains(&chunk.id);
+        }
+        self.budget = Some(budget);
+    }
+
+    pub fn summary(&self) -> QueryTraceSummary {
+        QueryTraceSummary {
+            id: self.id.clone(),
+            created_at: self.created_at.clone(),
+            query: self.query.clone(),
+            collection: self.collection.clone(),
+            model: self.budget.as_ref().map(|b| b.model.clone()),
+            chunks: self.chunks.len(),
+            total_ms: self.total_ms,
+        }
+    }
+}
+
+impl TraceChunk {
+    pub fn new(
+        chunk: &DataChunk,
+        score: f32,
+        semantic_score: Option<f32>,
+        lexical_score: Option<f32>,
+    ) -> Self {
+        Self {
+            id: chunk.id.clone(),
+            source_type: chunk.metadata.source_type.clone(),
+            app_name: chunk.metadata.app_name.clone(),
+            timestamp: chunk.metadata.timestamp.clone(),
+            score,
+            semantic_score,
+            lexical_score,
+            included: true,
+            preview: chunk.content.chars().take(PREVIEW_CHARS).collect(),
+        }
+    }
+}
+
+/// Add a trace to the query history, replacing an earlier version with the same id.
+/// Failing to persist it is logged, never returned: tracing must not fail a query.
+pub fn record(trace: &QueryTrace) {
+    let mut history = HISTORY.lock().unwrap();
+    let entries = history.get_or_insert_with(load_history);
+
+    entries.retain(|t| t.id != trace.id);
+    entries.push_back(trace.clone());
+    while entries.len() > MAX_HISTORY_ENTRIES {
+        entries.pop_front();
+    }
+
+    if let Err(e) = save_history(entries) {
+        println!("[TRACE] Failed to save query history: {}", e);
+    }
+}
+
+/// Past queries, newest first
+pub fn list_traces(limit: Option<usize>) -> Vec<QueryTraceSummary> {
+    let mut history = HISTORY.lock().unwrap();
+    let entries = history.get_or_insert_with(load_history);
+    entries
+        .iter()
+        .rev()
+        .take(limit.unwrap_or(MAX_HISTORY_ENTRIES))
+        .map(QueryTrace::summary)
+        .collect()
+}
+
+pub fn get_trace(id: &str) -> Result<QueryTrace, String> {
+    let mut history = HISTORY.lock().unwrap();
+    let entries = history.get_or_insert_with(load_history);
+    entries
+        .iter()
+        .find(|t| t.id == id)
+        .cloned()
+        .ok_or_else(|| format!("No query trace with id {}", id))
+}
+
+pub fn clear_traces() -> Result<(), String> {
+    let mut history = HISTORY.lock().unwrap();
+    let entries = history.get_or_insert_with(VecDeque::new);
+    entries.clear();
+    save_history(entries)
+}
+
+fn history_path() -> Result<PathBuf, String> {
+    Ok(crate::paths::get_app_data_dir()?.join(HISTORY_FILE_NAME))
+}
+
+fn load_history() -> VecDeque<QueryTrace> {
+    let content = match history_path().and_then(|path| {
+        fs::read_to_string(path).map_err(|e| format!("Failed to read query history: {}", e))
+    }) {
+        Ok(content) => content,
+        // No history yet
+        Err(_) => return VecDeque::new(),
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        println!("[TRACE] Ignoring unreadable query history: {}", e);
+        VecDeque::new()
+    })
+}
+
+fn save_history(entries: &VecDeque<QueryTrace>) -> Result<(), String> {
+    let content = serde_json::to_string(entries)
+        .map_err(|e| format!("Failed to serialize query history: {}", e))?;
+    fs::write(history_path()?, content).map_err(|e| format!("Failed to write query history: {}", e))
+}
@@ -110,6 +110,8 @@ pub struct OpenAIRequest {
     pub messages: Vec<OpenAIMessage>,
     pub max_tokens: u32,
     pub temperature: f32,
+    #[serde(default)]
+    pub stream: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -128,6 +130,24 @@ pub struct OpenAIChoice {
     pub message: OpenAIMessage,
 }
 
+// One server-sent event of a streamed chat completion
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OpenAIStreamChunk {
+    pub choices: Vec<OpenAIStreamChoice>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OpenAIStreamChoice {
+    #[serde(default)]
+    pub delta: OpenAIDelta,
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct OpenAIDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
 // New structures for app discovery
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SystemApp {